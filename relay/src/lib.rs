@@ -0,0 +1,367 @@
+// Relay server for online play.
+// It doesn't run the game at all: it only seats clients in rooms and
+// forwards their inputs to each other. Clients are trusted to simulate
+// the rest on their own.
+// Spectators get the inputs too. For those joining late, the room keeps
+// the last snapshot from the host plus every input sent since then.
+
+extern crate ws;
+extern crate brickbreak;
+
+use std::rc::Rc;
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+use brickbreak::net::{Message, SessionConfig};
+use brickbreak::net::lockstep::SNAPSHOT_INTERVAL;
+
+// Every online mode is played by two
+const SEATS: usize = 2;
+
+// Most inputs kept for late spectators. Normally the next snapshot
+// trims them long before this; if the host stops sending snapshots,
+// spectators joining now just wait for the next one instead.
+const HISTORY_LIMIT: usize = 2 * SNAPSHOT_INTERVAL as usize * SEATS;
+
+struct Room {
+    seats:      Vec<Option<ws::Sender>>,
+    spectators: Vec<ws::Sender>,
+    config:     Option<SessionConfig>,
+    started:    bool,
+    // What a late spectator needs to catch up: the last snapshot, and
+    // every input from its tick on, with the tick they are for
+    snapshot:   Option<String>,
+    snapshot_tick: u64,
+    history:    Vec<(u64, String)>,
+}
+
+impl Room {
+    fn new() -> Room {
+        Room {
+            seats:      vec![None; SEATS],
+            spectators: vec![],
+            config:     None,
+            started:    false,
+            snapshot:   None,
+            snapshot_tick: 0,
+            history:    vec![],
+        }
+    }
+
+    fn is_full(&self) -> bool {
+        self.seats.iter().all(|seat| seat.is_some())
+    }
+
+    fn is_empty(&self) -> bool {
+        self.seats.iter().all(|seat| seat.is_none())
+    }
+
+    // Sends a message to everyone but the given seat
+    fn broadcast(&self, from: Option<usize>, text: &str) {
+        for (i, seat) in self.seats.iter().enumerate() {
+            if Some(i) == from {
+                continue;
+            }
+            if let Some(ref out) = *seat {
+                let _ = out.send(text);
+            }
+        }
+    }
+
+    fn broadcast_spectators(&self, text: &str) {
+        for out in &self.spectators {
+            let _ = out.send(text);
+        }
+    }
+
+    // Brings a spectator up to date
+    fn catch_up(&self, out: &ws::Sender) {
+        if !self.started {
+            return;
+        }
+        if let Some(ref config) = self.config {
+            let _ = out.send(Message::Start(config.clone()).to_string());
+        }
+        if let Some(ref snapshot) = self.snapshot {
+            let _ = out.send(snapshot.as_str());
+        }
+        for &(_, ref input) in &self.history {
+            let _ = out.send(input.as_str());
+        }
+    }
+
+    // Once everyone is seated and the host has said how to play, go
+    fn try_start(&mut self) {
+        if self.started || !self.is_full() {
+            return;
+        }
+        let start = match self.config {
+            Some(ref config) => Message::Start(config.clone()).to_string(),
+            None => return,
+        };
+        self.started = true;
+        self.broadcast(None, &start);
+        self.broadcast_spectators(&start);
+    }
+
+    // A match can't go on with a seat empty. Whoever takes it next plays
+    // a new one, once the host has configured it again.
+    fn end_match(&mut self) {
+        self.started = false;
+        self.config = None;
+        self.snapshot = None;
+        self.snapshot_tick = 0;
+        self.history.clear();
+    }
+}
+
+
+
+pub struct Relay {
+    rooms: HashMap<String, Room>,
+}
+
+impl Relay {
+    pub fn new() -> Relay {
+        Relay {
+            rooms: HashMap::new(),
+        }
+    }
+
+    fn join(&mut self, name: &str, out: &ws::Sender) -> Result<usize, String> {
+        let room = self.rooms.entry(name.to_string()).or_insert_with(Room::new);
+        if room.started {
+            return Err(format!("room {} is already playing", name));
+        }
+
+        let seat = match room.seats.iter().position(|seat| seat.is_none()) {
+            Some(seat) => seat,
+            None => return Err(format!("room {} is full", name)),
+        };
+        room.seats[seat] = Some(out.clone());
+        Ok(seat)
+    }
+
+    fn watch(&mut self, name: &str, out: &ws::Sender) {
+        // Spectators may show up before the players do
+        let room = self.rooms.entry(name.to_string()).or_insert_with(Room::new);
+        room.catch_up(out);
+        room.spectators.push(out.clone());
+    }
+
+    fn configure(&mut self, name: &str, seat: usize, config: SessionConfig) -> Result<(), String> {
+        let room = match self.rooms.get_mut(name) {
+            Some(room) => room,
+            None => return Err("not in a room".to_string()),
+        };
+        if seat != 0 {
+            return Err("only the host may configure the room".to_string());
+        }
+        if config.players() != SEATS {
+            return Err(format!("{} can't be played online", config.mode.name()));
+        }
+
+        room.config = Some(config);
+        room.try_start();
+        Ok(())
+    }
+
+    fn forward(&self, name: &str, seat: usize, text: &str) {
+        if let Some(room) = self.rooms.get(name) {
+            if room.started {
+                room.broadcast(Some(seat), text);
+            }
+        }
+    }
+
+    fn forward_input(&mut self, name: &str, seat: usize, tick: u64, text: &str) {
+        if let Some(room) = self.rooms.get_mut(name) {
+            if room.started {
+                room.broadcast(Some(seat), text);
+                room.broadcast_spectators(text);
+
+                // Inputs from before the snapshot are already part of it
+                if tick >= room.snapshot_tick {
+                    room.history.push((tick, text.to_string()));
+                }
+                if room.history.len() > HISTORY_LIMIT {
+                    let excess = room.history.len() - HISTORY_LIMIT;
+                    room.history.drain(..excess);
+                }
+            }
+        }
+    }
+
+    fn share_snapshot(&mut self, name: &str, seat: usize, tick: u64, text: &str) -> Result<(), String> {
+        let room = match self.rooms.get_mut(name) {
+            Some(room) => room,
+            None => return Err("not in a room".to_string()),
+        };
+        if seat != 0 {
+            return Err("only the host may send snapshots".to_string());
+        }
+        if !room.started {
+            return Ok(());
+        }
+
+        // Inputs for earlier ticks are already part of the snapshot. The
+        // ones for later ticks may have been sent before it, so keep them.
+        room.history.retain(|&(input_tick, _)| input_tick >= tick);
+        room.snapshot = Some(text.to_string());
+        room.snapshot_tick = tick;
+        room.broadcast_spectators(text);
+        Ok(())
+    }
+
+    fn leave(&mut self, name: &str, seat: usize) {
+        let empty = match self.rooms.get_mut(name) {
+            Some(room) => {
+                let leave = Message::Leave(seat).to_string();
+                room.seats[seat] = None;
+                room.broadcast(None, &leave);
+                room.broadcast_spectators(&leave);
+                room.end_match();
+                // Spectators stay on for whatever is played next
+                room.is_empty() && room.spectators.is_empty()
+            },
+            None => false,
+        };
+
+        if empty {
+            self.rooms.remove(name);
+        }
+    }
+
+    fn stop_watching(&mut self, name: &str, out: &ws::Sender) {
+        let empty = match self.rooms.get_mut(name) {
+            Some(room) => {
+                let id = out.connection_id();
+                room.spectators.retain(|spectator| spectator.connection_id() != id);
+                room.is_empty() && room.spectators.is_empty()
+            },
+            None => false,
+        };
+
+        if empty {
+            self.rooms.remove(name);
+        }
+    }
+}
+
+
+
+struct Connection {
+    out:   ws::Sender,
+    relay: Rc<RefCell<Relay>>,
+    // Room name and seat, once joined
+    seat:  Option<(String, usize)>,
+    // Room being watched, for spectators
+    watching: Option<String>,
+}
+
+impl Connection {
+    fn handle(&mut self, text: &str, message: Message) -> Result<(), String> {
+        match message {
+            Message::Join(room) => {
+                if self.seat.is_some() || self.watching.is_some() {
+                    return Err("already in a room".to_string());
+                }
+                let seat = self.relay.borrow_mut().join(&room, &self.out)?;
+                self.seat = Some((room.clone(), seat));
+                let _ = self.out.send(Message::Welcome(seat).to_string());
+
+                // The host may have configured the room already
+                if let Some(room) = self.relay.borrow_mut().rooms.get_mut(&room) {
+                    room.try_start();
+                }
+                Ok(())
+            },
+            Message::Watch(room) => {
+                if self.seat.is_some() || self.watching.is_some() {
+                    return Err("already in a room".to_string());
+                }
+                self.relay.borrow_mut().watch(&room, &self.out);
+                self.watching = Some(room);
+                Ok(())
+            },
+            Message::Config(config) => {
+                let (room, seat) = self.seated()?;
+                self.relay.borrow_mut().configure(&room, seat, config)
+            },
+            Message::Input(player, tick, _) => {
+                let (room, seat) = self.speaking_for(player)?;
+                self.relay.borrow_mut().forward_input(&room, seat, tick, text);
+                Ok(())
+            },
+            Message::Hash(player, _, _) => {
+                let (room, seat) = self.speaking_for(player)?;
+                self.relay.borrow().forward(&room, seat, text);
+                Ok(())
+            },
+            Message::Snapshot(snapshot) => {
+                let (room, seat) = self.seated()?;
+                self.relay.borrow_mut().share_snapshot(&room, seat, snapshot.tick, text)
+            },
+            _ => Err(format!("unexpected message: {}", text)),
+        }
+    }
+
+    fn seated(&self) -> Result<(String, usize), String> {
+        match self.seat {
+            Some((ref room, seat)) => Ok((room.clone(), seat)),
+            None => Err("not in a room".to_string()),
+        }
+    }
+
+    // No speaking on behalf of others
+    fn speaking_for(&self, player: usize) -> Result<(String, usize), String> {
+        let (room, seat) = self.seated()?;
+        if player != seat {
+            return Err(format!("seat {} can't send for player {}", seat, player));
+        }
+        Ok((room, seat))
+    }
+}
+
+impl ws::Handler for Connection {
+    fn on_message(&mut self, msg: ws::Message) -> ws::Result<()> {
+        let text = match msg.as_text() {
+            Ok(text) => text.to_string(),
+            Err(_) => return Ok(()),
+        };
+
+        let result = match Message::parse(&text) {
+            Ok(message) => self.handle(&text, message),
+            Err(reason) => Err(reason),
+        };
+
+        if let Err(reason) = result {
+            self.out.send(Message::Error(reason).to_string())?;
+        }
+        Ok(())
+    }
+
+    fn on_close(&mut self, _: ws::CloseCode, _: &str) {
+        if let Some((room, seat)) = self.seat.take() {
+            self.relay.borrow_mut().leave(&room, seat);
+        }
+        if let Some(room) = self.watching.take() {
+            self.relay.borrow_mut().stop_watching(&room, &self.out);
+        }
+    }
+}
+
+
+
+// Serves forever on the given address, e.g. "127.0.0.1:3012".
+pub fn serve(address: &str) -> ws::Result<()> {
+    let relay = Rc::new(RefCell::new(Relay::new()));
+    ws::listen(address, |out| {
+        Connection {
+            out:   out,
+            relay: relay.clone(),
+            seat:  None,
+            watching: None,
+        }
+    })
+}
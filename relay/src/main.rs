@@ -0,0 +1,14 @@
+extern crate relay;
+
+use std::env;
+
+// Usage: brickbreak-relay [address]
+fn main() {
+    let address = env::args().nth(1).unwrap_or("127.0.0.1:3012".to_string());
+    println!("Relay listening on ws://{}", address);
+
+    if let Err(error) = relay::serve(&address) {
+        eprintln!("Relay failed: {}", error);
+        ::std::process::exit(1);
+    }
+}
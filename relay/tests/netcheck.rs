@@ -0,0 +1,344 @@
+// Scripted online match between two headless clients.
+//
+// Starts a relay, connects two bots to it and lets them play a versus
+// match in lockstep for a fixed number of ticks. A spectator shows up
+// after a few snapshots, so it has to start off the latest one. All
+// three must end up on the same game state, otherwise the simulation
+// isn't deterministic (or the protocol is broken).
+//
+// Bots don't wait for a clock: they play as soon as the other one's
+// inputs arrive, so a long match only takes a few seconds.
+
+extern crate ws;
+extern crate relay;
+extern crate brickbreak;
+
+use std::thread;
+use std::sync::Arc;
+use std::sync::mpsc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use ws::util::Token;
+
+use brickbreak::sim::{Game, GameMode, PlayerInput, Rng};
+use brickbreak::net::{Lockstep, Message, SessionConfig, Spectator};
+use brickbreak::net::lockstep::SNAPSHOT_INTERVAL;
+
+const ADDRESS: &str = "127.0.0.1:3013";
+const TICKS: u64 = 3600;
+
+// The host waits here for the spectator, halfway between two snapshots
+const LATE_JOIN: u64 = 2 * SNAPSHOT_INTERVAL + SNAPSHOT_INTERVAL / 2;
+const RESUME: Token = Token(1);
+
+struct Report {
+    who:      String,
+    tick:     u64,
+    checksum: u64,
+    points:   [u32; 2],
+    desync:   Option<u64>,
+    // Tick of the snapshot the spectator started off
+    restored: Option<u64>,
+}
+
+// Lets the host hold on until the spectator has caught up
+struct LateJoin {
+    reached: mpsc::Sender<()>,
+    watched: Arc<AtomicBool>,
+    waiting: bool,
+}
+
+struct Bot {
+    out:     ws::Sender,
+    ticks:   u64,
+    rng:     Rng,
+    player:  Option<usize>,
+    game:    Option<Game>,
+    session: Option<Lockstep>,
+    done:    bool,
+    report:  mpsc::Sender<Report>,
+    late:    Option<LateJoin>,
+}
+
+impl Bot {
+    // Whether to stop at this tick until the spectator is there. Only
+    // the host waits; the other player can't go on without it anyway.
+    fn hold(&mut self, tick: u64) -> bool {
+        if self.player != Some(0) {
+            return false;
+        }
+        let late = match self.late {
+            Some(ref mut late) => late,
+            None => return false,
+        };
+        if tick < LATE_JOIN || late.watched.load(Ordering::SeqCst) {
+            return false;
+        }
+
+        if !late.waiting {
+            late.waiting = true;
+            let _ = late.reached.send(());
+        }
+        let _ = self.out.timeout(1, RESUME);
+        true
+    }
+
+    // Follows the ball, with a little noise so players don't mirror
+    // each other and the inputs actually matter
+    fn think(&mut self, game: &Game, player: usize) -> PlayerInput {
+        let mut input = PlayerInput::new();
+        let paddle = &game.paddles[player];
+        let target = game.ball_state.pos.0 + self.rng.range(-0.4, 0.4) * paddle.sz.0;
+        let distance = target - paddle.xpos;
+
+        input.left = distance < -paddle.basespd;
+        input.right = distance > paddle.basespd;
+        input.boost = distance.abs() > paddle.sz.0;
+        input.launch = self.rng.next_f32() < 0.05;
+        input
+    }
+
+    // Plays every tick we have the inputs for. Runs whenever something
+    // arrives, since that's the only thing that lets us go on.
+    fn pump(&mut self) {
+        let (mut game, mut session) = match (self.game.take(), self.session.take()) {
+            (Some(game), Some(session)) => (game, session),
+            _ => return,
+        };
+
+        while session.tick < self.ticks {
+            if self.hold(session.tick) {
+                break;
+            }
+            let input = self.think(&game, session.player);
+            session.submit(input);
+            if !session.advance(&mut game) {
+                break;
+            }
+        }
+        for message in session.drain_outbox() {
+            let _ = self.out.send(message.to_string());
+        }
+
+        // Keep the connection open when done; the other bot may still
+        // need our inputs, and leaving would stall it
+        if session.tick >= self.ticks && !self.done {
+            self.done = true;
+            let _ = self.report.send(Report {
+                who:      format!("player {}", session.player),
+                tick:     session.tick,
+                checksum: game.checksum(),
+                points:   game.versus.points,
+                desync:   session.desync,
+                restored: None,
+            });
+        }
+
+        self.game = Some(game);
+        self.session = Some(session);
+    }
+}
+
+impl ws::Handler for Bot {
+    fn on_open(&mut self, _: ws::Handshake) -> ws::Result<()> {
+        self.out.send(Message::Join("netcheck".to_string()).to_string())
+    }
+
+    fn on_message(&mut self, msg: ws::Message) -> ws::Result<()> {
+        let message = match Message::parse(msg.as_text()?) {
+            Ok(message) => message,
+            Err(reason) => panic!("bad message from relay: {}", reason),
+        };
+
+        match message {
+            Message::Welcome(player) => {
+                self.player = Some(player);
+                if player == 0 {
+                    let config = SessionConfig {
+                        seed:  0x5EED,
+                        mode:  GameMode::Versus,
+                        field: (1280.0, 720.0),
+                    };
+                    self.out.send(Message::Config(config).to_string())?;
+                }
+            },
+            Message::Start(config) => {
+                let player = self.player.expect("started before being seated");
+                self.game = Some(config.create_game());
+                self.session = Some(Lockstep::new(player, &config));
+                self.pump();
+            },
+            Message::Error(reason) => panic!("relay error: {}", reason),
+            message => {
+                if let Some(ref mut session) = self.session {
+                    session.receive(&message);
+                }
+                self.pump();
+            },
+        }
+        Ok(())
+    }
+
+    fn on_timeout(&mut self, event: Token) -> ws::Result<()> {
+        if event == RESUME {
+            self.pump();
+        }
+        Ok(())
+    }
+}
+
+
+
+// Watches the match and reports once it has seen as many ticks as the
+// players. It runs as fast as inputs come in.
+struct Watcher {
+    out:       ws::Sender,
+    ticks:     u64,
+    game:      Option<Game>,
+    spectator: Option<Spectator>,
+    done:      bool,
+    report:    mpsc::Sender<Report>,
+    restored:  Option<u64>,
+    watched:   Arc<AtomicBool>,
+}
+
+impl ws::Handler for Watcher {
+    fn on_open(&mut self, _: ws::Handshake) -> ws::Result<()> {
+        self.out.send(Message::Watch("netcheck".to_string()).to_string())
+    }
+
+    fn on_message(&mut self, msg: ws::Message) -> ws::Result<()> {
+        let message = match Message::parse(msg.as_text()?) {
+            Ok(message) => message,
+            Err(reason) => panic!("bad message from relay: {}", reason),
+        };
+
+        match message {
+            Message::Start(config) => {
+                self.game = Some(config.create_game());
+                self.spectator = Some(Spectator::new(&config));
+            },
+            Message::Error(reason) => panic!("relay error: {}", reason),
+            message => {
+                if let (Some(game), Some(spectator)) = (self.game.as_mut(), self.spectator.as_mut()) {
+                    if let Message::Snapshot(ref snapshot) = message {
+                        if snapshot.tick > spectator.tick && self.restored.is_none() {
+                            self.restored = Some(snapshot.tick);
+                        }
+                    }
+                    spectator.receive(game, &message);
+                    // The snapshot comes right before the inputs since
+                    // then, so by now we have everything we need
+                    if self.restored.is_some() {
+                        self.watched.store(true, Ordering::SeqCst);
+                    }
+                    while spectator.tick < self.ticks && spectator.advance(game) {}
+
+                    if spectator.tick >= self.ticks && !self.done {
+                        self.done = true;
+                        let _ = self.report.send(Report {
+                            who:      "spectator".to_string(),
+                            tick:     spectator.tick,
+                            checksum: game.checksum(),
+                            points:   game.versus.points,
+                            desync:   None,
+                            restored: self.restored,
+                        });
+                    }
+                }
+            },
+        }
+        Ok(())
+    }
+}
+
+
+
+#[test]
+fn clients_stay_in_lockstep() {
+    thread::spawn(|| {
+        relay::serve(ADDRESS).expect("relay failed");
+    });
+    // Give the relay some time to bind
+    thread::sleep(Duration::from_millis(250));
+
+    let (report, reports) = mpsc::channel();
+    let (reached, late_join) = mpsc::channel();
+    let watched = Arc::new(AtomicBool::new(false));
+    for bot in 0..2 {
+        let report = report.clone();
+        let reached = reached.clone();
+        let watched = watched.clone();
+        thread::spawn(move || {
+            ws::connect(format!("ws://{}", ADDRESS), |out| {
+                Bot {
+                    out:     out,
+                    ticks:   TICKS,
+                    rng:     Rng::new(bot),
+                    player:  None,
+                    game:    None,
+                    session: None,
+                    done:    false,
+                    report:  report.clone(),
+                    // Seats go in order of joining, so we don't know
+                    // yet which bot hosts
+                    late:    Some(LateJoin {
+                        reached: reached.clone(),
+                        watched: watched.clone(),
+                        waiting: false,
+                    }),
+                }
+            }).expect("bot could not connect");
+        });
+    }
+
+    if late_join.recv_timeout(Duration::from_secs(60)).is_err() {
+        panic!("match never got to tick {}", LATE_JOIN);
+    }
+    {
+        let report = report.clone();
+        let watched = watched.clone();
+        thread::spawn(move || {
+            ws::connect(format!("ws://{}", ADDRESS), |out| {
+                Watcher {
+                    out:       out,
+                    ticks:     TICKS,
+                    game:      None,
+                    spectator: None,
+                    done:      false,
+                    report:    report.clone(),
+                    restored:  None,
+                    watched:   watched.clone(),
+                }
+            }).expect("spectator could not connect");
+        });
+    }
+
+    let mut results = vec![];
+    for _ in 0..3 {
+        match reports.recv_timeout(Duration::from_secs(60)) {
+            Ok(result) => results.push(result),
+            Err(_) => panic!("match did not finish in time"),
+        }
+    }
+
+    for result in &results {
+        println!("{}: tick {}, checksum {:016x}, points {:?}",
+                 result.who, result.tick, result.checksum, result.points);
+        if let Some(tick) = result.restored {
+            println!("{}: started off the snapshot at tick {}", result.who, tick);
+        }
+        assert_eq!(result.desync, None, "{} saw a desync", result.who);
+        assert_eq!(result.tick, TICKS);
+    }
+    assert!(results.iter().all(|result| result.checksum == results[0].checksum),
+            "final checksums differ");
+
+    // It must have joined between snapshots, not at the very end
+    let restored = results.iter()
+        .filter_map(|result| result.restored)
+        .next()
+        .expect("spectator didn't start off a snapshot");
+    assert!(restored > 0 && restored <= LATE_JOIN, "restored at tick {}", restored);
+}
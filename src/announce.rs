@@ -0,0 +1,106 @@
+// Spoken announcements, for players who can't see the screen.
+// Like the audio queue, this turns each tick's events into requests,
+// and whoever runs the game passes them on to assistive technology.
+
+use sim::{Game, GameEvent, GameMode, Effect};
+use locale::Locale;
+
+pub struct Announcer {
+    pending: Vec<String>,
+    // Level announced last, to tell when another one starts
+    level:   Option<usize>,
+    tick:    u64,
+}
+
+// Spelled out, unlike on the HUD
+fn effect_name(locale: &Locale, effect: Effect) -> &str {
+    locale.text(match effect {
+        Effect::Wide      => "announce.wide",
+        Effect::Slow      => "announce.slow",
+        Effect::ExtraLife => "announce.life",
+    })
+}
+
+impl Announcer {
+    pub fn new() -> Announcer {
+        Announcer {
+            pending: vec![],
+            level:   None,
+            tick:    0,
+        }
+    }
+
+    pub fn announce(&mut self, text: &str) {
+        self.pending.push(text.to_string());
+    }
+
+    // Call after the game was stepped, with every event it had since
+    // the last call
+    pub fn feed(&mut self, game: &Game, events: &[GameEvent], locale: &Locale) {
+        // Starting over (or loading a game) takes the clock back
+        if game.tick < self.tick {
+            self.level = None;
+        }
+        self.tick = game.tick;
+
+        let score = game.score.to_string();
+        for event in events {
+            match *event {
+                GameEvent::LevelCleared { level } => {
+                    let text = locale.format("announce.level_cleared",
+                                             &[("level", &(level + 1).to_string()), ("score", &score)]);
+                    self.announce(&text);
+                },
+                GameEvent::BallLost { .. } => {
+                    let text = if game.mode == GameMode::Versus {
+                        let points = game.versus.points;
+                        match game.versus.winner {
+                            Some(winner) => locale.format("announce.winner", &[
+                                ("player", &(winner + 1).to_string()),
+                                ("points", &points[winner].to_string()),
+                                ("other", &points[1 - winner].to_string()),
+                            ]),
+                            None => locale.format("announce.goal", &[
+                                ("first", &points[0].to_string()),
+                                ("second", &points[1].to_string()),
+                            ]),
+                        }
+                    } else if game.lives == 0 {
+                        locale.format("announce.game_over", &[("score", &score)])
+                    } else if game.lives == 1 {
+                        locale.format("announce.last_life", &[("score", &score)])
+                    } else {
+                        locale.format("announce.ball_lost",
+                                      &[("lives", &game.lives.to_string()), ("score", &score)])
+                    };
+                    self.announce(&text);
+                },
+                GameEvent::PowerUpCaught { effect, player, .. } => {
+                    let effect = effect_name(locale, effect);
+                    let text = if game.paddles.len() > 1 {
+                        locale.format("announce.power_up_player",
+                                      &[("player", &(player + 1).to_string()), ("effect", effect)])
+                    } else {
+                        locale.format("announce.power_up", &[("effect", effect)])
+                    };
+                    self.announce(&text);
+                },
+                _ => {},
+            }
+        }
+
+        // Versus plays on a single field, so only the rest get told
+        if game.mode != GameMode::Versus && self.level != Some(game.level) {
+            self.level = Some(game.level);
+            let text = locale.format("announce.level", &[
+                ("level", &(game.level + 1).to_string()),
+                ("name", &game.levels.get(game.level).name),
+            ]);
+            self.announce(&text);
+        }
+    }
+
+    pub fn drain(&mut self) -> Vec<String> {
+        self.pending.drain(..).collect()
+    }
+}
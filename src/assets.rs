@@ -0,0 +1,206 @@
+// Asset manifest.
+// Lists everything the game needs before it can start, and keeps track
+// of how loading it all is going. The actual loading is up to whoever
+// runs the game, which in the browser means images, font faces and
+// fetch() calls reporting back here.
+
+use sim::LevelSet;
+
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum AssetKind {
+    Image,
+    Font,
+    Sound,
+    // Built-in level set, checked but never fetched
+    Levels,
+}
+
+impl AssetKind {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            AssetKind::Image  => "image",
+            AssetKind::Font   => "font",
+            AssetKind::Sound  => "sound",
+            AssetKind::Levels => "levels",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<AssetKind> {
+        match name {
+            "image"  => Some(AssetKind::Image),
+            "font"   => Some(AssetKind::Font),
+            "sound"  => Some(AssetKind::Sound),
+            "levels" => Some(AssetKind::Levels),
+            _ => None,
+        }
+    }
+}
+
+#[derive(PartialEq, Clone, Debug)]
+pub struct Asset {
+    pub kind: AssetKind,
+    pub name: String,
+    // Relative to the page. Empty for level sets.
+    pub path: String,
+}
+
+// Something to draw, taken from an image. Many sprites can share one
+// image (an atlas), each from its own region of it.
+#[derive(PartialEq, Clone, Debug)]
+pub struct SpriteDef {
+    pub name:   String,
+    pub image:  String,
+    // (x, y, width, height), or the whole image when missing
+    pub region: Option<(u32, u32, u32, u32)>,
+}
+
+// As written on the manifest file:
+//
+//     # Comments start with a hash
+//     image  atlas    ./sprites.png
+//     sprite ball     atlas 0 0 64 64
+//     sprite paddle   atlas 0 64 256 32
+//     font   GohuFont ./gohufont-11.ttf
+//     sound  bounce   ./bounce.wav
+//     levels CLASSIC
+#[derive(PartialEq, Clone, Debug)]
+pub struct Manifest {
+    pub assets:  Vec<Asset>,
+    pub sprites: Vec<SpriteDef>,
+}
+
+impl Manifest {
+    pub fn parse(text: &str) -> Result<Manifest, String> {
+        let mut manifest = Manifest {
+            assets:  vec![],
+            sprites: vec![],
+        };
+
+        for (number, line) in text.lines().enumerate() {
+            let number = number + 1;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            if fields[0] == "sprite" {
+                let region = match fields.len() {
+                    3 => None,
+                    7 => {
+                        let mut numbers = vec![];
+                        for field in &fields[3..] {
+                            numbers.push(field.parse::<u32>()
+                                .map_err(|_| format!("line {}: invalid region {}", number, field))?);
+                        }
+                        Some((numbers[0], numbers[1], numbers[2], numbers[3]))
+                    },
+                    _ => return Err(format!("line {}: expected sprite <name> <image> [x y w h]", number)),
+                };
+                if manifest.sprite(fields[1]).is_some() {
+                    return Err(format!("line {}: sprite {} listed twice", number, fields[1]));
+                }
+                if manifest.asset(AssetKind::Image, fields[2]).is_none() {
+                    return Err(format!("line {}: unknown image {}", number, fields[2]));
+                }
+                manifest.sprites.push(SpriteDef {
+                    name:   fields[1].to_string(),
+                    image:  fields[2].to_string(),
+                    region: region,
+                });
+                continue;
+            }
+
+            let kind = AssetKind::from_name(fields[0])
+                .ok_or_else(|| format!("line {}: unknown asset kind {}", number, fields[0]))?;
+            let expected = if kind == AssetKind::Levels { 2 } else { 3 };
+            if fields.len() != expected {
+                return Err(format!("line {}: expected {} <name>{}", number, kind.name(),
+                                   if expected == 3 { " <path>" } else { "" }));
+            }
+            if manifest.asset(kind, fields[1]).is_some() {
+                return Err(format!("line {}: {} {} listed twice", number, kind.name(), fields[1]));
+            }
+            manifest.assets.push(Asset {
+                kind: kind,
+                name: fields[1].to_string(),
+                path: fields.get(2).map_or(String::new(), |path| path.to_string()),
+            });
+        }
+
+        Ok(manifest)
+    }
+
+    pub fn asset(&self, kind: AssetKind, name: &str) -> Option<&Asset> {
+        self.assets.iter().find(|asset| asset.kind == kind && asset.name == name)
+    }
+
+    pub fn sprite(&self, name: &str) -> Option<&SpriteDef> {
+        self.sprites.iter().find(|sprite| sprite.name == name)
+    }
+}
+
+
+
+#[derive(PartialEq, Clone, Debug)]
+pub enum LoadState {
+    Pending,
+    Loaded,
+    Failed(String),
+}
+
+// How far along loading a manifest is. Entries follow the order of
+// the manifest's assets.
+pub struct Loading {
+    pub states: Vec<LoadState>,
+}
+
+impl Loading {
+    // Level sets are compiled in, so they're checked right away
+    pub fn new(manifest: &Manifest) -> Loading {
+        Loading {
+            states: manifest.assets.iter().map(|asset| {
+                if asset.kind != AssetKind::Levels {
+                    return LoadState::Pending;
+                }
+                match LevelSet::by_name(&asset.name) {
+                    Some(_) => LoadState::Loaded,
+                    None => LoadState::Failed("no such level set".to_string()),
+                }
+            }).collect(),
+        }
+    }
+
+    // Anything but the first report on an asset is ignored
+    pub fn finish(&mut self, index: usize, result: Result<(), String>) {
+        if let Some(state) = self.states.get_mut(index) {
+            if *state == LoadState::Pending {
+                *state = match result {
+                    Ok(())      => LoadState::Loaded,
+                    Err(reason) => LoadState::Failed(reason),
+                };
+            }
+        }
+    }
+
+    // From 0.0 to 1.0. Failed assets count as done.
+    pub fn progress(&self) -> f32 {
+        if self.states.is_empty() {
+            return 1.0;
+        }
+        let done = self.states.iter().filter(|state| **state != LoadState::Pending).count();
+        done as f32 / self.states.len() as f32
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.states.iter().all(|state| *state != LoadState::Pending)
+    }
+
+    // (index, reason) of everything that failed
+    pub fn errors(&self) -> Vec<(usize, &str)> {
+        self.states.iter().enumerate().filter_map(|(i, state)| match *state {
+            LoadState::Failed(ref reason) => Some((i, reason.as_ref())),
+            _ => None,
+        }).collect()
+    }
+}
@@ -0,0 +1,132 @@
+// What everything sounds like. Each effect and music track is a small
+// Track for the synthesizer, rendered whenever it's needed.
+
+use audio::{Sound, MUSIC_TRACKS};
+use audio::synth::{Track, Instrument, Envelope, Wave};
+
+// Fixed, so every render comes out exactly the same
+const NOISE_SEED: u64 = 0xB10C;
+
+fn blip(duty: f32) -> Instrument {
+    Instrument::new(Wave::Square(duty), Envelope::new(0.002, 0.03, 0.4, 0.03), 0.35)
+}
+
+fn lead() -> Instrument {
+    Instrument::new(Wave::Square(0.25), Envelope::new(0.005, 0.08, 0.5, 0.05), 0.18)
+}
+
+fn bass() -> Instrument {
+    Instrument::new(Wave::Triangle, Envelope::new(0.005, 0.05, 0.8, 0.03), 0.3)
+}
+
+fn hat() -> Instrument {
+    Instrument::new(Wave::Noise, Envelope::new(0.001, 0.03, 0.0, 0.01), 0.12)
+}
+
+fn sound_track(sound: Sound) -> Track {
+    match sound {
+        Sound::PaddleHit => Track::new(600.0)
+            .voice(blip(0.5), "A4:0.5"),
+        Sound::WallHit => Track::new(600.0)
+            .voice(Instrument::new(Wave::Triangle, Envelope::new(0.002, 0.02, 0.5, 0.02), 0.5), "E4:0.3"),
+        Sound::BlockHit => Track::new(600.0)
+            .voice(blip(0.25), "E5:0.4"),
+        Sound::SolidHit => Track::new(600.0)
+            .voice(Instrument::new(Wave::Noise, Envelope::new(0.001, 0.04, 0.2, 0.03), 0.3), "A6:0.5")
+            .voice(Instrument::new(Wave::Triangle, Envelope::new(0.001, 0.04, 0.3, 0.03), 0.4), "A3:0.5"),
+        Sound::BlockBreak => Track::new(600.0)
+            .voice(blip(0.5).with_slide(3000.0), "A5:0.8"),
+        Sound::PowerUpBreak => Track::new(900.0)
+            .voice(blip(0.25), "C6:0.5 E6:0.5 G6:0.5 B6:1"),
+        Sound::BallLost => Track::new(120.0)
+            .voice(Instrument::new(Wave::Saw, Envelope::new(0.005, 0.1, 0.7, 0.2), 0.3)
+                   .with_slide(-250.0), "A3:0.8"),
+        Sound::PowerUp => Track::new(720.0)
+            .voice(blip(0.5), "C6 E6 G6 C7:2"),
+        Sound::ExtraLife => Track::new(600.0)
+            .voice(blip(0.25), "G5 C6 E6 G6 E6 G6:3"),
+        Sound::LevelClear => Track::new(300.0)
+            .voice(lead(), "C5 E5 G5 C6:2 G5 C6:4")
+            .voice(bass(), "C3:2 G3:2 C3:4"),
+    }
+}
+
+// Four bars of melody over a bass line, with hats on every beat
+fn music_track(name: &str) -> Option<Track> {
+    let hats = "C8:0.5 C8:0.5 C8:0.5 C8:0.5 C8:0.5 C8:0.5 C8:0.5 C8:0.5 \
+                C8:0.5 C8:0.5 C8:0.5 C8:0.5 C8:0.5 C8:0.5 C8:0.5 C8:0.5 \
+                C8:0.5 C8:0.5 C8:0.5 C8:0.5 C8:0.5 C8:0.5 C8:0.5 C8:0.5 \
+                C8:0.5 C8:0.5 C8:0.5 C8:0.5 C8:0.5 C8:0.5 C8:0.5 C8:0.5";
+
+    let (tempo, melody, bass_line) = match name {
+        "music-1" => (140.0,
+            "E5 G5 A5:2 G5 E5 D5:2 C5 D5 E5 G5 E5:2 -:2",
+            "A2:2 A3:2 F2:2 F3:2 C3:2 C4:2 G2:2 G3:2"),
+        "music-2" => (150.0,
+            "C5:0.5 D5:0.5 E5 G5 E5 D5:0.5 C5:0.5 D5 A4:2 C5 D5 E5 D5 C5:2 -:2",
+            "C3:2 G2:2 A2:2 E2:2 F2:2 C3:2 G2:4"),
+        "music-3" => (128.0,
+            "A4 C5 E5:2 D5 C5 B4:2 A4 B4 C5 E5 D5:4",
+            "A2:2 E3:2 G2:2 D3:2 F2:2 C3:2 E2:2 E3:2"),
+        "music-4" => (160.0,
+            "D5 F5 A5 F5 D5 F5 A5:2 G5 E5 C5 E5 G5:2 F5 E5",
+            "D3:2 D3:2 C3:2 C3:2 Bb2:2 Bb2:2 A2:2 A2:2"),
+        "music-versus" => (170.0,
+            "E5:0.5 E5:0.5 - E5 - C5:0.5 E5:1.5 G5:2 G4:2 C5 G4 E4:2 A4 Bb4:0.5 A4:0.5",
+            "E2:2 E3:2 C3:2 G2:2 C3:2 G2:2 A2:2 A2:2"),
+        _ => return None,
+    };
+
+    Some(Track::new(tempo)
+         .voice(lead(), melody)
+         .voice(bass(), bass_line)
+         .voice(hat(), hats))
+}
+
+// Every music track there is, by name
+pub fn music_names() -> Vec<String> {
+    let mut names: Vec<String> = (0..MUSIC_TRACKS).map(|level| format!("music-{}", level + 1)).collect();
+    names.push("music-versus".to_string());
+    names
+}
+
+pub fn render_sound(sound: Sound) -> Vec<f32> {
+    sound_track(sound).render(false, NOISE_SEED)
+}
+
+// Music loops, and comes out seamless
+pub fn render_music(name: &str) -> Option<Vec<f32>> {
+    music_track(name).map(|track| track.render(true, NOISE_SEED))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use audio::ALL_SOUNDS;
+    use audio::synth::SAMPLE_RATE;
+
+    #[test]
+    fn sounds_render_the_same_every_time() {
+        for &sound in ALL_SOUNDS.iter() {
+            let samples = render_sound(sound);
+            assert!(!samples.is_empty(), "{} is silent", sound.name());
+            assert_eq!(samples, render_sound(sound), "{} changes", sound.name());
+        }
+    }
+
+    #[test]
+    fn music_loops_without_a_seam() {
+        for name in music_names() {
+            let track = music_track(&name).unwrap();
+            let looped = render_music(&name).unwrap();
+            let once = track.render(false, NOISE_SEED);
+
+            let length = (track.length * (60.0 / track.tempo) * SAMPLE_RATE as f32) as usize;
+            assert_eq!(looped.len(), length, "{} has the wrong length", name);
+            for (i, &sample) in looped.iter().enumerate() {
+                let tail = once.get(length + i).cloned().unwrap_or(0.0);
+                assert_eq!(sample, (once[i] + tail).max(-1.0).min(1.0), "{} has a seam", name);
+            }
+        }
+    }
+}
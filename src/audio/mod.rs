@@ -0,0 +1,90 @@
+// Sound.
+// Gameplay never plays anything by itself: each tick's events are
+// turned into requests on an AudioQueue, and whoever runs the game plays
+// them. That keeps the simulation headless, and lets native tools check
+// exactly what a tick sounded like.
+//
+// The sounds themselves come from a small synthesizer, so they can be
+// rendered anywhere, down to WAV files on the command line.
+
+pub mod queue;
+pub mod synth;
+pub mod bank;
+pub mod wav;
+
+pub use self::queue::{AudioQueue, AudioEvent};
+
+// Music loops through these tracks as levels go by
+pub const MUSIC_TRACKS: usize = 4;
+
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug)]
+pub enum Channel {
+    Music,
+    Effects,
+}
+
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug)]
+pub enum Sound {
+    PaddleHit,
+    WallHit,
+    // Tough block which still has some hits left
+    BlockHit,
+    SolidHit,
+    BlockBreak,
+    PowerUpBreak,
+    BallLost,
+    PowerUp,
+    ExtraLife,
+    LevelClear,
+}
+
+pub const ALL_SOUNDS: [Sound; 10] = [
+    Sound::PaddleHit,
+    Sound::WallHit,
+    Sound::BlockHit,
+    Sound::SolidHit,
+    Sound::BlockBreak,
+    Sound::PowerUpBreak,
+    Sound::BallLost,
+    Sound::PowerUp,
+    Sound::ExtraLife,
+    Sound::LevelClear,
+];
+
+impl Sound {
+    // Also the name it goes by on the asset manifest
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Sound::PaddleHit    => "paddle",
+            Sound::WallHit      => "wall",
+            Sound::BlockHit     => "block-hit",
+            Sound::SolidHit     => "solid-hit",
+            Sound::BlockBreak   => "block-break",
+            Sound::PowerUpBreak => "powerup-break",
+            Sound::BallLost     => "ball-lost",
+            Sound::PowerUp      => "powerup",
+            Sound::ExtraLife    => "extra-life",
+            Sound::LevelClear   => "level-clear",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Sound> {
+        ALL_SOUNDS.iter().find(|sound| sound.name() == name).cloned()
+    }
+}
+
+// Name of the music track for a level. Versus has one of its own.
+pub fn music_for_level(level: usize, versus: bool) -> String {
+    if versus {
+        "music-versus".to_string()
+    } else {
+        format!("music-{}", (level % MUSIC_TRACKS) + 1)
+    }
+}
+
+// Turns a volume setting (0 to 100) into a gain. Ears hear loudness
+// roughly on a log scale, so the slider gets squared.
+pub fn gain(volume: u32) -> f32 {
+    let volume = volume.min(100) as f32 / 100.0;
+    volume * volume
+}
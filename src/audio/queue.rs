@@ -0,0 +1,189 @@
+use sim::{Game, GameEvent, GameMode, BlockKind, Effect};
+use audio::{Sound, music_for_level};
+
+#[derive(PartialEq, Clone, Debug)]
+pub enum AudioEvent {
+    Play(Sound),
+    // Switch to another looping track, or stop the music
+    Music(Option<String>),
+}
+
+// Sounds requested since the queue was last drained
+pub struct AudioQueue {
+    events: Vec<AudioEvent>,
+    music:  Option<String>,
+}
+
+// What a single game event sounds like, if anything
+pub fn sound_for(event: &GameEvent) -> Option<Sound> {
+    match *event {
+        GameEvent::PaddleHit { .. } => Some(Sound::PaddleHit),
+        GameEvent::WallHit { .. } => Some(Sound::WallHit),
+        GameEvent::BlockHit { kind: BlockKind::Solid, .. } => Some(Sound::SolidHit),
+        GameEvent::BlockHit { .. } => Some(Sound::BlockHit),
+        GameEvent::BlockBroken { kind: BlockKind::PowerUp(_), .. } => Some(Sound::PowerUpBreak),
+        GameEvent::BlockBroken { .. } => Some(Sound::BlockBreak),
+        GameEvent::BallLost { .. } => Some(Sound::BallLost),
+        GameEvent::PowerUpCaught { effect: Effect::ExtraLife, .. } => Some(Sound::ExtraLife),
+        GameEvent::PowerUpCaught { .. } => Some(Sound::PowerUp),
+        GameEvent::LevelCleared { .. } => Some(Sound::LevelClear),
+    }
+}
+
+impl AudioQueue {
+    pub fn new() -> AudioQueue {
+        AudioQueue {
+            events: vec![],
+            music:  None,
+        }
+    }
+
+    // Queues the sounds of every event the game had since the last call,
+    // and picks the music for the level being played
+    pub fn feed(&mut self, game: &Game, events: &[GameEvent]) {
+        for event in events {
+            if let Some(sound) = sound_for(event) {
+                // Many blocks breaking at once still make one sound
+                let queued = AudioEvent::Play(sound);
+                if !self.events.contains(&queued) {
+                    self.events.push(queued);
+                }
+            }
+        }
+
+        let track = music_for_level(game.level, game.mode == GameMode::Versus);
+        self.set_music(Some(track));
+    }
+
+    pub fn play(&mut self, sound: Sound) {
+        self.events.push(AudioEvent::Play(sound));
+    }
+
+    // Only queued when it actually changes
+    pub fn set_music(&mut self, track: Option<String>) {
+        if self.music != track {
+            self.music = track.clone();
+            self.events.push(AudioEvent::Music(track));
+        }
+    }
+
+    pub fn music(&self) -> Option<&str> {
+        self.music.as_ref().map(|track| track.as_ref())
+    }
+
+    pub fn pending(&self) -> &[AudioEvent] {
+        &self.events
+    }
+
+    pub fn drain(&mut self) -> Vec<AudioEvent> {
+        ::std::mem::replace(&mut self.events, vec![])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sim::{Level, LevelSet, FIELD_SIZE};
+    use audio::Sound;
+
+    // Long enough for the ball to get anywhere on the field
+    const MAX_TICKS: usize = 600;
+
+    // One level, with its grid given row by row
+    fn game_with(rows: &[&str]) -> Game {
+        let text = format!("name TEST\nkey 1 normal 1 #FFF\nkey w wide 1 #4F4\ngrid\n{}\n", rows.join("\n"));
+        let levels = LevelSet {
+            name:   "TEST".to_string(),
+            levels: vec![Level::parse(&text).unwrap()],
+        };
+        Game::with_levels(GameMode::Single, FIELD_SIZE, 0, levels)
+    }
+
+    // Sends the ball straight up or down from somewhere, and returns the
+    // sounds of the first tick anything happened on
+    fn first_sounds(game: &mut Game, pos: (f32, f32), down: bool) -> Vec<Sound> {
+        let speed = game.ball_speed();
+        game.ball_state.stopped = false;
+        game.ball_state.pos = pos;
+        game.ball_state.spd = (0.0, if down { speed } else { -speed });
+
+        let mut queue = AudioQueue::new();
+        for _ in 0..MAX_TICKS {
+            game.step(&[]);
+            if !game.events.is_empty() {
+                queue.feed(game, &game.events);
+                return queue.drain().into_iter().filter_map(|event| match event {
+                    AudioEvent::Play(sound) => Some(sound),
+                    AudioEvent::Music(_) => None,
+                }).collect();
+            }
+        }
+        panic!("nothing happened");
+    }
+
+    // Straight up from some way under a block
+    fn under(game: &Game, cell: (usize, usize)) -> (f32, f32) {
+        let pos = game.cell_position(cell);
+        (pos.0, pos.1 + (game.block_size.1 * 3.0))
+    }
+
+    #[test]
+    fn paddle_hits() {
+        let mut game = game_with(&["1"]);
+        let pos = (game.paddles[0].xpos, game.paddles[0].ypos - 30.0);
+        assert_eq!(first_sounds(&mut game, pos, true), vec![Sound::PaddleHit]);
+    }
+
+    #[test]
+    fn block_breaks() {
+        let mut game = game_with(&["w", ".", ".", ".", ".", ".", ".", ".", ".", ".......1"]);
+        let pos = under(&game, (7, 9));
+        assert_eq!(first_sounds(&mut game, pos, false), vec![Sound::BlockBreak]);
+    }
+
+    #[test]
+    fn power_up_blocks_break() {
+        let mut game = game_with(&["1", ".", ".", ".", ".", ".", ".", ".", ".", ".......w"]);
+        let pos = under(&game, (7, 9));
+        assert_eq!(first_sounds(&mut game, pos, false), vec![Sound::PowerUpBreak]);
+    }
+
+    #[test]
+    fn balls_get_lost() {
+        let mut game = game_with(&["1"]);
+        let pos = (40.0, game.field.1 - 40.0);
+        assert_eq!(first_sounds(&mut game, pos, true), vec![Sound::BallLost]);
+    }
+
+    #[test]
+    fn many_blocks_make_one_sound() {
+        let game = game_with(&["1"]);
+        let broken = GameEvent::BlockBroken { pos: (0.0, 0.0), kind: BlockKind::Normal, color: "#FFF".to_string() };
+        let mut queue = AudioQueue::new();
+        queue.feed(&game, &[broken.clone(), broken.clone(), broken]);
+        let plays = queue.drain().into_iter().filter(|event| *event == AudioEvent::Play(Sound::BlockBreak)).count();
+        assert_eq!(plays, 1);
+    }
+
+    #[test]
+    fn music_is_queued_on_change_only() {
+        let mut queue = AudioQueue::new();
+        queue.set_music(Some("one".to_string()));
+        queue.set_music(Some("one".to_string()));
+        queue.set_music(Some("two".to_string()));
+        queue.set_music(None);
+        queue.set_music(None);
+        assert_eq!(queue.drain(), vec![
+            AudioEvent::Music(Some("one".to_string())),
+            AudioEvent::Music(Some("two".to_string())),
+            AudioEvent::Music(None),
+        ]);
+        assert_eq!(queue.music(), None);
+
+        // Feeding the same level over and over keeps the same track
+        let game = game_with(&["1"]);
+        queue.feed(&game, &[]);
+        queue.feed(&game, &[]);
+        assert_eq!(queue.drain(), vec![AudioEvent::Music(Some(music_for_level(0, false)))]);
+    }
+}
@@ -0,0 +1,291 @@
+// Tiny chiptune synthesizer.
+// Everything the game plays is rendered from code into mono PCM
+// buffers, in [-1.0, 1.0]. Nothing here depends on the browser, so
+// sounds come out exactly the same when rendered natively.
+
+use std::f32::consts::PI;
+
+use sim::Rng;
+
+pub const SAMPLE_RATE: u32 = 22050;
+
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum Wave {
+    // With its duty cycle, from 0.0 to 1.0
+    Square(f32),
+    Triangle,
+    Saw,
+    Sine,
+    Noise,
+}
+
+// Volume over the life of a note, in seconds. The note is held for
+// attack, decay and sustain, then fades out during release.
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub struct Envelope {
+    pub attack:  f32,
+    pub decay:   f32,
+    // Level held after decaying, from 0.0 to 1.0
+    pub sustain: f32,
+    pub release: f32,
+}
+
+impl Envelope {
+    pub fn new(attack: f32, decay: f32, sustain: f32, release: f32) -> Envelope {
+        Envelope {
+            attack:  attack,
+            decay:   decay,
+            sustain: sustain,
+            release: release,
+        }
+    }
+
+    // Level at time `t` of a note held for `held` seconds
+    pub fn level(&self, t: f32, held: f32) -> f32 {
+        let holding = |t: f32| {
+            if t < self.attack {
+                t / self.attack
+            } else if t < self.attack + self.decay {
+                1.0 - ((1.0 - self.sustain) * (t - self.attack) / self.decay)
+            } else {
+                self.sustain
+            }
+        };
+
+        if t < held {
+            holding(t)
+        } else if t < held + self.release {
+            holding(held) * (1.0 - ((t - held) / self.release))
+        } else {
+            0.0
+        }
+    }
+}
+
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub struct Instrument {
+    pub wave:     Wave,
+    pub envelope: Envelope,
+    pub volume:   f32,
+    // Pitch change while playing, in Hz per second
+    pub slide:    f32,
+}
+
+impl Instrument {
+    pub fn new(wave: Wave, envelope: Envelope, volume: f32) -> Instrument {
+        Instrument {
+            wave:     wave,
+            envelope: envelope,
+            volume:   volume,
+            slide:    0.0,
+        }
+    }
+
+    pub fn with_slide(mut self, slide: f32) -> Instrument {
+        self.slide = slide;
+        self
+    }
+
+    // Adds a note of the given frequency, held for `held` seconds, to
+    // the buffer, starting at sample `start`. The buffer grows to fit.
+    pub fn play(&self, buffer: &mut Vec<f32>, start: usize, frequency: f32, held: f32, rng: &mut Rng) {
+        let rate = SAMPLE_RATE as f32;
+        let length = ((held + self.envelope.release) * rate) as usize;
+        if buffer.len() < start + length {
+            buffer.resize(start + length, 0.0);
+        }
+
+        let mut phase = 0.0f32;
+        let mut noise = 0.0f32;
+        for i in 0..length {
+            let t = i as f32 / rate;
+            let frequency = (frequency + (self.slide * t)).max(0.0);
+
+            // Noise only changes value once per cycle, so pitch
+            // still means something for it
+            let last_phase = phase;
+            phase = (phase + (frequency / rate)) % 1.0;
+            if phase < last_phase || i == 0 {
+                noise = rng.range(-1.0, 1.0);
+            }
+
+            let value = match self.wave {
+                Wave::Square(duty) => if phase < duty { 1.0 } else { -1.0 },
+                Wave::Triangle => 1.0 - (4.0 * (phase - 0.5).abs()),
+                Wave::Saw => (2.0 * phase) - 1.0,
+                Wave::Sine => (2.0 * PI * phase).sin(),
+                Wave::Noise => noise,
+            };
+            buffer[start + i] += value * self.envelope.level(t, held) * self.volume;
+        }
+    }
+}
+
+
+
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub struct Note {
+    // In beats
+    pub start:  f32,
+    pub length: f32,
+    // MIDI note number. 69 is A4, at 440Hz.
+    pub pitch:  i32,
+}
+
+pub fn note_frequency(pitch: i32) -> f32 {
+    440.0 * 2.0f32.powf((pitch - 69) as f32 / 12.0)
+}
+
+// Reads notes written one after the other, as `<name><octave>:<beats>`
+// such as `C4:1`, `F#3:0.5` or `Bb5:2`. A dash is a rest, and the
+// length may be left out for one beat.
+//
+//     C4 E4 G4:2 -:0.5 C5:1.5
+pub fn parse_notes(text: &str) -> Result<Vec<Note>, String> {
+    let mut notes = vec![];
+    let mut time = 0.0;
+
+    for word in text.split_whitespace() {
+        let mut parts = word.splitn(2, ':');
+        let name = parts.next().unwrap_or("");
+        let length: f32 = match parts.next() {
+            Some(length) => length.parse()
+                .map_err(|_| format!("invalid length in {}", word))?,
+            None => 1.0,
+        };
+        if !(length > 0.0) {
+            return Err(format!("invalid length in {}", word));
+        }
+
+        if name != "-" {
+            notes.push(Note {
+                start:  time,
+                length: length,
+                pitch:  parse_pitch(name).ok_or_else(|| format!("invalid note {}", word))?,
+            });
+        }
+        time += length;
+    }
+
+    Ok(notes)
+}
+
+fn parse_pitch(name: &str) -> Option<i32> {
+    let mut chars = name.chars();
+    let base = match chars.next()? {
+        'C' => 0, 'D' => 2, 'E' => 4, 'F' => 5, 'G' => 7, 'A' => 9, 'B' => 11,
+        _ => return None,
+    };
+    let rest: String = chars.collect();
+    let (accidental, octave) = if rest.starts_with('#') {
+        (1, &rest[1..])
+    } else if rest.starts_with('b') {
+        (-1, &rest[1..])
+    } else {
+        (0, &rest[..])
+    };
+    let octave: i32 = octave.parse().ok()?;
+    Some(((octave + 1) * 12) + base + accidental)
+}
+
+
+
+// Instruments playing their notes together
+pub struct Track {
+    // Beats per minute
+    pub tempo:  f32,
+    // Total length, in beats. Notes may ring past it, unless looping.
+    pub length: f32,
+    pub voices: Vec<(Instrument, Vec<Note>)>,
+}
+
+impl Track {
+    pub fn new(tempo: f32) -> Track {
+        Track {
+            tempo:  tempo,
+            length: 0.0,
+            voices: vec![],
+        }
+    }
+
+    // Also stretches the track to fit the notes
+    pub fn voice(mut self, instrument: Instrument, notes: &str) -> Track {
+        let notes = parse_notes(notes).expect("built-in notes are broken");
+        let end = notes.iter().map(|note| note.start + note.length).fold(0.0, f32::max);
+        self.length = self.length.max(end);
+        self.voices.push((instrument, notes));
+        self
+    }
+
+    // Renders the whole thing. Looping tracks are cut at their length,
+    // with anything ringing past it folded back onto the start, so
+    // they loop without a seam.
+    pub fn render(&self, looping: bool, seed: u64) -> Vec<f32> {
+        let mut rng = Rng::new(seed);
+        let beat = 60.0 / self.tempo;
+        let mut buffer = vec![];
+
+        for &(ref instrument, ref notes) in &self.voices {
+            for note in notes {
+                let start = (note.start * beat * SAMPLE_RATE as f32) as usize;
+                instrument.play(&mut buffer, start, note_frequency(note.pitch),
+                                note.length * beat, &mut rng);
+            }
+        }
+
+        if looping {
+            let length = (self.length * beat * SAMPLE_RATE as f32) as usize;
+            if buffer.len() > length {
+                let tail: Vec<f32> = buffer.drain(length..).collect();
+                for (i, sample) in tail.into_iter().enumerate() {
+                    buffer[i % length] += sample;
+                }
+            }
+            buffer.resize(length, 0.0);
+        }
+
+        // Keep it all in range
+        for sample in &mut buffer {
+            *sample = sample.max(-1.0).min(1.0);
+        }
+        buffer
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn noisy() -> Instrument {
+        Instrument::new(Wave::Noise, Envelope::new(0.01, 0.05, 0.5, 0.25), 0.5)
+    }
+
+    #[test]
+    fn effects_render_the_same_every_time() {
+        // Half a second a beat. The last note starts 1.5s in, and goes on
+        // for its half a second plus the release.
+        let track = Track::new(120.0).voice(noisy(), "A4:2 -:1 C5:1");
+        let samples = track.render(false, 7);
+        assert_eq!(samples.len(), (1.5 * SAMPLE_RATE as f32) as usize + (0.75 * SAMPLE_RATE as f32) as usize);
+        assert_eq!(samples, track.render(false, 7));
+        assert!(samples != track.render(false, 8));
+        assert!(samples.iter().all(|sample| sample.abs() <= 1.0));
+    }
+
+    #[test]
+    fn loops_fold_back_what_rings_past_their_end() {
+        // The note rings for half a second past the end of the track
+        let track = Track::new(60.0)
+            .voice(Instrument::new(Wave::Triangle, Envelope::new(0.01, 0.05, 0.5, 0.5), 0.4), "-:1 A4:1");
+        let once = track.render(false, 7);
+        let looped = track.render(true, 7);
+
+        let length = 2 * SAMPLE_RATE as usize;
+        assert_eq!(looped.len(), length);
+        assert!(once.len() > length);
+        for (i, &sample) in looped.iter().enumerate() {
+            let tail = once.get(length + i).cloned().unwrap_or(0.0);
+            assert_eq!(sample, once[i] + tail);
+        }
+    }
+}
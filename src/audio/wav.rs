@@ -0,0 +1,77 @@
+// Mono 16-bit PCM in a WAV file, which anything can play back
+
+fn push_u16(out: &mut Vec<u8>, value: u16) {
+    out.push(value as u8);
+    out.push((value >> 8) as u8);
+}
+
+fn push_u32(out: &mut Vec<u8>, value: u32) {
+    push_u16(out, value as u16);
+    push_u16(out, (value >> 16) as u16);
+}
+
+pub fn encode(samples: &[f32], rate: u32) -> Vec<u8> {
+    let data_size = samples.len() as u32 * 2;
+    let mut out = Vec::with_capacity(44 + data_size as usize);
+
+    out.extend_from_slice(b"RIFF");
+    push_u32(&mut out, 36 + data_size);
+    out.extend_from_slice(b"WAVE");
+
+    out.extend_from_slice(b"fmt ");
+    push_u32(&mut out, 16);
+    push_u16(&mut out, 1);        // PCM
+    push_u16(&mut out, 1);        // Channels
+    push_u32(&mut out, rate);
+    push_u32(&mut out, rate * 2); // Bytes per second
+    push_u16(&mut out, 2);        // Bytes per sample
+    push_u16(&mut out, 16);       // Bits per sample
+
+    out.extend_from_slice(b"data");
+    push_u32(&mut out, data_size);
+    for &sample in samples {
+        let value = (sample.max(-1.0).min(1.0) * 32767.0) as i16;
+        push_u16(&mut out, value as u16);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn u16_at(data: &[u8], at: usize) -> u16 {
+        data[at] as u16 | ((data[at + 1] as u16) << 8)
+    }
+
+    fn u32_at(data: &[u8], at: usize) -> u32 {
+        u16_at(data, at) as u32 | ((u16_at(data, at + 2) as u32) << 16)
+    }
+
+    #[test]
+    fn header_describes_the_samples() {
+        let data = encode(&[0.0, 0.5, -1.0, 2.0, -2.0], 22050);
+        assert_eq!(data.len(), 44 + 10);
+
+        assert_eq!(&data[0..4], b"RIFF");
+        assert_eq!(u32_at(&data, 4), 36 + 10);
+        assert_eq!(&data[8..12], b"WAVE");
+        assert_eq!(&data[12..16], b"fmt ");
+        assert_eq!(u32_at(&data, 16), 16);
+        assert_eq!(u16_at(&data, 20), 1);
+        assert_eq!(u16_at(&data, 22), 1);
+        assert_eq!(u32_at(&data, 24), 22050);
+        assert_eq!(u32_at(&data, 28), 44100);
+        assert_eq!(u16_at(&data, 32), 2);
+        assert_eq!(u16_at(&data, 34), 16);
+        assert_eq!(&data[36..40], b"data");
+        assert_eq!(u32_at(&data, 40), 10);
+    }
+
+    #[test]
+    fn samples_are_clipped_to_16_bits() {
+        let data = encode(&[0.0, 0.5, -1.0, 2.0, -2.0], 22050);
+        let samples: Vec<i16> = (0..5).map(|i| u16_at(&data, 44 + (i * 2)) as i16).collect();
+        assert_eq!(samples, vec![0, 16383, -32767, 32767, -32767]);
+    }
+}
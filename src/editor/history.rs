@@ -0,0 +1,63 @@
+// Undo and redo, by keeping whole copies of whatever is being edited.
+// Levels are small enough for that to be the simplest thing that works.
+
+// Oldest steps get dropped past this many
+pub const HISTORY_LIMIT: usize = 100;
+
+pub struct History<T> {
+    undo: Vec<T>,
+    redo: Vec<T>,
+}
+
+impl<T> History<T> {
+    pub fn new() -> History<T> {
+        History {
+            undo: vec![],
+            redo: vec![],
+        }
+    }
+
+    // Call with the state from before each change. Anything undone
+    // so far can't be redone after that.
+    pub fn record(&mut self, before: T) {
+        self.undo.push(before);
+        if self.undo.len() > HISTORY_LIMIT {
+            self.undo.remove(0);
+        }
+        self.redo.clear();
+    }
+
+    // Swaps the current state for the one before it, if any
+    pub fn undo(&mut self, current: T) -> Result<T, T> {
+        match self.undo.pop() {
+            Some(before) => {
+                self.redo.push(current);
+                Ok(before)
+            },
+            None => Err(current),
+        }
+    }
+
+    pub fn redo(&mut self, current: T) -> Result<T, T> {
+        match self.redo.pop() {
+            Some(after) => {
+                self.undo.push(current);
+                Ok(after)
+            },
+            None => Err(current),
+        }
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+    }
+}
@@ -0,0 +1,344 @@
+// Level editor.
+// Holds the level being made, a brush with the block to place next,
+// and a cursor on the grid. Whoever runs the editor turns keys and
+// clicks into calls on it, and every change to the level can be undone.
+//
+// Levels can also be drawn to PNG pictures, for tools that look at
+// them outside the game.
+
+pub mod history;
+pub mod png;
+pub mod preview;
+
+pub use self::history::{History, HISTORY_LIMIT};
+
+use std::fmt::Write;
+
+use sim::{Level, LevelBlock, LevelSet, BlockKind, Effect, GRID_COLUMNS, GRID_ROWS, MAX_KEYS};
+use storage::Storage;
+
+// Where the level being made is kept between sessions
+pub const EDITOR_KEY: &'static str = "brickbreak.editor";
+
+// Levels get play-tested on a set of their own, which keeps their
+// scores off the tables
+pub const EDITOR_LEVEL_SET: &'static str = "EDITOR";
+
+pub const MAX_HP: u32 = 9;
+
+// Colors to paint normal blocks with. Levels may use any color at all,
+// but these are the ones the built-in levels stick to.
+pub const BRUSH_COLORS: [&'static str; 8] = [
+    "#F44", "#FA4", "#FF4", "#4F4", "#4FF", "#4AF", "#F4F", "#FFF",
+];
+
+pub const BRUSH_KINDS: [BlockKind; 5] = [
+    BlockKind::Normal,
+    BlockKind::Solid,
+    BlockKind::PowerUp(Effect::Wide),
+    BlockKind::PowerUp(Effect::Slow),
+    BlockKind::PowerUp(Effect::ExtraLife),
+];
+
+// Solid blocks are gray on the built-in levels
+const SOLID_COLOR: &'static str = "#888";
+
+// The block placed next
+#[derive(PartialEq, Clone, Debug)]
+pub struct Brush {
+    pub kind:  BlockKind,
+    pub hp:    u32,
+    pub color: String,
+}
+
+impl Brush {
+    pub fn new() -> Brush {
+        Brush {
+            kind:  BlockKind::Normal,
+            hp:    1,
+            color: BRUSH_COLORS[0].to_string(),
+        }
+    }
+
+    pub fn block(&self, cell: (usize, usize)) -> LevelBlock {
+        LevelBlock {
+            cell:  cell,
+            kind:  self.kind,
+            hp:    self.hp,
+            color: self.color.clone(),
+        }
+    }
+}
+
+pub struct Editor {
+    pub level:  Level,
+    pub cursor: (usize, usize),
+    pub brush:  Brush,
+    history:    History<Level>,
+    // While a stroke goes on (say, the mouse being dragged along), all
+    // its changes undo at once. Tells whether it changed anything yet.
+    stroke:     Option<bool>,
+}
+
+impl Editor {
+    pub fn new() -> Editor {
+        Editor::with_level(Level::new("UNTITLED"))
+    }
+
+    pub fn with_level(level: Level) -> Editor {
+        Editor {
+            level:   level,
+            cursor:  (GRID_COLUMNS / 2, GRID_ROWS / 2),
+            brush:   Brush::new(),
+            history: History::new(),
+            stroke:  None,
+        }
+    }
+
+    // Picks up where the last session left off
+    pub fn load(storage: &Storage) -> Result<Editor, String> {
+        match storage.load(EDITOR_KEY) {
+            Some(text) => Level::parse(&text).map(Editor::with_level),
+            None => Ok(Editor::new()),
+        }
+    }
+
+    pub fn save(&self, storage: &mut Storage) -> Result<(), String> {
+        storage.save(EDITOR_KEY, &self.export()?)
+    }
+
+    // On the level file format
+    pub fn export(&self) -> Result<String, String> {
+        let mut text = String::new();
+        write!(text, "{}", self.level)
+            .map_err(|_| format!("more than {} kinds of block", MAX_KEYS))?;
+        Ok(text)
+    }
+
+    // Replaces the level with one from a file. That can be undone too.
+    pub fn import(&mut self, text: &str) -> Result<(), String> {
+        let level = Level::parse(text)?;
+        if level.key_count() > MAX_KEYS {
+            return Err(format!("more than {} kinds of block", MAX_KEYS));
+        }
+        self.change(|current| *current = level);
+        Ok(())
+    }
+
+    // Only the one level, for play-testing it
+    pub fn level_set(&self) -> LevelSet {
+        LevelSet {
+            name:   EDITOR_LEVEL_SET.to_string(),
+            levels: vec![self.level.clone()],
+        }
+    }
+
+    // Levels with nothing to break get cleared the moment they start
+    pub fn can_play(&self) -> bool {
+        self.level.breakable_count() > 0
+    }
+
+    // Applies a change to the level, keeping what it was for undoing it.
+    // Returns whether anything changed.
+    fn change<F>(&mut self, apply: F) -> bool
+        where F: FnOnce(&mut Level) {
+        let before = self.level.clone();
+        apply(&mut self.level);
+        if self.level == before {
+            return false;
+        }
+
+        match self.stroke {
+            Some(true) => {},
+            Some(false) => {
+                self.history.record(before);
+                self.stroke = Some(true);
+            },
+            None => self.history.record(before),
+        }
+        true
+    }
+
+    pub fn begin_stroke(&mut self) {
+        self.stroke = Some(false);
+    }
+
+    pub fn end_stroke(&mut self) {
+        self.stroke = None;
+    }
+
+    // Puts the brush's block on a cell, over whatever was there.
+    // Fails if the level would need more keys than files can have.
+    pub fn paint(&mut self, cell: (usize, usize)) -> bool {
+        let block = self.brush.block(cell);
+        let new_key = !self.level.blocks.iter()
+            .any(|other| other.cell != cell && other.same_key(&block));
+        if new_key && self.level.key_count() >= MAX_KEYS {
+            return false;
+        }
+
+        self.change(|level| {
+            level.blocks.retain(|other| other.cell != cell);
+            level.blocks.push(block);
+            // Files list blocks row by row anyway
+            level.blocks.sort_by_key(|block| (block.cell.1, block.cell.0));
+        })
+    }
+
+    pub fn erase(&mut self, cell: (usize, usize)) -> bool {
+        self.change(|level| level.blocks.retain(|block| block.cell != cell))
+    }
+
+    // Gives the block on a cell the brush's color, and nothing else.
+    // Fails just like paint() does.
+    pub fn recolor(&mut self, cell: (usize, usize)) -> bool {
+        let block = match self.level.block_at(cell) {
+            Some(block) => LevelBlock {
+                color: self.brush.color.clone(),
+                ..block.clone()
+            },
+            None => return false,
+        };
+        let new_key = !self.level.blocks.iter()
+            .any(|other| other.cell != cell && other.same_key(&block));
+        if new_key && self.level.key_count() >= MAX_KEYS {
+            return false;
+        }
+
+        self.change(|level| {
+            for other in level.blocks.iter_mut().filter(|other| other.cell == cell) {
+                *other = block.clone();
+            }
+        })
+    }
+
+    // Takes the brush from a block already on the level
+    pub fn pick(&mut self, cell: (usize, usize)) -> bool {
+        match self.level.block_at(cell) {
+            Some(block) => {
+                self.brush = Brush {
+                    kind:  block.kind,
+                    hp:    block.hp,
+                    color: block.color.clone(),
+                };
+                true
+            },
+            None => false,
+        }
+    }
+
+    pub fn rename(&mut self, name: &str) -> bool {
+        let name = name.trim().to_uppercase();
+        if name.is_empty() {
+            return false;
+        }
+        self.change(|level| level.name = name)
+    }
+
+    pub fn clear(&mut self) -> bool {
+        self.change(|level| level.blocks.clear())
+    }
+
+    pub fn undo(&mut self) -> bool {
+        let current = self.level.clone();
+        match self.history.undo(current) {
+            Ok(level) => {
+                self.level = level;
+                true
+            },
+            Err(_) => false,
+        }
+    }
+
+    pub fn redo(&mut self) -> bool {
+        let current = self.level.clone();
+        match self.history.redo(current) {
+            Ok(level) => {
+                self.level = level;
+                true
+            },
+            Err(_) => false,
+        }
+    }
+
+    pub fn can_undo(&self) -> bool {
+        self.history.can_undo()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        self.history.can_redo()
+    }
+
+    // Stops at the edges of the grid
+    pub fn move_cursor(&mut self, delta: (i32, i32)) {
+        let column = (self.cursor.0 as i32 + delta.0).max(0).min(GRID_COLUMNS as i32 - 1);
+        let row = (self.cursor.1 as i32 + delta.1).max(0).min(GRID_ROWS as i32 - 1);
+        self.cursor = (column as usize, row as usize);
+    }
+
+    // Solid and power-up blocks come in their usual colors, which can
+    // still be changed afterwards
+    pub fn next_kind(&mut self, forward: bool) {
+        let count = BRUSH_KINDS.len();
+        let current = BRUSH_KINDS.iter().position(|&kind| kind == self.brush.kind).unwrap_or(0);
+        let next = if forward { (current + 1) % count } else { (current + count - 1) % count };
+        self.brush.kind = BRUSH_KINDS[next];
+        match self.brush.kind {
+            BlockKind::Normal => {},
+            BlockKind::Solid => {
+                self.brush.color = SOLID_COLOR.to_string();
+                self.brush.hp = 1;
+            },
+            BlockKind::PowerUp(effect) => self.brush.color = effect.color().to_string(),
+        }
+    }
+
+    pub fn next_color(&mut self, forward: bool) {
+        let count = BRUSH_COLORS.len();
+        let next = match BRUSH_COLORS.iter().position(|&color| color == self.brush.color) {
+            Some(current) => if forward { (current + 1) % count } else { (current + count - 1) % count },
+            // Picked off some block in a color of its own
+            None => 0,
+        };
+        self.brush.color = BRUSH_COLORS[next].to_string();
+    }
+
+    pub fn set_color(&mut self, color: &str) {
+        self.brush.color = color.to_string();
+    }
+
+    // Solid blocks never break, so they don't get any
+    pub fn change_hp(&mut self, up: bool) {
+        if self.brush.kind == BlockKind::Solid {
+            return;
+        }
+        self.brush.hp = if up {
+            (self.brush.hp + 1).min(MAX_HP)
+        } else {
+            self.brush.hp.saturating_sub(1).max(1)
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recoloring_keeps_levels_writable() {
+        let mut editor = Editor::new();
+        for i in 0..MAX_KEYS {
+            editor.brush.color = format!("#{:03X}", i);
+            assert!(editor.paint((i % GRID_COLUMNS, i / GRID_COLUMNS)));
+        }
+
+        editor.brush.color = "#ABC".to_string();
+        assert!(!editor.recolor((0, 0)));
+        assert!(editor.export().is_ok());
+
+        // Colors already on the level are fine
+        editor.brush.color = "#001".to_string();
+        assert!(editor.recolor((0, 0)));
+        assert!(editor.export().is_ok());
+    }
+}
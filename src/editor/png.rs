@@ -0,0 +1,84 @@
+// 8-bit RGB in a PNG file, which anything can show.
+// Image data goes in uncompressed deflate blocks, which keeps this
+// short at the cost of bigger files.
+
+// Most bytes a stored deflate block may hold
+const STORED_BLOCK: usize = 65535;
+
+fn push_u32(out: &mut Vec<u8>, value: u32) {
+    out.push((value >> 24) as u8);
+    out.push((value >> 16) as u8);
+    out.push((value >> 8) as u8);
+    out.push(value as u8);
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xffff_ffffu32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xedb8_8320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for &byte in data {
+        a = (a + byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    (b << 16) | a
+}
+
+fn push_chunk(out: &mut Vec<u8>, kind: &[u8], data: &[u8]) {
+    push_u32(out, data.len() as u32);
+    let start = out.len();
+    out.extend_from_slice(kind);
+    out.extend_from_slice(data);
+    let crc = crc32(&out[start..]);
+    push_u32(out, crc);
+}
+
+// Pixels are rows of (red, green, blue) bytes, top to bottom
+pub fn encode(size: (u32, u32), pixels: &[u8]) -> Vec<u8> {
+    let row = size.0 as usize * 3;
+    assert_eq!(pixels.len(), row * size.1 as usize, "wrong number of pixels");
+
+    // Every row starts with its filter, which is none
+    let mut raw = Vec::with_capacity((row + 1) * size.1 as usize);
+    for line in pixels.chunks(row) {
+        raw.push(0);
+        raw.extend_from_slice(line);
+    }
+
+    let mut zlib = vec![0x78, 0x01];
+    let blocks: Vec<&[u8]> = raw.chunks(STORED_BLOCK).collect();
+    for (i, block) in blocks.iter().enumerate() {
+        zlib.push(if i + 1 == blocks.len() { 1 } else { 0 });
+        let len = block.len() as u16;
+        zlib.extend_from_slice(&[len as u8, (len >> 8) as u8, !len as u8, (!len >> 8) as u8]);
+        zlib.extend_from_slice(block);
+    }
+    // Images with no pixels still need a last block
+    if blocks.is_empty() {
+        zlib.extend_from_slice(&[1, 0, 0, 0xff, 0xff]);
+    }
+    push_u32(&mut zlib, adler32(&raw));
+
+    let mut header = vec![];
+    push_u32(&mut header, size.0);
+    push_u32(&mut header, size.1);
+    header.extend_from_slice(&[8,    // Bits per channel
+                               2,    // RGB
+                               0,    // Deflate
+                               0,    // Adaptive filtering
+                               0]);  // Not interlaced
+
+    let mut out = vec![0x89, b'P', b'N', b'G', 0x0d, 0x0a, 0x1a, 0x0a];
+    push_chunk(&mut out, b"IHDR", &header);
+    push_chunk(&mut out, b"IDAT", &zlib);
+    push_chunk(&mut out, b"IEND", &[]);
+    out
+}
@@ -0,0 +1,90 @@
+// Pictures of levels, for looking at them without running the game.
+// Blocks go where the game puts them, in a theme's colors, with the
+// paddles at their starting spots.
+
+use sim::{Game, GameMode, Level, LevelSet, FIELD_SIZE};
+use theme::Theme;
+
+// Rows of (red, green, blue) bytes, top to bottom
+pub struct Image {
+    pub size:   (u32, u32),
+    pub pixels: Vec<u8>,
+}
+
+impl Image {
+    pub fn new(size: (u32, u32), color: [u8; 3]) -> Image {
+        let mut pixels = Vec::with_capacity(size.0 as usize * size.1 as usize * 3);
+        for _ in 0..(size.0 * size.1) {
+            pixels.extend_from_slice(&color);
+        }
+        Image {
+            size:   size,
+            pixels: pixels,
+        }
+    }
+
+    // Whatever falls off the image is left out
+    pub fn fill_rect(&mut self, pos: (f32, f32), size: (f32, f32), color: [u8; 3]) {
+        let clamp = |value: f32, max: u32| value.round().max(0.0).min(max as f32) as u32;
+        let (left, right) = (clamp(pos.0, self.size.0), clamp(pos.0 + size.0, self.size.0));
+        let (top, bottom) = (clamp(pos.1, self.size.1), clamp(pos.1 + size.1, self.size.1));
+        for y in top..bottom {
+            for x in left..right {
+                let index = ((y * self.size.0) + x) as usize * 3;
+                self.pixels[index..(index + 3)].copy_from_slice(&color);
+            }
+        }
+    }
+}
+
+// "#RGB" or "#RRGGBB", as level and theme files write them
+pub fn parse_color(text: &str) -> Option<[u8; 3]> {
+    if !text.starts_with('#') || !text[1..].chars().all(|c| c.is_digit(16)) {
+        return None;
+    }
+    let digits: Vec<u8> = text[1..].chars().map(|c| c.to_digit(16).unwrap() as u8).collect();
+    match digits.len() {
+        3 => Some([digits[0] * 17, digits[1] * 17, digits[2] * 17]),
+        6 => Some([(digits[0] * 16) + digits[1], (digits[2] * 16) + digits[3], (digits[4] * 16) + digits[5]]),
+        _ => None,
+    }
+}
+
+// Colors that can't be read come out gray, which stands out enough
+// on any theme
+fn color(text: &str) -> [u8; 3] {
+    parse_color(text).unwrap_or([128, 128, 128])
+}
+
+// The field at scale times its size
+pub fn render(level: &Level, mode: GameMode, theme: &Theme, scale: f32) -> Image {
+    let levels = LevelSet {
+        name:   "PREVIEW".to_string(),
+        levels: vec![level.clone()],
+    };
+    let game = Game::with_levels(mode, FIELD_SIZE, 0, levels);
+    let size = ((game.field.0 * scale).round() as u32, (game.field.1 * scale).round() as u32);
+    let mut image = Image::new(size, color(&theme.background));
+
+    // Shading gets flattened into bands along the sides and bottom,
+    // which is about what it looks like at this size
+    let block = (game.block_size.0 * scale, game.block_size.1 * scale);
+    let bevel = (block.1 / 8.0).max(1.0);
+    for level_block in &game.level_blocks {
+        let pos = ((level_block.pos.0 * scale) - (block.0 / 2.0),
+                   (level_block.pos.1 * scale) - (block.1 / 2.0));
+        image.fill_rect(pos, block, color(&theme.bevel.1));
+        image.fill_rect((pos.0, pos.1 + block.1 - bevel), (block.0, bevel), color(&theme.bevel.0));
+        image.fill_rect((pos.0 + bevel, pos.1), (block.0 - (bevel * 2.0), block.1 - bevel),
+                        color(theme.block_color(level_block.kind, &level_block.color)));
+    }
+
+    // Tinted when there's more than one, as in the game
+    for (i, paddle) in game.paddles.iter().enumerate() {
+        let tint = if game.paddles.len() > 1 { theme.paddle(i) } else { &theme.text };
+        image.fill_rect(((paddle.xpos - (paddle.sz.0 / 2.0)) * scale, paddle.ypos * scale),
+                        (paddle.sz.0 * scale, paddle.sz.1 * scale),
+                        color(tint));
+    }
+    image
+}
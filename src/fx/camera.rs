@@ -0,0 +1,206 @@
+use sim::{Game, GameEvent, GameMode, Rng};
+
+// Shake piles up as "trauma", from 0.0 to 1.0, which wears off over
+// time. How much the screen moves goes with its square, so small hits
+// barely register and big ones really rattle.
+const BREAK_TRAUMA:  f32 = 0.15;
+const LOSS_TRAUMA:   f32 = 0.6;
+const TRAUMA_DECAY:  f32 = 0.03;
+// At full trauma, for a 720 pixels tall field
+const MAX_SHAKE:     f32 = 12.0;
+const MAX_TILT:      f32 = 0.03; // radians
+const REFERENCE_HEIGHT: f32 = 720.0;
+
+// Ticks the game holds still after hitting many blocks at once
+const HIT_STOP_TICKS: u32 = 6;
+const HIT_STOP_BLOCKS: usize = 2;
+
+// Zoom used when a single block is left, and how fast we get there
+const LAST_BLOCK_ZOOM: f32 = 1.08;
+const ZOOM_EASING:     f32 = 0.05;
+
+// How the scene should be drawn: zoomed and rotated around `center`,
+// then moved by `offset`
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub struct CameraView {
+    pub center: (f32, f32),
+    pub zoom:   f32,
+    pub angle:  f32,
+    pub offset: (f32, f32),
+}
+
+pub struct Camera {
+    // Shaking and zooming can be turned off, for players who get sick
+    // from them. Hit-stop stays, since nothing moves.
+    pub reduce_motion: bool,
+    trauma: f32,
+    freeze: u32,
+    zoom:   f32,
+    focus:  (f32, f32),
+    target: Option<(f32, f32)>,
+    field:  (f32, f32),
+    // Where the shake has the screen on this tick: (x, y, angle)
+    jitter: (f32, f32, f32),
+    // Not the game's generator, which has to stay in sync online
+    rng:    Rng,
+}
+
+impl Camera {
+    pub fn new(seed: u64) -> Camera {
+        Camera {
+            reduce_motion: false,
+            trauma: 0.0,
+            freeze: 0,
+            zoom:   1.0,
+            focus:  (0.0, 0.0),
+            target: None,
+            field:  (0.0, 0.0),
+            jitter: (0.0, 0.0, 0.0),
+            rng:    Rng::new(seed),
+        }
+    }
+
+    pub fn shake(&mut self, trauma: f32) {
+        self.trauma = (self.trauma + trauma).min(1.0);
+    }
+
+    // Whether the game should be held still. Only honored offline,
+    // since nobody else would wait for us.
+    pub fn is_frozen(&self) -> bool {
+        self.freeze > 0
+    }
+
+    // Reacts to every event the game had since the last call
+    pub fn feed(&mut self, game: &Game, events: &[GameEvent]) {
+        self.field = game.field;
+
+        let mut hits = 0;
+        for event in events {
+            match *event {
+                GameEvent::BlockBroken { .. } => {
+                    self.shake(BREAK_TRAUMA);
+                    hits += 1;
+                },
+                GameEvent::BlockHit { .. } => hits += 1,
+                GameEvent::BallLost { .. } => self.shake(LOSS_TRAUMA),
+                _ => {},
+            }
+        }
+        if hits >= HIT_STOP_BLOCKS {
+            self.freeze = HIT_STOP_TICKS;
+        }
+
+        // Closing in on the last block, halfway from the middle of the
+        // field so the paddles stay in sight. Versus never runs out.
+        self.target = None;
+        if game.mode != GameMode::Versus && game.breakable_left() == 1 {
+            let last = game.level_blocks.iter().find(|block| block.kind.is_breakable());
+            if let Some(block) = last {
+                let middle = (game.field.0 / 2.0, game.field.1 / 2.0);
+                self.target = Some(((middle.0 + block.pos.0) / 2.0,
+                                    (middle.1 + block.pos.1) / 2.0));
+            }
+        }
+    }
+
+    // Wears effects off by one tick
+    pub fn update(&mut self) {
+        self.freeze = self.freeze.saturating_sub(1);
+        self.trauma = (self.trauma - TRAUMA_DECAY).max(0.0);
+
+        let middle = (self.field.0 / 2.0, self.field.1 / 2.0);
+        let (focus, zoom) = match self.target {
+            Some(target) => (target, LAST_BLOCK_ZOOM),
+            None => (middle, 1.0),
+        };
+        self.focus.0 += (focus.0 - self.focus.0) * ZOOM_EASING;
+        self.focus.1 += (focus.1 - self.focus.1) * ZOOM_EASING;
+        self.zoom += (zoom - self.zoom) * ZOOM_EASING;
+
+        let amount = self.trauma * self.trauma;
+        let shake = amount * MAX_SHAKE * self.field.1 / REFERENCE_HEIGHT;
+        self.jitter = (shake * self.rng.range(-1.0, 1.0),
+                       shake * self.rng.range(-1.0, 1.0),
+                       amount * MAX_TILT * self.rng.range(-1.0, 1.0));
+    }
+
+    pub fn view(&self) -> CameraView {
+        if self.reduce_motion {
+            return CameraView {
+                center: self.focus,
+                zoom:   1.0,
+                angle:  0.0,
+                offset: (0.0, 0.0),
+            };
+        }
+
+        CameraView {
+            center: self.focus,
+            zoom:   self.zoom,
+            angle:  self.jitter.2,
+            offset: (self.jitter.0, self.jitter.1),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sim::{BlockKind, FIELD_SIZE};
+
+    fn hit() -> GameEvent {
+        GameEvent::BlockHit { pos: (0.0, 0.0), kind: BlockKind::Solid }
+    }
+
+    fn camera() -> Camera {
+        let mut camera = Camera::new(1);
+        camera.field = FIELD_SIZE;
+        camera
+    }
+
+    #[test]
+    fn trauma_wears_off() {
+        let mut camera = camera();
+        camera.shake(0.6);
+        camera.shake(0.6);
+        assert_eq!(camera.trauma, 1.0);
+
+        camera.update();
+        assert!((camera.trauma - (1.0 - TRAUMA_DECAY)).abs() < 0.0001);
+        assert!(camera.view().offset != (0.0, 0.0));
+
+        for _ in 0..((1.0 / TRAUMA_DECAY) as usize) {
+            camera.update();
+        }
+        assert_eq!(camera.trauma, 0.0);
+        assert_eq!(camera.view().offset, (0.0, 0.0));
+        assert_eq!(camera.view().angle, 0.0);
+    }
+
+    #[test]
+    fn many_hits_at_once_stop_the_game() {
+        let game = Game::new(GameMode::Single, FIELD_SIZE, 1);
+        let mut camera = camera();
+
+        camera.feed(&game, &[hit()]);
+        assert!(!camera.is_frozen());
+
+        camera.feed(&game, &[hit(), hit()]);
+        for _ in 0..HIT_STOP_TICKS {
+            assert!(camera.is_frozen());
+            camera.update();
+        }
+        assert!(!camera.is_frozen());
+    }
+
+    #[test]
+    fn reduced_motion_holds_the_screen_still() {
+        let mut camera = camera();
+        camera.reduce_motion = true;
+        camera.shake(1.0);
+        camera.update();
+
+        let view = camera.view();
+        assert_eq!((view.offset, view.angle, view.zoom), ((0.0, 0.0), 0.0, 1.0));
+    }
+}
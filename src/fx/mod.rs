@@ -0,0 +1,10 @@
+// Visual effects.
+// These only ever read the game, and never change it, so they can go
+// as wild as they like without breaking networked play. Nothing in here
+// touches the browser either, so it can all run headless.
+
+pub mod particles;
+pub mod camera;
+
+pub use self::particles::{Particle, Particles, PARTICLE_BUDGET};
+pub use self::camera::{Camera, CameraView};
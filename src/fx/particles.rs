@@ -0,0 +1,258 @@
+use std::slice;
+use std::f32::consts::PI;
+
+use sim::{Game, GameEvent, Rng};
+use theme::Theme;
+
+// Most particles alive at once. Anything emitted past that is dropped,
+// so a screen full of explosions costs the same as a couple of them.
+pub const PARTICLE_BUDGET: usize = 256;
+
+// Sizes and speeds below are for a 720 pixels tall field, and get
+// scaled to the actual one
+const REFERENCE_HEIGHT: f32 = 720.0;
+
+const DEBRIS_COUNT:   usize = 12;
+const DEBRIS_SPEED:   f32   = 4.0;
+const DEBRIS_SIZE:    f32   = 6.0;
+const DEBRIS_LIFE:    u32   = 40;
+const DEBRIS_GRAVITY: f32   = 0.25;
+
+const SPARK_COUNT:    usize = 6;
+const SPARK_SPEED:    f32   = 3.0;
+const SPARK_SIZE:     f32   = 3.0;
+const SPARK_LIFE:     u32   = 15;
+
+const TRAIL_SIZE:     f32   = 4.0;
+const TRAIL_LIFE:     u32   = 20;
+
+#[derive(Clone, Debug)]
+pub struct Particle {
+    pub pos:      (f32, f32),
+    pub spd:      (f32, f32),
+    // Added to the vertical speed on every tick
+    pub gravity:  f32,
+    pub size:     f32,
+    pub color:    String,
+    // Ticks left, out of how many it started with
+    pub life:     u32,
+    pub lifespan: u32,
+}
+
+impl Particle {
+    fn new() -> Particle {
+        Particle {
+            pos:      (0.0, 0.0),
+            spd:      (0.0, 0.0),
+            gravity:  0.0,
+            size:     0.0,
+            color:    String::new(),
+            life:     0,
+            lifespan: 1,
+        }
+    }
+
+    // Fades from 1.0 to 0.0 as it dies
+    pub fn alpha(&self) -> f32 {
+        self.life as f32 / self.lifespan as f32
+    }
+}
+
+// Every particle is allocated up front. Live ones sit at the start of
+// the pool, and dead ones are swapped to the back to be reused.
+pub struct Particles {
+    pool: Vec<Particle>,
+    live: usize,
+    // Separate from the game's own generator, which must stay in sync
+    // with every other client
+    rng:  Rng,
+}
+
+impl Particles {
+    pub fn new(seed: u64) -> Particles {
+        Particles {
+            pool: (0..PARTICLE_BUDGET).map(|_| Particle::new()).collect(),
+            live: 0,
+            rng:  Rng::new(seed),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.live
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.live == 0
+    }
+
+    pub fn iter(&self) -> slice::Iter<Particle> {
+        self.pool[..self.live].iter()
+    }
+
+    pub fn clear(&mut self) {
+        self.live = 0;
+    }
+
+    // Returns false when out of budget
+    pub fn emit(&mut self, pos: (f32, f32), spd: (f32, f32), gravity: f32,
+                size: f32, color: &str, life: u32) -> bool {
+        if self.live >= self.pool.len() || life == 0 {
+            return false;
+        }
+
+        let particle = &mut self.pool[self.live];
+        particle.pos = pos;
+        particle.spd = spd;
+        particle.gravity = gravity;
+        particle.size = size;
+        particle.color.clear();
+        particle.color.push_str(color);
+        particle.life = life;
+        particle.lifespan = life;
+
+        self.live += 1;
+        true
+    }
+
+    // Particles flying off in every direction
+    pub fn burst(&mut self, pos: (f32, f32), count: usize, speed: f32, gravity: f32,
+                 size: f32, color: &str, life: u32) {
+        for _ in 0..count {
+            let angle = self.rng.range(0.0, 2.0 * PI);
+            let speed = speed * self.rng.range(0.5, 1.0);
+            let life = (life as f32 * self.rng.range(0.75, 1.0)) as u32;
+            let spd = (speed * angle.cos(), speed * angle.sin());
+            if !self.emit(pos, spd, gravity, size, color, life) {
+                break;
+            }
+        }
+    }
+
+    // Emits whatever the game's events call for, given every event since
+    // the last call. Debris takes the color its block was drawn in.
+    pub fn feed(&mut self, game: &Game, events: &[GameEvent], theme: &Theme) {
+        let unit = game.field.1 / REFERENCE_HEIGHT;
+
+        for event in events {
+            match *event {
+                GameEvent::BlockBroken { pos, kind, ref color } =>
+                    self.burst(pos, DEBRIS_COUNT, DEBRIS_SPEED * unit, DEBRIS_GRAVITY * unit,
+                               DEBRIS_SIZE * unit, theme.block_color(kind, color), DEBRIS_LIFE),
+                GameEvent::PaddleHit { pos, .. } | GameEvent::WallHit { pos } =>
+                    self.burst(pos, SPARK_COUNT, SPARK_SPEED * unit, 0.0,
+                               SPARK_SIZE * unit, &theme.sparks, SPARK_LIFE),
+                _ => {},
+            }
+        }
+
+        // Capsules leave a trail behind while falling
+        for powerup in &game.powerups {
+            let drift = self.rng.range(-0.5, 0.5) * unit;
+            self.emit(powerup.pos, (drift, 0.0), 0.0,
+                      TRAIL_SIZE * unit, theme.effect_color(powerup.effect), TRAIL_LIFE);
+        }
+    }
+
+    // Moves every particle along by one tick
+    pub fn update(&mut self) {
+        let mut i = 0;
+        while i < self.live {
+            let dead = {
+                let particle = &mut self.pool[i];
+                particle.spd.1 += particle.gravity;
+                particle.pos.0 += particle.spd.0;
+                particle.pos.1 += particle.spd.1;
+                particle.life -= 1;
+                particle.life == 0
+            };
+
+            if dead {
+                self.live -= 1;
+                self.pool.swap(i, self.live);
+            } else {
+                i += 1;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sim::{GameMode, BlockKind, Effect, PowerUp, FIELD_SIZE};
+
+    fn broken_block() -> GameEvent {
+        GameEvent::BlockBroken {
+            pos:   (640.0, 200.0),
+            kind:  BlockKind::Normal,
+            color: "#ff0000".to_string(),
+        }
+    }
+
+    #[test]
+    fn bursts_stay_within_budget() {
+        let game = Game::new(GameMode::Single, FIELD_SIZE, 1);
+        let events: Vec<GameEvent> = (0..PARTICLE_BUDGET).map(|_| broken_block()).collect();
+        let mut particles = Particles::new(1);
+
+        particles.feed(&game, &events, &Theme::classic());
+        assert_eq!(particles.len(), PARTICLE_BUDGET);
+        assert!(!particles.emit((0.0, 0.0), (0.0, 0.0), 0.0, 1.0, "#fff", 10));
+    }
+
+    #[test]
+    fn dead_particles_are_reused() {
+        let mut particles = Particles::new(1);
+        for life in 0..PARTICLE_BUDGET {
+            assert!(particles.emit((0.0, 0.0), (0.0, 0.0), 0.0, 1.0, "#fff", 1 + life as u32 % 2));
+        }
+
+        // Half of them only last a single tick
+        particles.update();
+        assert_eq!(particles.len(), PARTICLE_BUDGET / 2);
+        assert!(particles.iter().all(|particle| particle.life == 1));
+
+        for _ in 0..PARTICLE_BUDGET / 2 {
+            assert!(particles.emit((0.0, 0.0), (0.0, 0.0), 0.0, 1.0, "#000", 5));
+        }
+        assert_eq!(particles.len(), PARTICLE_BUDGET);
+        assert_eq!(particles.iter().filter(|particle| particle.color == "#000").count(),
+                   PARTICLE_BUDGET / 2);
+    }
+
+    #[test]
+    fn particles_expire_after_their_lifetime() {
+        let mut particles = Particles::new(1);
+        particles.emit((0.0, 0.0), (1.0, -2.0), 0.5, 1.0, "#fff", 3);
+
+        particles.update();
+        particles.update();
+        assert_eq!(particles.len(), 1);
+        {
+            let particle = particles.iter().next().unwrap();
+            assert_eq!(particle.pos, (2.0, -2.5));
+            assert_eq!(particle.alpha(), 1.0 / 3.0);
+        }
+
+        particles.update();
+        assert!(particles.is_empty());
+    }
+
+    #[test]
+    fn capsules_leave_trails() {
+        let mut game = Game::new(GameMode::Single, FIELD_SIZE, 1);
+        game.powerups.push(PowerUp { pos: (100.0, 100.0), effect: Effect::Wide, spd: 2.0 });
+        game.powerups.push(PowerUp { pos: (300.0, 100.0), effect: Effect::Slow, spd: 2.0 });
+        let theme = Theme::classic();
+        let mut particles = Particles::new(1);
+
+        // One per capsule on every tick, at the capsule
+        for tick in 1..4 {
+            particles.feed(&game, &[], &theme);
+            assert_eq!(particles.len(), 2 * tick);
+        }
+        assert!(particles.iter().all(|particle| particle.pos.1 == 100.0));
+        let wide = theme.effect_color(Effect::Wide);
+        assert_eq!(particles.iter().filter(|particle| particle.color == wide).count(), 3);
+    }
+}
@@ -0,0 +1,93 @@
+// Places HUD elements by the edges of the drawing area, so they stay
+// put whatever its size. Everything is laid out in lines of text,
+// counted inwards from the edge (or downwards from the middle).
+
+use text::{Align, TextStyle};
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum Anchor {
+    TopLeft,
+    Top,
+    TopRight,
+    Left,
+    Center,
+    Right,
+    BottomLeft,
+    Bottom,
+    BottomRight,
+}
+
+impl Anchor {
+    // Text by a side edge hugs it
+    pub fn align(&self) -> Align {
+        match *self {
+            Anchor::TopLeft | Anchor::Left | Anchor::BottomLeft => Align::Left,
+            Anchor::Top | Anchor::Center | Anchor::Bottom => Align::Center,
+            Anchor::TopRight | Anchor::Right | Anchor::BottomRight => Align::Right,
+        }
+    }
+}
+
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub struct Layout {
+    pub size:   (f32, f32),
+    // Space kept clear around the edges
+    pub margin: f32,
+    // Height of a line of text
+    pub line:   f32,
+    // From the top of a line down to its baseline
+    baseline:   f32,
+}
+
+impl Layout {
+    pub fn new(size: (f32, f32), style: &TextStyle) -> Layout {
+        Layout {
+            size:     size,
+            margin:   style.size,
+            line:     style.line_height(),
+            baseline: style.baseline(),
+        }
+    }
+
+    // Where the baseline of a line of text goes
+    pub fn text(&self, anchor: Anchor, line: u32) -> (f32, f32) {
+        let (x, y) = self.point(anchor, line);
+        (x, y + self.baseline)
+    }
+
+    // Top left corner of a box of the given size, on that line
+    pub fn rect(&self, anchor: Anchor, line: u32, size: (f32, f32)) -> (f32, f32) {
+        let (x, y) = self.point(anchor, line);
+        let x = match anchor.align() {
+            Align::Left   => x,
+            Align::Center => x - (size.0 / 2.0),
+            Align::Right  => x - size.0,
+        };
+        (x, y + ((self.line - size.1) / 2.0))
+    }
+
+    // Room for text on one of the three anchors along an edge, which
+    // get the space between the margins shared out evenly
+    pub fn column_width(&self) -> f32 {
+        (self.size.0 - (self.margin * 2.0)) / 3.0
+    }
+
+    // Top of the line, on the side it's aligned to
+    fn point(&self, anchor: Anchor, line: u32) -> (f32, f32) {
+        let line = line as f32;
+        let x = match anchor.align() {
+            Align::Left   => self.margin,
+            Align::Center => self.size.0 / 2.0,
+            Align::Right  => self.size.0 - self.margin,
+        };
+        let y = match anchor {
+            Anchor::TopLeft | Anchor::Top | Anchor::TopRight =>
+                self.margin + (line * self.line),
+            Anchor::Left | Anchor::Center | Anchor::Right =>
+                (self.size.1 / 2.0) + (line * self.line),
+            Anchor::BottomLeft | Anchor::Bottom | Anchor::BottomRight =>
+                self.size.1 - self.margin - ((line + 1.0) * self.line),
+        };
+        (x, y)
+    }
+}
@@ -0,0 +1,113 @@
+// Heads-up display.
+// Works out what goes on the HUD and where, from the game alone, so
+// the browser only has to draw it. Like the effects in fx, it never
+// changes the game.
+
+pub mod layout;
+
+pub use self::layout::{Anchor, Layout};
+
+use sim::{Game, GameEvent, Effect};
+
+// The game steps once per frame, which browsers try to keep at 60
+pub const TICK_RATE: f32 = 60.0;
+
+// Blocks broken in a row before the combo counter shows up
+pub const COMBO_MINIMUM: u32 = 2;
+
+// How long a finished combo stays up, in ticks
+pub const COMBO_LINGER: u32 = 90;
+
+// Lives shown as icons. Any more get shown as a number.
+pub const MAX_LIFE_ICONS: u32 = 8;
+
+// Power-up still running
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub struct EffectTimer {
+    pub effect:  Effect,
+    pub player:  usize,
+    // In seconds
+    pub seconds: f32,
+    // How much of it is left, from 1.0 down to 0.0
+    pub left:    f32,
+}
+
+pub struct Hud {
+    // Blocks broken since the ball last touched a paddle
+    pub combo:      u32,
+    pub best_combo: u32,
+    // Last combo, while it's still shown
+    finished:       Option<(u32, u32)>,
+    tick:           u64,
+}
+
+impl Hud {
+    pub fn new() -> Hud {
+        Hud {
+            combo:      0,
+            best_combo: 0,
+            finished:   None,
+            tick:       0,
+        }
+    }
+
+    // Given every event the game had since the last call
+    pub fn feed(&mut self, game: &Game, events: &[GameEvent]) {
+        // Starting over (or loading a game) takes the clock back
+        if game.tick < self.tick {
+            self.combo = 0;
+            self.best_combo = 0;
+            self.finished = None;
+        }
+        self.tick = game.tick;
+
+        for event in events {
+            match *event {
+                GameEvent::BlockBroken { .. } => {
+                    self.combo += 1;
+                    self.best_combo = self.best_combo.max(self.combo);
+                },
+                GameEvent::PaddleHit { .. } | GameEvent::BallLost { .. } => self.end_combo(),
+                _ => {},
+            }
+        }
+    }
+
+    pub fn update(&mut self) {
+        self.finished = match self.finished {
+            Some((combo, ticks)) if ticks > 1 => Some((combo, ticks - 1)),
+            _ => None,
+        };
+    }
+
+    fn end_combo(&mut self) {
+        if self.combo >= COMBO_MINIMUM {
+            self.finished = Some((self.combo, COMBO_LINGER));
+        }
+        self.combo = 0;
+    }
+
+    // Combo to show, if any. The one going on wins over the last one.
+    pub fn shown_combo(&self) -> Option<u32> {
+        if self.combo >= COMBO_MINIMUM {
+            Some(self.combo)
+        } else {
+            self.finished.map(|(combo, _)| combo)
+        }
+    }
+}
+
+// Power-ups running out, soonest first
+pub fn timers(game: &Game) -> Vec<EffectTimer> {
+    let mut timers: Vec<EffectTimer> = game.effects.iter()
+        .filter(|active| active.effect.duration() > 0)
+        .map(|active| EffectTimer {
+            effect:  active.effect,
+            player:  active.player,
+            seconds: active.remaining as f32 / TICK_RATE,
+            left:    active.remaining as f32 / active.effect.duration() as f32,
+        })
+        .collect();
+    timers.sort_by(|a, b| a.seconds.partial_cmp(&b.seconds).unwrap());
+    timers
+}
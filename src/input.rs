@@ -1,25 +1,70 @@
 use std::collections::HashMap;
+use std::f64::consts::PI;
 
 #[derive(Hash, Eq, PartialEq, Clone)]
 pub enum KeyType {
     Right,
     Left,
+    Up,
+    Down,
     S,
     A,
     Enter,
-    FullScreen,
+    Calibrate,
+    ModeSelect,
+    SwitchView,
+    HighScores,
+    Resume,
+    Quit,
+    Options,
+    // Leaves menus
+    Back,
+    // Level editor
+    Erase,
+    Recolor,
+    Pick,
+    NextKind,
+    PrevColor,
+    NextColor,
+    MoreHp,
+    LessHp,
+    Undo,
+    Redo,
+    Rename,
+    PlayTest,
+    Export,
+    P2Left,
+    P2Right,
+    P2Launch,
+    P2Boost,
 //    LMB,
 //    Q,
 }
 
-#[derive(PartialEq)]
+// Orientation follows the screen angle reported by the browser
+// (screen.orientation.angle or the older window.orientation).
+#[derive(PartialEq, Clone, Copy, Debug)]
 pub enum OrientationType {
     Unknown,
-    Portrait,
-    Landscape,
+    Portrait,         //   0 degrees
+    PortraitFlipped,  // 180 degrees
+    Landscape,        //  90 degrees
+    LandscapeFlipped, // 270 (or -90) degrees
 }
 
-#[derive(Clone)]
+impl OrientationType {
+    pub fn from_angle(angle: i32) -> OrientationType {
+        match ((angle % 360) + 360) % 360 {
+            0   => OrientationType::Portrait,
+            90  => OrientationType::Landscape,
+            180 => OrientationType::PortraitFlipped,
+            270 => OrientationType::LandscapeFlipped,
+            _   => OrientationType::Unknown,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
 pub struct DeviceTilt {
     pub abs:   f64,
     pub alpha: f64,
@@ -27,37 +72,198 @@ pub struct DeviceTilt {
     pub gamma: f64,
 }
 
+impl DeviceTilt {
+    pub fn new() -> DeviceTilt {
+        DeviceTilt {
+            abs:   0.0,
+            alpha: 0.0, // Z = yaw
+            beta:  0.0, // X = roll
+            gamma: 0.0, // Y = pitch
+        }
+    }
+
+    // Remaps the raw angles to the screen's point of view, yielding
+    // (horizontal, vertical) tilt in radians. Horizontal tilt is what
+    // actually steers the paddle.
+    // The browser always reports beta and gamma relative to the device's
+    // natural (portrait) orientation, so once the screen is rotated we have
+    // to swap and flip axis accordingly.
+    pub fn screen_axis(&self, orient: OrientationType) -> (f64, f64) {
+        match orient {
+            OrientationType::Unknown |
+            OrientationType::Portrait         => ( self.gamma,  self.beta),
+            OrientationType::PortraitFlipped  => (-self.gamma, -self.beta),
+            OrientationType::Landscape        => ( self.beta,  -self.gamma),
+            OrientationType::LandscapeFlipped => (-self.beta,   self.gamma),
+        }
+    }
+}
+
+pub struct TiltConfig {
+    // Anything below this angle (radians) from the neutral pose is ignored
+    pub dead_zone:   f64,
+    // Angle (radians) which makes the paddle reach the edge of the screen,
+    // before applying sensitivity
+    pub max_angle:   f64,
+    pub sensitivity: f64,
+    // 0.0 follows the device instantly; closer to 1.0 means smoother,
+    // but laggier, movement
+    pub smoothing:   f64,
+}
+
+impl TiltConfig {
+    pub fn new() -> TiltConfig {
+        TiltConfig {
+            dead_zone:   2.0f64.to_radians(),
+            max_angle:   25.0f64.to_radians(),
+            sensitivity: 1.0,
+            smoothing:   0.6,
+        }
+    }
+}
+
 pub struct TiltState {
-    pub active: bool,
-    pub orient: OrientationType,
-    pub async:  DeviceTilt,
-    pub old:    DeviceTilt,
-    pub new:    DeviceTilt,
+    pub active:  bool,
+    pub orient:  OrientationType,
+    pub async:   DeviceTilt,
+    pub old:     DeviceTilt,
+    pub new:     DeviceTilt,
+    pub config:  TiltConfig,
+    // Pose captured by the player which is considered "centered"
+    pub neutral: Option<DeviceTilt>,
+    // Smoothed steering value in range [-1.0, 1.0]
+    pub axis:    f64,
 }
 
 impl TiltState {
     pub fn new() -> TiltState {
-        let tilt = DeviceTilt {
-            abs:   0.0,
-            alpha: 0.0, // Z = yaw
-            beta:  0.0, // X = roll
-            gamma: 0.0, // Y = pitch
-        };
-        
+        let tilt = DeviceTilt::new();
+
         TiltState {
-            active: false,
-            orient: OrientationType::Unknown,
-            async:  tilt.clone(),
-            old:    tilt.clone(),
-            new:    tilt.clone(),
+            active:  false,
+            orient:  OrientationType::Unknown,
+            async:   tilt.clone(),
+            old:     tilt.clone(),
+            new:     tilt.clone(),
+            config:  TiltConfig::new(),
+            neutral: None,
+            axis:    0.0,
+        }
+    }
+
+    pub fn is_calibrated(&self) -> bool {
+        self.neutral.is_some()
+    }
+
+    // Takes the current pose as neutral. Also recenters the paddle.
+    pub fn calibrate(&mut self) {
+        self.neutral = Some(self.new.clone());
+        self.axis = 0.0;
+    }
+
+    // Computes the unsmoothed steering value for a sample, already taking
+    // neutral pose, dead zone and sensitivity into account.
+    pub fn target_axis(&self, sample: &DeviceTilt) -> f64 {
+        let current = sample.screen_axis(self.orient).0;
+        let neutral = match self.neutral {
+            Some(ref neutral) => neutral.screen_axis(self.orient).0,
+            None => 0.0,
+        };
+
+        // Steering reads gamma in portrait but beta in landscape, and beta
+        // ranges over [-180, 180], so we have to take the shortest way
+        // around, or flipping the device would make the paddle jump
+        let mut delta = current - neutral;
+        while delta > PI {
+            delta -= 2.0 * PI;
+        }
+        while delta < -PI {
+            delta += 2.0 * PI;
+        }
+
+        let dead_zone = self.config.dead_zone;
+        if delta.abs() <= dead_zone {
+            return 0.0;
+        }
+
+        let range = (self.config.max_angle - dead_zone).max(0.0001);
+        let ratio = (delta.abs() - dead_zone) / range * self.config.sensitivity;
+        ratio.min(1.0) * delta.signum()
+    }
+
+    // Feeds a new sample into the controller, returning the smoothed
+    // steering value.
+    pub fn feed(&mut self, sample: &DeviceTilt) -> f64 {
+        let target = self.target_axis(sample);
+        let smoothing = self.config.smoothing.max(0.0).min(0.99);
+        self.axis += (target - self.axis) * (1.0 - smoothing);
+        self.axis
+    }
+
+    // Runs a recorded sequence of samples through the controller, one per
+    // tick, returning the steering value after each of them. Useful for
+    // checking behavior against captured sessions without a device.
+    pub fn replay(&mut self, samples: &[DeviceTilt]) -> Vec<f64> {
+        let mut output = Vec::with_capacity(samples.len());
+        for sample in samples {
+            self.new = sample.clone();
+            output.push(self.feed(sample));
+            self.old = self.new.clone();
+        }
+        output
+    }
+}
+
+// Which keys drive a single paddle
+#[derive(Clone)]
+pub struct PaddleControls {
+    pub left:   KeyType,
+    pub right:  KeyType,
+    pub launch: KeyType,
+    pub boost:  KeyType,
+}
+
+impl PaddleControls {
+    pub fn player_one() -> PaddleControls {
+        PaddleControls {
+            left:   KeyType::Left,
+            right:  KeyType::Right,
+            launch: KeyType::S,
+            boost:  KeyType::A,
+        }
+    }
+
+    pub fn player_two() -> PaddleControls {
+        PaddleControls {
+            left:   KeyType::P2Left,
+            right:  KeyType::P2Right,
+            launch: KeyType::P2Launch,
+            boost:  KeyType::P2Boost,
+        }
+    }
+
+    pub fn for_player(player: usize) -> PaddleControls {
+        match player {
+            0 => PaddleControls::player_one(),
+            _ => PaddleControls::player_two(),
         }
     }
 }
 
+// Gamepad state is polled once per frame and packed as a bitmask,
+// so it can be handed over from JS in a single value
+pub const PAD_LEFT:   u32 = 1 << 0;
+pub const PAD_RIGHT:  u32 = 1 << 1;
+pub const PAD_LAUNCH: u32 = 1 << 2;
+pub const PAD_BOOST:  u32 = 1 << 3;
+
 pub struct KeyState {
     pub async: HashMap<KeyType, bool>,
     pub old:   HashMap<KeyType, bool>,
     pub new:   HashMap<KeyType, bool>,
+    // Gamepads are kept apart so they don't release keys still
+    // held on the keyboard
+    pub pad:   HashMap<KeyType, bool>,
 }
 
 impl KeyState {
@@ -66,6 +272,192 @@ impl KeyState {
             async: HashMap::new(),
             old:   HashMap::new(),
             new:   HashMap::new(),
+            pad:   HashMap::new(),
+        }
+    }
+
+    // Collects keyboard and gamepad state for this frame
+    pub fn collect(&mut self) {
+        self.new = self.async.clone();
+        for (key, &state) in &self.pad {
+            if state {
+                self.new.insert(key.clone(), true);
+            }
+        }
+    }
+
+    pub fn pad_dispatch(&mut self, controls: &PaddleControls, mask: u32) {
+        self.pad.insert(controls.left.clone(),   mask & PAD_LEFT   != 0);
+        self.pad.insert(controls.right.clone(),  mask & PAD_RIGHT  != 0);
+        self.pad.insert(controls.launch.clone(), mask & PAD_LAUNCH != 0);
+        self.pad.insert(controls.boost.clone(),  mask & PAD_BOOST  != 0);
+    }
+
+    // Whether a key is being held on this frame
+    pub fn held(&self, key: &KeyType) -> bool {
+        match self.new.get(key) {
+            Some(&state) => state,
+            None => false,
+        }
+    }
+
+    // Whether a key has just been pressed on this frame
+    pub fn pressed(&self, key: &KeyType) -> bool {
+        let newstate = match self.new.get(key) {
+            Some(&state) => state,
+            None => false,
+        };
+        let oldstate = match self.old.get(key) {
+            Some(&state) => state,
+            None => false,
+        };
+        newstate && !oldstate
+    }
+}
+
+
+
+// Mouse buttons, as the browser numbers them
+pub const POINTER_PRIMARY:   usize = 0;
+pub const POINTER_SECONDARY: usize = 1;
+
+// Mouse, or anything else that points at the screen. Buttons work like
+// keys: events change the async state, which gets collected once per frame.
+pub struct PointerState {
+    // Over the field, if the pointer is on the canvas at all
+    pub pos:   Option<(f32, f32)>,
+    pub async: [bool; 2],
+    pub old:   [bool; 2],
+    pub new:   [bool; 2],
+    // Set whenever it moves, until the frame is over
+    pub moved: bool,
+}
+
+impl PointerState {
+    pub fn new() -> PointerState {
+        PointerState {
+            pos:   None,
+            async: [false; 2],
+            old:   [false; 2],
+            new:   [false; 2],
+            moved: false,
+        }
+    }
+
+    pub fn collect(&mut self) {
+        self.new = self.async;
+    }
+
+    pub fn held(&self, button: usize) -> bool {
+        self.new[button]
+    }
+
+    pub fn pressed(&self, button: usize) -> bool {
+        self.new[button] && !self.old[button]
+    }
+
+    pub fn released(&self, button: usize) -> bool {
+        !self.new[button] && self.old[button]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Angles in degrees, as the browser reports them
+    fn sample(beta: f64, gamma: f64) -> DeviceTilt {
+        DeviceTilt {
+            abs:   0.0,
+            alpha: 0.0,
+            beta:  beta.to_radians(),
+            gamma: gamma.to_radians(),
         }
     }
+
+    // Calibrated on the given pose, following the device instantly
+    fn tilt_at(rest: DeviceTilt, orient: OrientationType) -> TiltState {
+        let mut tilt = TiltState::new();
+        tilt.active = true;
+        tilt.orient = orient;
+        tilt.config.smoothing = 0.0;
+        tilt.new = rest;
+        tilt.calibrate();
+        tilt
+    }
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-9
+    }
+
+    #[test]
+    fn dead_zone_is_ignored() {
+        let mut tilt = tilt_at(sample(0.0, 0.0), OrientationType::Portrait);
+        let output = tilt.replay(&[sample(0.0, 1.0), sample(0.0, -1.9), sample(5.0, 0.0), sample(0.0, 3.0)]);
+        assert_eq!(&output[..3], &[0.0, 0.0, 0.0]);
+        assert!(output[3] > 0.0);
+    }
+
+    #[test]
+    fn sensitivity_scales_past_the_dead_zone() {
+        // Halfway between the dead zone and the largest angle
+        let halfway = sample(0.0, 13.5);
+        let mut tilt = tilt_at(sample(0.0, 0.0), OrientationType::Portrait);
+        assert!(close(tilt.replay(&[halfway.clone()])[0], 0.5));
+
+        tilt.config.sensitivity = 2.0;
+        assert!(close(tilt.replay(&[halfway.clone()])[0], 1.0));
+
+        // Never past the edge of the screen
+        tilt.config.sensitivity = 1.0;
+        assert!(close(tilt.replay(&[sample(0.0, -60.0)])[0], -1.0));
+    }
+
+    #[test]
+    fn smoothing_converges() {
+        let mut tilt = tilt_at(sample(0.0, 0.0), OrientationType::Portrait);
+        tilt.config.smoothing = 0.6;
+        let output = tilt.replay(&vec![sample(0.0, 30.0); 60]);
+
+        assert!(output[0] > 0.0 && output[0] < 1.0);
+        assert!(output.windows(2).all(|pair| pair[1] >= pair[0]));
+        assert!(close(output[59], 1.0));
+    }
+
+    #[test]
+    fn recalibrating_at_rest_centers_there() {
+        let rest = sample(0.0, 20.0);
+        let mut tilt = tilt_at(sample(0.0, 0.0), OrientationType::Portrait);
+        assert!(tilt.replay(&[rest.clone()])[0] > 0.0);
+
+        tilt.new = rest.clone();
+        tilt.calibrate();
+        assert_eq!(tilt.axis, 0.0);
+        let output = tilt.replay(&[rest, sample(0.0, 33.5), sample(0.0, 6.5)]);
+        assert_eq!(output[0], 0.0);
+        assert!(close(output[1], 0.5));
+        assert!(close(output[2], -0.5));
+    }
+
+    #[test]
+    fn axis_follows_the_screen() {
+        let steer = |orient: OrientationType, pose: DeviceTilt| {
+            tilt_at(sample(0.0, 0.0), orient).replay(&[pose])[0]
+        };
+
+        // Portrait steers on gamma, landscape on beta
+        assert!(steer(OrientationType::Portrait, sample(0.0, 13.5)) > 0.0);
+        assert!(steer(OrientationType::PortraitFlipped, sample(0.0, 13.5)) < 0.0);
+        assert_eq!(steer(OrientationType::Portrait, sample(13.5, 0.0)), 0.0);
+        assert!(steer(OrientationType::Landscape, sample(13.5, 0.0)) > 0.0);
+        assert!(steer(OrientationType::LandscapeFlipped, sample(13.5, 0.0)) < 0.0);
+        assert_eq!(steer(OrientationType::Landscape, sample(0.0, 13.5)), 0.0);
+    }
+
+    #[test]
+    fn beta_wraps_around() {
+        // Flipping past 180 degrees is a small tilt, not a whole turn
+        let mut tilt = tilt_at(sample(179.0, 0.0), OrientationType::Landscape);
+        assert!(close(tilt.replay(&[sample(-167.5, 0.0)])[0], 0.5));
+    }
 }
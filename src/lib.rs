@@ -0,0 +1,17 @@
+// Everything which doesn't need a browser lives here, so it can
+// also be used by native tools such as the relay server.
+
+pub mod input;
+pub mod sim;
+pub mod net;
+pub mod storage;
+pub mod settings;
+pub mod fx;
+pub mod assets;
+pub mod audio;
+pub mod hud;
+pub mod text;
+pub mod theme;
+pub mod locale;
+pub mod announce;
+pub mod editor;
@@ -0,0 +1,225 @@
+// Translations.
+// Every piece of text the player gets to read or hear is looked up by
+// key on a string table. Tables are text files, one string per line,
+// and the game ships a few of them:
+//
+//     # Comments start with a hash
+//     code           pt
+//     name           PORTUGUÊS
+//     pause          PAUSA
+//     hud.score      PONTOS {score}
+//
+// Names in braces get filled in when the string is used. Anything a
+// table leaves out is taken from the English one, so a translation
+// that falls behind still shows every string, just not in its language.
+
+use std::collections::HashMap;
+
+use sim::{GameMode, Effect};
+
+pub const DEFAULT_LANGUAGE: &'static str = "en";
+
+// Follows whatever language the browser asks for
+pub const AUTO_LANGUAGE: &'static str = "auto";
+
+// English first, since every other one starts from it
+const FILES: [&'static str; 3] = [
+    include_str!("../locales/en.txt"),
+    include_str!("../locales/pt.txt"),
+    include_str!("../locales/es.txt"),
+];
+
+#[derive(PartialEq, Clone, Debug)]
+pub struct Locale {
+    // As in language tags, e.g. "pt"
+    pub code:    String,
+    // In the language itself, for the options menu
+    pub name:    String,
+    strings:     HashMap<String, String>,
+}
+
+impl Locale {
+    pub fn english() -> Locale {
+        Locale::parse_over(None, FILES[0]).expect("English string table is broken")
+    }
+
+    pub fn parse(text: &str) -> Result<Locale, String> {
+        Locale::parse_over(Some(Locale::english()), text)
+    }
+
+    fn parse_over(base: Option<Locale>, text: &str) -> Result<Locale, String> {
+        let mut strings = base.map(|base| base.strings).unwrap_or_else(HashMap::new);
+        let (mut code, mut name) = (None, None);
+
+        for (number, line) in text.lines().enumerate() {
+            let number = number + 1;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            // Spacing within the string is kept, since hints use it
+            // to keep keys apart
+            let split = line.find(char::is_whitespace).unwrap_or(line.len());
+            let (key, value) = (&line[..split], line[split..].trim());
+            if value.is_empty() {
+                return Err(format!("line {}: {} is empty", number, key));
+            }
+
+            match key {
+                "code" => code = Some(value.to_string()),
+                "name" => name = Some(value.to_string()),
+                _ => { strings.insert(key.to_string(), value.to_string()); },
+            }
+        }
+
+        match (code, name) {
+            (Some(code), Some(name)) => Ok(Locale {
+                code:    code,
+                name:    name,
+                strings: strings,
+            }),
+            _ => Err("string table needs a code and a name".to_string()),
+        }
+    }
+
+    // The languages shipped with the game, in the order they're picked from
+    pub fn builtin() -> Vec<Locale> {
+        let english = Locale::english();
+        let mut locales = vec![english.clone()];
+        for text in FILES[1..].iter() {
+            let locale = Locale::parse_over(Some(english.clone()), text)
+                .expect("built-in string table is broken");
+            locales.push(locale);
+        }
+        locales
+    }
+
+    pub fn by_code(code: &str) -> Option<Locale> {
+        Locale::builtin().into_iter().find(|locale| locale.code == code)
+    }
+
+    // Best match for a language tag, as browsers give them: "pt-BR"
+    // gets Portuguese, even if there's nothing for Brazil in particular,
+    // and languages nobody translated to get English
+    pub fn negotiate(tag: &str) -> Locale {
+        let tag = tag.trim().to_lowercase().replace('_', "-");
+        let primary = tag.split('-').next().unwrap_or("").to_string();
+        let locales = Locale::builtin();
+
+        locales.iter().find(|locale| locale.code.to_lowercase() == tag)
+            .or_else(|| locales.iter().find(|locale| locale.code.to_lowercase() == primary))
+            .cloned()
+            .unwrap_or_else(Locale::english)
+    }
+
+    // Strings missing from English too are a bug, but showing the key
+    // at least tells which one
+    pub fn text<'a>(&'a self, key: &'a str) -> &'a str {
+        self.strings.get(key).map(|text| text.as_ref()).unwrap_or(key)
+    }
+
+    // Fills in each {name} with its value. Names without a value are
+    // left as they are.
+    pub fn format(&self, key: &str, args: &[(&str, &str)]) -> String {
+        let mut text = self.text(key).to_string();
+        for &(name, value) in args {
+            text = text.replace(&format!("{{{}}}", name), value);
+        }
+        text
+    }
+
+    pub fn mode(&self, mode: GameMode) -> &str {
+        self.text(match mode {
+            GameMode::Single => "mode.single",
+            GameMode::Coop   => "mode.coop",
+            GameMode::Versus => "mode.versus",
+        })
+    }
+
+    // Short, for the HUD
+    pub fn effect(&self, effect: Effect) -> &str {
+        self.text(match effect {
+            Effect::Wide      => "effect.wide",
+            Effect::Slow      => "effect.slow",
+            Effect::ExtraLife => "effect.life",
+        })
+    }
+}
+
+// Codes of the built-in languages
+pub fn language_codes() -> Vec<String> {
+    Locale::builtin().into_iter().map(|locale| locale.code).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Keys a table sets on its own, without English underneath
+    fn own_strings(text: &str) -> HashMap<String, String> {
+        Locale::parse_over(None, text).unwrap().strings
+    }
+
+    // Every {name} in a string, in order
+    fn placeholders(text: &str) -> Vec<&str> {
+        text.split('{').skip(1).filter_map(|part| part.split('}').next()).collect()
+    }
+
+    #[test]
+    fn tables_have_every_key() {
+        let english = own_strings(FILES[0]);
+        for text in FILES[1..].iter() {
+            let strings = own_strings(text);
+            let code = Locale::parse(text).unwrap().code;
+            for key in english.keys() {
+                assert!(strings.contains_key(key), "{} is missing {}", code, key);
+            }
+            for key in strings.keys() {
+                assert!(english.contains_key(key), "{} has {}, which English doesn't", code, key);
+            }
+        }
+    }
+
+    #[test]
+    fn translations_keep_placeholders() {
+        let english = own_strings(FILES[0]);
+        for text in FILES[1..].iter() {
+            let strings = own_strings(text);
+            for (key, value) in &strings {
+                let mut expected = placeholders(&english[key]);
+                let mut found = placeholders(value);
+                expected.sort();
+                found.sort();
+                assert_eq!(found, expected, "{}", key);
+            }
+        }
+    }
+
+    #[test]
+    fn tags_get_the_closest_language() {
+        assert_eq!(Locale::negotiate("pt-BR").code, "pt");
+        assert_eq!(Locale::negotiate("es_MX").code, "es");
+        assert_eq!(Locale::negotiate(" PT ").code, "pt");
+        assert_eq!(Locale::negotiate("de-DE").code, DEFAULT_LANGUAGE);
+        assert_eq!(Locale::negotiate("").code, DEFAULT_LANGUAGE);
+    }
+
+    #[test]
+    fn missing_strings_fall_back_to_english() {
+        let english = Locale::english();
+        let partial = Locale::parse("code xx\nname TEST\npause PAUSA").unwrap();
+        assert_eq!(partial.text("pause"), "PAUSA");
+        assert_eq!(partial.text("title.play"), english.text("title.play"));
+        assert_eq!(partial.format("hud.score", &[("score", "10")]), "SCORE 10");
+        // Not even English has it
+        assert_eq!(partial.text("no.such.key"), "no.such.key");
+    }
+
+    #[test]
+    fn tables_need_a_code_and_a_name() {
+        assert!(Locale::parse("code xx").is_err());
+        assert!(Locale::parse("name TEST").is_err());
+        assert!(Locale::parse("code xx\nname TEST\npause").is_err());
+    }
+}
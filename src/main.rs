@@ -4,6 +4,7 @@
 extern crate stdweb;
 #[macro_use]
 extern crate lazy_static;
+extern crate brickbreak;
 
 use stdweb::web::{
     self,
@@ -32,7 +33,8 @@ use stdweb::unstable::TryInto;
 
 // ==============================
 
-pub mod input;
+use brickbreak::{input, sim, net, storage, settings, fx, assets, audio, hud, text, theme, locale, announce,
+                 editor};
 pub mod world;
 
 
@@ -45,26 +47,85 @@ lazy_static! {
 
 
 
-fn on_key(key: &str, _location: KeyboardLocation, pressed: bool) -> bool {
+fn on_key(key: &str, _location: KeyboardLocation, pressed: bool, repeat: bool) -> bool {
     match key {
         "ArrowRight" =>
             WORLD.lock().unwrap().input_dispatch(input::KeyType::Right, pressed),
         "ArrowLeft" =>
             WORLD.lock().unwrap().input_dispatch(input::KeyType::Left, pressed),
+        "ArrowUp" =>
+            WORLD.lock().unwrap().input_dispatch(input::KeyType::Up, pressed),
+        "ArrowDown" =>
+            WORLD.lock().unwrap().input_dispatch(input::KeyType::Down, pressed),
         "s" | " " =>
             WORLD.lock().unwrap().input_dispatch(input::KeyType::S, pressed),
         "a" =>
             WORLD.lock().unwrap().input_dispatch(input::KeyType::A, pressed),
         "Enter" =>
             WORLD.lock().unwrap().input_dispatch(input::KeyType::Enter, pressed),
-        "F4" => {
-            // Fullscreen toggling can only be done by an user-generated
-            // event, so we have to dispatch the keystate and then check
-            // for keypresses using async vs. new, instead of new vs. old.
-            // It is a hack, but it is also effective
-            WORLD.lock().unwrap().input_dispatch(input::KeyType::FullScreen, pressed);
+        "c" =>
+            WORLD.lock().unwrap().input_dispatch(input::KeyType::Calibrate, pressed),
+        "m" =>
+            WORLD.lock().unwrap().input_dispatch(input::KeyType::ModeSelect, pressed),
+        "v" =>
+            WORLD.lock().unwrap().input_dispatch(input::KeyType::SwitchView, pressed),
+        "h" =>
+            WORLD.lock().unwrap().input_dispatch(input::KeyType::HighScores, pressed),
+        "r" =>
+            WORLD.lock().unwrap().input_dispatch(input::KeyType::Resume, pressed),
+        "q" =>
+            WORLD.lock().unwrap().input_dispatch(input::KeyType::Quit, pressed),
+        "o" =>
+            WORLD.lock().unwrap().input_dispatch(input::KeyType::Options, pressed),
+        "Escape" | "Backspace" =>
+            WORLD.lock().unwrap().input_dispatch(input::KeyType::Back, pressed),
+
+        // Second player
+        "j" =>
+            WORLD.lock().unwrap().input_dispatch(input::KeyType::P2Left, pressed),
+        "l" =>
+            WORLD.lock().unwrap().input_dispatch(input::KeyType::P2Right, pressed),
+        "k" =>
+            WORLD.lock().unwrap().input_dispatch(input::KeyType::P2Launch, pressed),
+        "i" =>
+            WORLD.lock().unwrap().input_dispatch(input::KeyType::P2Boost, pressed),
+        // Level editor
+        "x" | "Delete" =>
+            WORLD.lock().unwrap().input_dispatch(input::KeyType::Erase, pressed),
+        "b" =>
+            WORLD.lock().unwrap().input_dispatch(input::KeyType::Recolor, pressed),
+        "g" =>
+            WORLD.lock().unwrap().input_dispatch(input::KeyType::Pick, pressed),
+        "t" =>
+            WORLD.lock().unwrap().input_dispatch(input::KeyType::NextKind, pressed),
+        "[" =>
+            WORLD.lock().unwrap().input_dispatch(input::KeyType::PrevColor, pressed),
+        "]" =>
+            WORLD.lock().unwrap().input_dispatch(input::KeyType::NextColor, pressed),
+        "+" | "=" =>
+            WORLD.lock().unwrap().input_dispatch(input::KeyType::MoreHp, pressed),
+        "-" =>
+            WORLD.lock().unwrap().input_dispatch(input::KeyType::LessHp, pressed),
+        "z" =>
+            WORLD.lock().unwrap().input_dispatch(input::KeyType::Undo, pressed),
+        "y" =>
+            WORLD.lock().unwrap().input_dispatch(input::KeyType::Redo, pressed),
+        "n" =>
+            WORLD.lock().unwrap().input_dispatch(input::KeyType::Rename, pressed),
+        "p" =>
+            WORLD.lock().unwrap().input_dispatch(input::KeyType::PlayTest, pressed),
+        "e" =>
+            WORLD.lock().unwrap().input_dispatch(input::KeyType::Export, pressed),
+
+        // Browsers only allow fullscreen from event handlers,
+        // so this can't wait for the next update
+        "F4" => if pressed && !repeat {
             WORLD.lock().unwrap().toggle_fullscreen();
         },
+        // Same goes for file pickers
+        "f" => if pressed && !repeat {
+            WORLD.lock().unwrap().open_level_file();
+        },
         _ => {
             js! { console.log("Key " + @{key} + ", state: " + @{pressed}); };
             return false;
@@ -78,9 +139,10 @@ fn on_key(key: &str, _location: KeyboardLocation, pressed: bool) -> bool {
     true
 }
 
-fn on_device_tilt(absolute: f64, alpha: f64, beta: f64, gamma: f64) {
+fn on_device_tilt(absolute: f64, alpha: f64, beta: f64, gamma: f64, angle: i32) {
     let mut world = WORLD.lock().unwrap();
     world.tilt.active = true;
+    world.tilt.orient = input::OrientationType::from_angle(angle);
 
     // Convert to radians
     world.tilt.async.abs = absolute.to_radians();
@@ -89,12 +151,104 @@ fn on_device_tilt(absolute: f64, alpha: f64, beta: f64, gamma: f64) {
     world.tilt.async.gamma = gamma.to_radians();
 }
 
+fn on_asset(index: u32, error: String) {
+    WORLD.lock().unwrap().asset_loaded(index as usize, &error);
+}
+
+fn on_net_open() {
+    WORLD.lock().unwrap().net_open();
+}
+
+fn on_net_message(text: String) {
+    WORLD.lock().unwrap().net_message(&text);
+}
+
+fn on_net_closed() {
+    WORLD.lock().unwrap().net_closed();
+}
+
+// Online play is requested through the page's address, e.g.
+// index.html?online=ws://localhost:3012&room=lobby&mode=versus
+// Add &watch=1 to spectate the match in that room instead.
+// Random levels are too, e.g. index.html?random=anything
+fn query_param(name: &str) -> Option<String> {
+    let value: String = js! {
+        var params = new URLSearchParams(window.location.search);
+        return params.get(@{name}) || "";
+    }.try_into().unwrap();
+
+    if value.is_empty() { None } else { Some(value) }
+}
+
+fn on_resize() {
+    WORLD.lock().unwrap().fit_viewport();
+}
+
+fn on_fullscreen_change(fullscreen: bool) {
+    WORLD.lock().unwrap().fullscreen_changed(fullscreen);
+}
+
+fn on_fullscreen_error() {
+    WORLD.lock().unwrap().fullscreen_failed();
+}
+
+fn on_orientation_change() {
+    WORLD.lock().unwrap().orientation_changed();
+}
+
+fn on_focus_lost() {
+    WORLD.lock().unwrap().focus_lost();
+}
+
+// Canvas pixels, which the world maps onto the field
+fn on_pointer_move(x: f64, y: f64) {
+    WORLD.lock().unwrap().pointer_moved((x as f32, y as f32));
+}
+
+fn on_pointer_button(button: u32, pressed: bool) {
+    WORLD.lock().unwrap().pointer_dispatch(button as usize, pressed);
+}
+
+fn on_pointer_leave() {
+    WORLD.lock().unwrap().pointer_left();
+}
+
+fn on_level_file(text: String) {
+    WORLD.lock().unwrap().level_file_loaded(&text);
+}
+
 fn on_touch(pressed: bool) {
     // WIP
     WORLD.lock().unwrap().input_dispatch(input::KeyType::S, pressed);
 }
 
 
+// Gamepads can't notify us, so we poll them on each frame.
+// Uses the standard mapping: D-pad or left stick to move,
+// bottom face button to launch, left face button to boost.
+fn poll_gamepad(index: u32) -> u32 {
+    let mask: u32 = js! {
+        var pads = navigator.getGamepads ? navigator.getGamepads() : [];
+        var pad = pads[@{index}];
+        var mask = 0;
+        if (!pad || !pad.connected) {
+            return 0;
+        }
+
+        var pressed = function (i) {
+            return pad.buttons.length > i && pad.buttons[i].pressed;
+        };
+        var axis = pad.axes.length > 0 ? pad.axes[0] : 0.0;
+
+        if (pressed(14) || axis < -0.5) { mask |= @{input::PAD_LEFT}; }
+        if (pressed(15) || axis > 0.5)  { mask |= @{input::PAD_RIGHT}; }
+        if (pressed(0))                 { mask |= @{input::PAD_LAUNCH}; }
+        if (pressed(2))                 { mask |= @{input::PAD_BOOST}; }
+        return mask;
+    }.try_into().unwrap();
+    mask
+}
+
 fn game_loop(last_call: f64) {
     let now: f64 = js!( return Date.now(); ).try_into().unwrap();
     let dt:  f64 = now - last_call;
@@ -103,6 +257,11 @@ fn game_loop(last_call: f64) {
     {
         let mut world = WORLD.lock().unwrap();
 
+        // Each gamepad drives the paddle of the same index
+        for player in 0..2 {
+            world.gamepad_dispatch(player, poll_gamepad(player as u32));
+        }
+
         world.fps = 1000.0f64 / dt;
         world.update(dt);
         world.render();
@@ -117,6 +276,12 @@ fn game_loop(last_call: f64) {
 fn main() {
     stdweb::initialize();
 
+    // Needed before anything touches the world, which starts loading
+    // assets as soon as it's created
+    js! {
+        Module.exports.assetCallback = @{on_asset};
+    };
+
     //WORLD.lock().unwrap().draw_box("red",   (20.0, 20.0), (150.0, 100.0));
     //WORLD.lock().unwrap().draw_box("blue",  (40.0, 40.0), (150.0, 100.0));
     //WORLD.lock().unwrap().draw_box("green", (60.0, 60.0), (150.0, 100.0));
@@ -124,14 +289,14 @@ fn main() {
     // Bind event listeners
     // Key down event
     web::window().add_event_listener(|event: KeydownEvent| {
-        if on_key(&event.key(), event.location(), true) {
+        if on_key(&event.key(), event.location(), true, event.repeat()) {
             event.prevent_default();
         }
     });
     
     // Key up event
     web::window().add_event_listener(|event: KeyupEvent| {
-        if on_key(&event.key(), event.location(), false) {
+        if on_key(&event.key(), event.location(), false, false) {
             event.prevent_default();
         }
     });
@@ -139,18 +304,19 @@ fn main() {
     // Device orientation event.
     // Needs to be done in pure JS, since we still don't have Rust
     // bindings...
-    // And oh, this doesn't work properly and might cause bugs on non-Firefox
-    // browsers, of course, so that's why I'm deactivating this.
-    /*
     js! {
         // Expose handler functions
         Module.exports.deviceTiltCallback  = @{on_device_tilt};
         Module.exports.deviceTouchCallback = @{on_touch};
 
         // Outsource events to WASM framework by using an event listener
-        /*if (@{web::window()}.DeviceOrientationEvent) {
+        if (@{web::window()}.DeviceOrientationEvent) {
             @{web::window()}.addEventListener("deviceorientation", function (e) {
-                e.preventDefault();
+                // Desktop browsers fire this once with nulls, even
+                // without a gyroscope
+                if (e.beta === null || e.gamma === null) {
+                    return;
+                }
 
                 // Sorry, I know this is horrible, but this is the only
                 // way I found to "cast" these values to floats in JS.
@@ -159,51 +325,145 @@ fn main() {
                 var alpha = 0.0;
                 var beta = 0.0;
                 var gamma = 0.0;
-                
-                abs += e.absolute;
+
+                abs += e.absolute ? 1.0 : 0.0;
                 alpha += e.alpha;
                 beta += e.beta;
                 gamma += e.gamma;
-                
-                Module.exports.deviceTiltCallback(abs, alpha, beta, gamma);
-            }, false);
-        }*/
-        if (@{web::window()}.DeviceMotionEvent) {
-            @{web::window()}.addEventListener("devicemotion", function (e) {
-                e.preventDefault();
 
-                // Same as above, but I didn't test these.
-                // Might not be needed.
-                var alpha = 0.0;
-                var beta = 0.0;
-                var gamma = 0.0;
-                
-                /*alpha += e.acceleration.z * 2.0;
-                beta += e.acceleration.x * 2.0;
-                gamma += e.acceleration.y * 2.0;*/
-                alpha += e.rotationRate.alpha;
-                beta += e.rotationRate.beta;
-                gamma += e.rotationRate.gamma;
-                
-                Module.exports.deviceTiltCallback(0.0,
-                                                  alpha,
-                                                  beta,
-                                                  gamma);
+                // Screen angle is needed to remap the axis
+                var angle = 0;
+                if (screen.orientation && typeof screen.orientation.angle === "number") {
+                    angle = screen.orientation.angle | 0;
+                } else if (typeof window.orientation === "number") {
+                    angle = window.orientation | 0;
+                }
+
+                Module.exports.deviceTiltCallback(abs, alpha, beta, gamma, angle);
             }, false);
-        } else {
-            alert("Sorry, your phone sucks");
         }
 
         if (@{web::window()}.TouchEvent) {
-            @{web::window()}.addEventListener("touchstart", (e) => {
+            @{web::window()}.addEventListener("touchstart", function (e) {
                 Module.exports.deviceTouchCallback(true);
             }, false);
 
-            @{web::window()}.addEventListener("touchend", (e) => {
+            @{web::window()}.addEventListener("touchend", function (e) {
                 Module.exports.deviceTouchCallback(false);
             }, false);
         }
-    };*/
+    };
+
+    // Window changes. The canvas is fitted again on all of them,
+    // and the game pauses whenever it can't be seen.
+    js! {
+        Module.exports.resizeCallback      = @{on_resize};
+        Module.exports.fullscreenCallback  = @{on_fullscreen_change};
+        Module.exports.fullscreenErrorCallback = @{on_fullscreen_error};
+        Module.exports.orientationCallback = @{on_orientation_change};
+        Module.exports.focusLostCallback   = @{on_focus_lost};
+
+        @{web::window()}.addEventListener("resize", function (e) {
+            Module.exports.resizeCallback();
+        }, false);
+
+        ["fullscreenchange", "webkitfullscreenchange", "mozfullscreenchange"].forEach(function (name) {
+            document.addEventListener(name, function (e) {
+                var element = document.fullscreenElement
+                    || document.webkitFullscreenElement
+                    || document.mozFullScreenElement;
+                Module.exports.fullscreenCallback(!!element);
+            }, false);
+        });
+
+        ["fullscreenerror", "webkitfullscreenerror", "mozfullscreenerror"].forEach(function (name) {
+            document.addEventListener(name, function (e) {
+                Module.exports.fullscreenErrorCallback();
+            }, false);
+        });
+
+        @{web::window()}.addEventListener("orientationchange", function (e) {
+            Module.exports.orientationCallback();
+        }, false);
+
+        @{web::window()}.addEventListener("blur", function (e) {
+            Module.exports.focusLostCallback();
+        }, false);
+
+        document.addEventListener("visibilitychange", function (e) {
+            if (document.hidden) {
+                Module.exports.focusLostCallback();
+            }
+        }, false);
+    };
+
+    // Mouse over the canvas, for the level editor
+    js! {
+        Module.exports.pointerMoveCallback   = @{on_pointer_move};
+        Module.exports.pointerButtonCallback = @{on_pointer_button};
+        Module.exports.pointerLeaveCallback  = @{on_pointer_leave};
+        Module.exports.levelFileCallback     = @{on_level_file};
+
+        var canvas = document.getElementById("viewport");
+        // The canvas may be drawn at a size other than its own
+        var position = function (e) {
+            var rect = canvas.getBoundingClientRect();
+            return [(e.clientX - rect.left) * canvas.width / rect.width,
+                    (e.clientY - rect.top) * canvas.height / rect.height];
+        };
+        // Main button first, then the other one
+        var buttons = { 0: @{input::POINTER_PRIMARY as u32}, 2: @{input::POINTER_SECONDARY as u32} };
+
+        canvas.addEventListener("mousemove", function (e) {
+            var pos = position(e);
+            Module.exports.pointerMoveCallback(pos[0], pos[1]);
+        }, false);
+
+        canvas.addEventListener("mousedown", function (e) {
+            if (e.button in buttons) {
+                var pos = position(e);
+                Module.exports.pointerMoveCallback(pos[0], pos[1]);
+                Module.exports.pointerButtonCallback(buttons[e.button], true);
+            }
+        }, false);
+
+        // Buttons may be let go of anywhere
+        @{web::window()}.addEventListener("mouseup", function (e) {
+            if (e.button in buttons) {
+                Module.exports.pointerButtonCallback(buttons[e.button], false);
+            }
+        }, false);
+
+        canvas.addEventListener("mouseleave", function (e) {
+            Module.exports.pointerLeaveCallback();
+        }, false);
+
+        // The other button erases instead
+        canvas.addEventListener("contextmenu", function (e) {
+            e.preventDefault();
+        }, false);
+    };
+
+    // Online session
+    js! {
+        Module.exports.netOpenCallback    = @{on_net_open};
+        Module.exports.netMessageCallback = @{on_net_message};
+        Module.exports.netClosedCallback  = @{on_net_closed};
+    };
+
+    if let Some(seed) = query_param("random") {
+        WORLD.lock().unwrap().play_random(&seed);
+    }
+
+    if let Some(url) = query_param("online") {
+        let room = query_param("room").unwrap_or("lobby".to_string());
+        let mode = match query_param("mode") {
+            Some(ref mode) if mode == "coop" => sim::GameMode::Coop,
+            _ => sim::GameMode::Versus,
+        };
+        let watching = query_param("watch").is_some();
+        WORLD.lock().unwrap().go_online(&url, &room, mode, watching);
+    }
 
     // This starts game loop by calling it on the
     // next available animation frame
@@ -0,0 +1,66 @@
+use std::collections::HashMap;
+
+use sim::PlayerInput;
+
+// Inputs received so far, by tick, one slot per player.
+pub struct InputBuffer {
+    players: usize,
+    inputs:  HashMap<u64, Vec<Option<PlayerInput>>>,
+}
+
+impl InputBuffer {
+    pub fn new(players: usize) -> InputBuffer {
+        InputBuffer {
+            players: players,
+            inputs:  HashMap::new(),
+        }
+    }
+
+    pub fn store(&mut self, player: usize, tick: u64, input: PlayerInput) {
+        if player >= self.players {
+            return;
+        }
+        let players = self.players;
+        let slots = self.inputs.entry(tick).or_insert_with(|| vec![None; players]);
+        slots[player] = Some(input);
+    }
+
+    // Idle input for everyone on the given tick
+    pub fn fill_idle(&mut self, tick: u64) {
+        for player in 0..self.players {
+            self.store(player, tick, PlayerInput::new());
+        }
+    }
+
+    pub fn is_ready(&self, tick: u64) -> bool {
+        match self.inputs.get(&tick) {
+            Some(slots) => slots.iter().all(|slot| slot.is_some()),
+            None => false,
+        }
+    }
+
+    // How many consecutive ticks, starting at the given one, can be simulated
+    pub fn ready_from(&self, tick: u64) -> u64 {
+        let mut count = 0;
+        while self.is_ready(tick + count) {
+            count += 1;
+        }
+        count
+    }
+
+    pub fn take(&mut self, tick: u64) -> Option<Vec<PlayerInput>> {
+        if !self.is_ready(tick) {
+            return None;
+        }
+        self.inputs.remove(&tick)
+            .map(|slots| slots.into_iter().map(|slot| slot.unwrap()).collect())
+    }
+
+    // Forgets everything older than the given tick
+    pub fn discard_before(&mut self, tick: u64) {
+        let old: Vec<u64> = self.inputs.keys().cloned().filter(|&t| t < tick).collect();
+        for t in old {
+            self.inputs.remove(&t);
+        }
+    }
+}
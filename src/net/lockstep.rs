@@ -0,0 +1,149 @@
+use std::collections::HashMap;
+
+use sim::{Game, PlayerInput};
+use net::buffer::InputBuffer;
+use net::protocol::{Message, SessionConfig};
+
+// How many ticks ahead local input is scheduled. This hides network
+// latency at the cost of a little input lag: as long as remote inputs
+// arrive within this window, nobody ever has to wait.
+pub const INPUT_DELAY: u64 = 4;
+
+// How often (in ticks) clients compare checksums
+pub const HASH_INTERVAL: u64 = 30;
+
+// How often (in ticks) the host shares the whole game state, so
+// spectators joining late have somewhere to start from
+pub const SNAPSHOT_INTERVAL: u64 = 300;
+
+// Deterministic lockstep session.
+// Every client runs the whole simulation, and a tick is only simulated
+// once the inputs of every player for that tick are known.
+pub struct Lockstep {
+    pub player:  usize,
+    pub players: usize,
+    // Tick whose inputs have to be known before the next step
+    pub tick:    u64,
+    // Tick the next local input will be scheduled for
+    next_local:  u64,
+    inputs:      InputBuffer,
+    own_hashes:  HashMap<u64, u64>,
+    peer_hashes: HashMap<u64, u64>,
+    outbox:      Vec<Message>,
+    // First tick on which clients disagreed, if any
+    pub desync:  Option<u64>,
+    // Set when someone left; the session can't go on without them
+    pub left:    Option<usize>,
+}
+
+impl Lockstep {
+    pub fn new(player: usize, config: &SessionConfig) -> Lockstep {
+        let players = config.players();
+        let mut inputs = InputBuffer::new(players);
+
+        // Nobody could have sent anything for the first few ticks
+        for tick in 0..INPUT_DELAY {
+            inputs.fill_idle(tick);
+        }
+
+        Lockstep {
+            player:      player,
+            players:     players,
+            tick:        0,
+            next_local:  INPUT_DELAY,
+            inputs:      inputs,
+            own_hashes:  HashMap::new(),
+            peer_hashes: HashMap::new(),
+            outbox:      vec![],
+            desync:      None,
+            left:        None,
+        }
+    }
+
+    fn store_input(&mut self, player: usize, tick: u64, input: PlayerInput) {
+        // Late or bogus inputs would only confuse us
+        if tick < self.tick {
+            return;
+        }
+        self.inputs.store(player, tick, input);
+    }
+
+    // Schedules local input, unless we're already too far ahead of
+    // the simulation. Should be called once per frame.
+    pub fn submit(&mut self, input: PlayerInput) {
+        if self.next_local > self.tick + INPUT_DELAY {
+            return;
+        }
+
+        let (player, tick) = (self.player, self.next_local);
+        self.store_input(player, tick, input);
+        self.outbox.push(Message::Input(player, tick, input));
+        self.next_local += 1;
+    }
+
+    pub fn receive(&mut self, message: &Message) {
+        match *message {
+            Message::Input(player, tick, input) => {
+                if player != self.player {
+                    self.store_input(player, tick, input);
+                }
+            },
+            Message::Hash(player, tick, hash) => {
+                if player != self.player {
+                    self.peer_hashes.insert(tick, hash);
+                    self.check_hash(tick);
+                }
+            },
+            Message::Leave(player) => self.left = Some(player),
+            _ => {},
+        }
+    }
+
+    fn check_hash(&mut self, tick: u64) {
+        let matches = match (self.own_hashes.get(&tick), self.peer_hashes.get(&tick)) {
+            (Some(own), Some(peer)) => own == peer,
+            _ => return,
+        };
+
+        if !matches && self.desync.is_none() {
+            self.desync = Some(tick);
+        }
+        self.own_hashes.remove(&tick);
+        self.peer_hashes.remove(&tick);
+    }
+
+    pub fn is_ready(&self) -> bool {
+        self.inputs.is_ready(self.tick)
+    }
+
+    // Simulates the next tick, if everyone's input has arrived.
+    pub fn advance(&mut self, game: &mut Game) -> bool {
+        if !self.is_ready() || self.left.is_some() {
+            return false;
+        }
+
+        let inputs = match self.inputs.take(self.tick) {
+            Some(inputs) => inputs,
+            None => return false,
+        };
+        game.step(&inputs);
+        self.tick += 1;
+
+        if self.tick % HASH_INTERVAL == 0 {
+            let (player, tick, hash) = (self.player, self.tick, game.checksum());
+            self.own_hashes.insert(tick, hash);
+            self.outbox.push(Message::Hash(player, tick, hash));
+            self.check_hash(tick);
+        }
+
+        if self.player == 0 && self.tick % SNAPSHOT_INTERVAL == 0 {
+            self.outbox.push(Message::Snapshot(game.snapshot()));
+        }
+        true
+    }
+
+    // Messages waiting to be sent to the relay
+    pub fn drain_outbox(&mut self) -> Vec<Message> {
+        self.outbox.drain(..).collect()
+    }
+}
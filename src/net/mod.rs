@@ -0,0 +1,15 @@
+// Networked play.
+// Clients talk to each other through a relay server, exchanging their
+// inputs for every tick. Since the simulation is deterministic, that is
+// all that's needed for everyone to see the same game.
+// Spectators get the very same inputs, plus a snapshot of the game every
+// now and then to start from when joining halfway through.
+
+pub mod protocol;
+pub mod lockstep;
+pub mod spectator;
+mod buffer;
+
+pub use self::protocol::{Message, SessionConfig};
+pub use self::lockstep::Lockstep;
+pub use self::spectator::Spectator;
@@ -0,0 +1,151 @@
+use std::fmt;
+
+use sim::{Game, GameMode, PlayerInput, Snapshot};
+
+// Messages exchanged between clients and the relay.
+// They travel as plain text frames, one message per frame, with
+// space-separated fields. Easy to read on the browser's network tab.
+#[derive(Clone, PartialEq, Debug)]
+pub enum Message {
+    // Client -> relay: join a room
+    Join(String),
+    // Client -> relay: watch the match in a room, without playing
+    Watch(String),
+    // Relay -> client: seat assigned to this client
+    Welcome(usize),
+    // Host -> relay: how the session should be set up
+    Config(SessionConfig),
+    // Relay -> clients: everyone is here, go
+    Start(SessionConfig),
+    // Input of a player for a given tick
+    Input(usize, u64, PlayerInput),
+    // Game checksum of a player after simulating a given tick
+    Hash(usize, u64, u64),
+    // Host -> relay -> spectators: the whole game state, every now and then
+    Snapshot(Snapshot),
+    // Relay -> clients: a player has left
+    Leave(usize),
+    Error(String),
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct SessionConfig {
+    pub seed:  u64,
+    pub mode:  GameMode,
+    pub field: (f32, f32),
+}
+
+impl SessionConfig {
+    pub fn players(&self) -> usize {
+        self.mode.paddle_count()
+    }
+
+    // Every client starts off this very same game
+    pub fn create_game(&self) -> Game {
+        Game::new(self.mode, self.field, self.seed)
+    }
+}
+
+
+
+impl fmt::Display for Message {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Message::Join(ref room) => write!(f, "JOIN {}", room),
+            Message::Watch(ref room) => write!(f, "WATCH {}", room),
+            Message::Welcome(player) => write!(f, "WELCOME {}", player),
+            Message::Config(ref config) => write!(f, "CONFIG {}", config),
+            Message::Start(ref config) => write!(f, "START {}", config),
+            Message::Input(player, tick, ref input) => {
+                match input.steer {
+                    Some(steer) => write!(f, "INPUT {} {} {} {}", player, tick, input.bits(), steer),
+                    None        => write!(f, "INPUT {} {} {}", player, tick, input.bits()),
+                }
+            },
+            Message::Hash(player, tick, hash) => write!(f, "HASH {} {} {:x}", player, tick, hash),
+            Message::Snapshot(ref snapshot) => write!(f, "SNAPSHOT {}", snapshot),
+            Message::Leave(player) => write!(f, "LEAVE {}", player),
+            Message::Error(ref reason) => write!(f, "ERROR {}", reason),
+        }
+    }
+}
+
+impl fmt::Display for SessionConfig {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {} {} {}", self.seed, self.mode.name(), self.field.0, self.field.1)
+    }
+}
+
+
+
+fn field<'a, I>(fields: &mut I, name: &str) -> Result<&'a str, String>
+    where I: Iterator<Item = &'a str> {
+    fields.next().ok_or_else(|| format!("missing {}", name))
+}
+
+fn number<'a, I, T>(fields: &mut I, name: &str) -> Result<T, String>
+    where I: Iterator<Item = &'a str>,
+          T: ::std::str::FromStr {
+    let value = field(fields, name)?;
+    value.parse().map_err(|_| format!("invalid {}: {}", name, value))
+}
+
+fn parse_config<'a, I>(fields: &mut I) -> Result<SessionConfig, String>
+    where I: Iterator<Item = &'a str> {
+    let seed = number(fields, "seed")?;
+    let mode = field(fields, "mode")?;
+    let mode = GameMode::from_name(mode).ok_or_else(|| format!("invalid mode: {}", mode))?;
+    let width = number(fields, "width")?;
+    let height = number(fields, "height")?;
+    Ok(SessionConfig {
+        seed:  seed,
+        mode:  mode,
+        field: (width, height),
+    })
+}
+
+impl Message {
+    pub fn parse(text: &str) -> Result<Message, String> {
+        let mut fields = text.split_whitespace();
+        let kind = field(&mut fields, "message type")?;
+
+        let message = match kind {
+            "JOIN" => Message::Join(field(&mut fields, "room")?.to_string()),
+            "WATCH" => Message::Watch(field(&mut fields, "room")?.to_string()),
+            "WELCOME" => Message::Welcome(number(&mut fields, "player")?),
+            "CONFIG" => Message::Config(parse_config(&mut fields)?),
+            "START" => Message::Start(parse_config(&mut fields)?),
+            "INPUT" => {
+                let player = number(&mut fields, "player")?;
+                let tick = number(&mut fields, "tick")?;
+                let bits = number(&mut fields, "input")?;
+                let steer = match fields.next() {
+                    Some(steer) => Some(steer.parse()
+                                        .map_err(|_| format!("invalid steer: {}", steer))?),
+                    None => None,
+                };
+                Message::Input(player, tick, PlayerInput::from_bits(bits, steer))
+            },
+            "HASH" => {
+                let player = number(&mut fields, "player")?;
+                let tick = number(&mut fields, "tick")?;
+                let hash = field(&mut fields, "hash")?;
+                let hash = u64::from_str_radix(hash, 16)
+                    .map_err(|_| format!("invalid hash: {}", hash))?;
+                Message::Hash(player, tick, hash)
+            },
+            "SNAPSHOT" => Message::Snapshot(Snapshot::parse(&mut fields)?),
+            "LEAVE" => Message::Leave(number(&mut fields, "player")?),
+            "ERROR" => {
+                let reason: Vec<&str> = fields.collect();
+                return Ok(Message::Error(reason.join(" ")));
+            },
+            _ => return Err(format!("unknown message: {}", kind)),
+        };
+
+        if fields.next().is_some() {
+            return Err(format!("trailing data on {}", kind));
+        }
+        Ok(message)
+    }
+}
@@ -0,0 +1,81 @@
+use sim::Game;
+use net::buffer::InputBuffer;
+use net::lockstep::INPUT_DELAY;
+use net::protocol::{Message, SessionConfig};
+
+// Read-only session.
+// Replays the inputs of the players as the relay forwards them. Late
+// comers start off the latest snapshot the host has shared instead of
+// the very first tick, and so does anyone falling too far behind.
+pub struct Spectator {
+    pub players: usize,
+    // Tick whose inputs have to be known before the next step
+    pub tick:    u64,
+    inputs:      InputBuffer,
+    // Newest tick anyone has sent input for, to tell how far behind we are
+    pub latest:  u64,
+    pub left:    Option<usize>,
+}
+
+impl Spectator {
+    pub fn new(config: &SessionConfig) -> Spectator {
+        let players = config.players();
+        let mut inputs = InputBuffer::new(players);
+
+        // Same as the players: the first few ticks are idle
+        for tick in 0..INPUT_DELAY {
+            inputs.fill_idle(tick);
+        }
+
+        Spectator {
+            players: players,
+            tick:    0,
+            inputs:  inputs,
+            latest:  0,
+            left:    None,
+        }
+    }
+
+    pub fn receive(&mut self, game: &mut Game, message: &Message) {
+        match *message {
+            Message::Input(player, tick, input) => {
+                if tick >= self.tick {
+                    self.inputs.store(player, tick, input);
+                }
+                if tick > self.latest {
+                    self.latest = tick;
+                }
+            },
+            Message::Snapshot(ref snapshot) => {
+                // Only jump forward. If we are past it already, we got
+                // there by replaying the same inputs anyway. One that
+                // doesn't fit our game can't be from our match at all.
+                if snapshot.tick > self.tick && game.restore(snapshot).is_ok() {
+                    self.tick = snapshot.tick;
+                    self.inputs.discard_before(snapshot.tick);
+                }
+            },
+            Message::Leave(player) => self.left = Some(player),
+            _ => {},
+        }
+    }
+
+    pub fn is_ready(&self) -> bool {
+        self.inputs.is_ready(self.tick)
+    }
+
+    // How many ticks we could simulate right away
+    pub fn behind(&self) -> u64 {
+        self.inputs.ready_from(self.tick)
+    }
+
+    pub fn advance(&mut self, game: &mut Game) -> bool {
+        let inputs = match self.inputs.take(self.tick) {
+            Some(inputs) => inputs,
+            None => return false,
+        };
+        game.step(&inputs);
+        self.tick += 1;
+        true
+    }
+}
@@ -0,0 +1,455 @@
+// Player preferences.
+// Every option is listed on the Setting enum, which the options menu
+// walks through, and knows its own limits, so a hand-edited or outdated
+// settings file can never put the game in a broken state.
+
+use storage::Storage;
+use theme::{theme_names, ColorVision, ALL_COLOR_VISIONS, DEFAULT_THEME};
+use locale::{language_codes, AUTO_LANGUAGE};
+
+pub const SETTINGS_KEY: &'static str = "brickbreak.settings";
+
+// Bump this whenever the format changes, and teach decode() how to
+// read the old one
+pub const SETTINGS_VERSION: u32 = 1;
+
+// Largest canvas sizes to pick from
+pub const RESOLUTIONS: [(u32, u32); 4] = [(640, 360), (960, 540), (1280, 720), (1920, 1080)];
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Settings {
+    // Code of a built-in language, or AUTO_LANGUAGE
+    pub language:         String,
+
+    // Audio, in percent
+    pub music_volume:     u32,
+    pub effects_volume:   u32,
+
+    // Controls
+    pub tilt_sensitivity: f32,
+    // How much faster paddles go while boosting
+    pub boost_multiplier: f32,
+    // Boost is switched on and off instead of held
+    pub toggle_boost:     bool,
+
+    // Display
+    // Height of text, in pixels of the field. GohuFont is drawn 11
+    // pixels high, so multiples of that look best.
+    pub font_size:        u32,
+    pub theme:            String,
+    pub max_resolution:   (u32, u32),
+    pub show_fps:         bool,
+
+    // Accessibility
+    pub reduce_motion:    bool,
+    pub color_vision:     ColorVision,
+    // Blocks carry a pattern telling their kind
+    pub block_patterns:   bool,
+    // Plain, strongly contrasting colors and outlined shapes, whatever
+    // the theme
+    pub high_contrast:    bool,
+}
+
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum Setting {
+    Language,
+    MusicVolume,
+    EffectsVolume,
+    TiltSensitivity,
+    BoostMultiplier,
+    ToggleBoost,
+    FontSize,
+    Theme,
+    MaxResolution,
+    ShowFps,
+    ReduceMotion,
+    ColorVision,
+    BlockPatterns,
+    HighContrast,
+}
+
+// In the order they show up on the options menu
+pub const ALL_SETTINGS: [Setting; 14] = [
+    Setting::Language,
+    Setting::MusicVolume,
+    Setting::EffectsVolume,
+    Setting::TiltSensitivity,
+    Setting::BoostMultiplier,
+    Setting::ToggleBoost,
+    Setting::FontSize,
+    Setting::Theme,
+    Setting::MaxResolution,
+    Setting::ShowFps,
+    Setting::ReduceMotion,
+    Setting::ColorVision,
+    Setting::BlockPatterns,
+    Setting::HighContrast,
+];
+
+impl Setting {
+    // Name on the settings file
+    pub fn key(&self) -> &'static str {
+        match *self {
+            Setting::Language        => "language",
+            Setting::MusicVolume     => "music_volume",
+            Setting::EffectsVolume   => "effects_volume",
+            Setting::TiltSensitivity => "tilt_sensitivity",
+            Setting::BoostMultiplier => "boost_multiplier",
+            Setting::ToggleBoost     => "toggle_boost",
+            Setting::FontSize        => "font_size",
+            Setting::Theme           => "theme",
+            Setting::MaxResolution   => "max_resolution",
+            Setting::ShowFps         => "show_fps",
+            Setting::ReduceMotion    => "reduce_motion",
+            Setting::ColorVision     => "color_vision",
+            Setting::BlockPatterns   => "block_patterns",
+            Setting::HighContrast    => "high_contrast",
+        }
+    }
+
+    pub fn from_key(key: &str) -> Option<Setting> {
+        ALL_SETTINGS.iter().find(|setting| setting.key() == key).cloned()
+    }
+
+    // The label and category each get looked up on the string table,
+    // as setting.<key> and category.<category>
+    pub fn category(&self) -> &'static str {
+        match *self {
+            Setting::Language => "general",
+            Setting::MusicVolume | Setting::EffectsVolume => "audio",
+            Setting::TiltSensitivity | Setting::BoostMultiplier
+                | Setting::ToggleBoost => "controls",
+            Setting::FontSize | Setting::Theme | Setting::MaxResolution
+                | Setting::ShowFps => "display",
+            Setting::ReduceMotion | Setting::ColorVision
+                | Setting::BlockPatterns | Setting::HighContrast => "accessibility",
+        }
+    }
+}
+
+
+
+// Ranges: (min, max, step)
+const VOLUME:      (u32, u32, u32) = (0, 100, 10);
+const FONT_SIZE:   (u32, u32, u32) = (11, 44, 11);
+const SENSITIVITY: (f32, f32, f32) = (0.25, 3.0, 0.25);
+const BOOST:       (f32, f32, f32) = (1.0, 3.0, 0.25);
+
+fn parse_u32(text: &str, range: (u32, u32, u32)) -> Result<u32, String> {
+    let value: u32 = text.parse().map_err(|_| format!("not a number: {}", text))?;
+    if value < range.0 || value > range.1 {
+        return Err(format!("{} is not between {} and {}", value, range.0, range.1));
+    }
+    Ok(value)
+}
+
+fn parse_f32(text: &str, range: (f32, f32, f32)) -> Result<f32, String> {
+    let value: f32 = text.parse().map_err(|_| format!("not a number: {}", text))?;
+    if !(value >= range.0 && value <= range.1) {
+        return Err(format!("{} is not between {} and {}", value, range.0, range.1));
+    }
+    Ok(value)
+}
+
+fn parse_bool(text: &str) -> Result<bool, String> {
+    match text {
+        "on"  => Ok(true),
+        "off" => Ok(false),
+        _ => Err(format!("expected on or off: {}", text)),
+    }
+}
+
+fn parse_resolution(text: &str) -> Result<(u32, u32), String> {
+    RESOLUTIONS.iter()
+        .find(|&&(width, height)| format!("{}x{}", width, height) == text)
+        .cloned()
+        .ok_or_else(|| format!("unsupported resolution: {}", text))
+}
+
+// Either a built-in language, or whatever the browser prefers
+fn language_choices() -> Vec<String> {
+    let mut choices = vec![AUTO_LANGUAGE.to_string()];
+    choices.extend(language_codes());
+    choices
+}
+
+fn parse_language(text: &str) -> Result<String, String> {
+    if language_choices().iter().any(|code| code == text) {
+        Ok(text.to_string())
+    } else {
+        Err(format!("no such language: {}", text))
+    }
+}
+
+// Themes are compiled in, so the name has to be one of theirs
+fn parse_theme(text: &str) -> Result<String, String> {
+    if theme_names().iter().any(|name| name == text) {
+        Ok(text.to_string())
+    } else {
+        Err(format!("no such theme: {}", text))
+    }
+}
+
+fn step_u32(value: u32, range: (u32, u32, u32), forward: bool) -> u32 {
+    if forward {
+        (value + range.2).min(range.1)
+    } else {
+        value.saturating_sub(range.2).max(range.0)
+    }
+}
+
+fn step_f32(value: f32, range: (f32, f32, f32), forward: bool) -> f32 {
+    if forward {
+        (value + range.2).min(range.1)
+    } else {
+        (value - range.2).max(range.0)
+    }
+}
+
+impl Settings {
+    pub fn new() -> Settings {
+        Settings {
+            language:         AUTO_LANGUAGE.to_string(),
+
+            music_volume:     80,
+            effects_volume:   80,
+
+            tilt_sensitivity: 1.0,
+            boost_multiplier: 2.0,
+            toggle_boost:     false,
+
+            font_size:        22,
+            theme:            DEFAULT_THEME.to_string(),
+            max_resolution:   (1280, 720),
+            show_fps:         true,
+
+            reduce_motion:    false,
+            color_vision:     ColorVision::Normal,
+            block_patterns:   false,
+            high_contrast:    false,
+        }
+    }
+
+    // Current value, as written on the settings file
+    pub fn value(&self, setting: Setting) -> String {
+        let toggle = |on: bool| if on { "on".to_string() } else { "off".to_string() };
+        match setting {
+            Setting::Language        => self.language.clone(),
+            Setting::MusicVolume     => self.music_volume.to_string(),
+            Setting::EffectsVolume   => self.effects_volume.to_string(),
+            Setting::TiltSensitivity => self.tilt_sensitivity.to_string(),
+            Setting::BoostMultiplier => self.boost_multiplier.to_string(),
+            Setting::ToggleBoost     => toggle(self.toggle_boost),
+            Setting::FontSize        => self.font_size.to_string(),
+            Setting::Theme           => self.theme.clone(),
+            Setting::MaxResolution   => format!("{}x{}", self.max_resolution.0, self.max_resolution.1),
+            Setting::ShowFps         => toggle(self.show_fps),
+            Setting::ReduceMotion    => toggle(self.reduce_motion),
+            Setting::ColorVision     => self.color_vision.name().to_string(),
+            Setting::BlockPatterns   => toggle(self.block_patterns),
+            Setting::HighContrast    => toggle(self.high_contrast),
+        }
+    }
+
+    // Changes a setting, unless the value makes no sense for it
+    pub fn set(&mut self, setting: Setting, text: &str) -> Result<(), String> {
+        match setting {
+            Setting::Language        => self.language = parse_language(text)?,
+            Setting::MusicVolume     => self.music_volume = parse_u32(text, VOLUME)?,
+            Setting::EffectsVolume   => self.effects_volume = parse_u32(text, VOLUME)?,
+            Setting::TiltSensitivity => self.tilt_sensitivity = parse_f32(text, SENSITIVITY)?,
+            Setting::BoostMultiplier => self.boost_multiplier = parse_f32(text, BOOST)?,
+            Setting::ToggleBoost     => self.toggle_boost = parse_bool(text)?,
+            Setting::FontSize        => self.font_size = parse_u32(text, FONT_SIZE)?,
+            Setting::Theme           => self.theme = parse_theme(text)?,
+            Setting::MaxResolution   => self.max_resolution = parse_resolution(text)?,
+            Setting::ShowFps         => self.show_fps = parse_bool(text)?,
+            Setting::ReduceMotion    => self.reduce_motion = parse_bool(text)?,
+            Setting::ColorVision     => self.color_vision = ColorVision::from_name(text)
+                .ok_or_else(|| format!("unknown color vision: {}", text))?,
+            Setting::BlockPatterns   => self.block_patterns = parse_bool(text)?,
+            Setting::HighContrast    => self.high_contrast = parse_bool(text)?,
+        }
+        Ok(())
+    }
+
+    // One step up or down, as done on the options menu.
+    // Toggles just flip, and lists wrap around.
+    pub fn adjust(&mut self, setting: Setting, forward: bool) {
+        match setting {
+            Setting::Language => {
+                let choices = language_choices();
+                let count = choices.len();
+                let current = choices.iter().position(|code| *code == self.language).unwrap_or(0);
+                let next = if forward { (current + 1) % count } else { (current + count - 1) % count };
+                self.language = choices[next].clone();
+            },
+            Setting::MusicVolume =>
+                self.music_volume = step_u32(self.music_volume, VOLUME, forward),
+            Setting::EffectsVolume =>
+                self.effects_volume = step_u32(self.effects_volume, VOLUME, forward),
+            Setting::TiltSensitivity =>
+                self.tilt_sensitivity = step_f32(self.tilt_sensitivity, SENSITIVITY, forward),
+            Setting::BoostMultiplier =>
+                self.boost_multiplier = step_f32(self.boost_multiplier, BOOST, forward),
+            Setting::ToggleBoost => self.toggle_boost = !self.toggle_boost,
+            Setting::FontSize =>
+                self.font_size = step_u32(self.font_size, FONT_SIZE, forward),
+            Setting::Theme => {
+                let names = theme_names();
+                let count = names.len();
+                let current = names.iter().position(|name| *name == self.theme).unwrap_or(0);
+                let next = if forward { (current + 1) % count } else { (current + count - 1) % count };
+                self.theme = names[next].clone();
+            },
+            Setting::MaxResolution => {
+                let count = RESOLUTIONS.len();
+                let current = RESOLUTIONS.iter()
+                    .position(|&resolution| resolution == self.max_resolution)
+                    .unwrap_or(0);
+                let next = if forward { (current + 1) % count } else { (current + count - 1) % count };
+                self.max_resolution = RESOLUTIONS[next];
+            },
+            Setting::ShowFps => self.show_fps = !self.show_fps,
+            Setting::ReduceMotion => self.reduce_motion = !self.reduce_motion,
+            Setting::ColorVision => {
+                let count = ALL_COLOR_VISIONS.len();
+                let current = ALL_COLOR_VISIONS.iter()
+                    .position(|&vision| vision == self.color_vision)
+                    .unwrap_or(0);
+                let next = if forward { (current + 1) % count } else { (current + count - 1) % count };
+                self.color_vision = ALL_COLOR_VISIONS[next];
+            },
+            Setting::BlockPatterns => self.block_patterns = !self.block_patterns,
+            Setting::HighContrast => self.high_contrast = !self.high_contrast,
+        }
+    }
+
+    pub fn load(storage: &Storage) -> Result<Settings, String> {
+        match storage.load(SETTINGS_KEY) {
+            Some(text) => Settings::decode(&text),
+            None => Ok(Settings::new()),
+        }
+    }
+
+    pub fn save(&self, storage: &mut Storage) -> Result<(), String> {
+        storage.save(SETTINGS_KEY, &self.encode())
+    }
+
+    // Stored as text: a header with the format version, then one
+    // setting per line.
+    //
+    //     SETTINGS 1
+    //     music_volume 80
+    //     show_fps on
+    pub fn encode(&self) -> String {
+        let mut text = format!("SETTINGS {}\n", SETTINGS_VERSION);
+        for setting in ALL_SETTINGS.iter() {
+            text.push_str(&format!("{} {}\n", setting.key(), self.value(*setting)));
+        }
+        text
+    }
+
+    // Settings which are missing or invalid are left on their
+    // defaults, so one bad line doesn't cost the player all the others
+    pub fn decode(text: &str) -> Result<Settings, String> {
+        let mut lines = text.lines();
+        let header: Vec<&str> = lines.next().unwrap_or("").split_whitespace().collect();
+        if header.len() != 2 || header[0] != "SETTINGS" {
+            return Err("missing header".to_string());
+        }
+        let version: u32 = header[1].parse()
+            .map_err(|_| format!("invalid version: {}", header[1]))?;
+
+        match version {
+            1 => Ok(Settings::decode_v1(lines)),
+            _ => Err(format!("unknown version: {}", version)),
+        }
+    }
+
+    fn decode_v1<'a, I>(lines: I) -> Settings
+        where I: Iterator<Item = &'a str> {
+        let mut settings = Settings::new();
+        for line in lines {
+            let mut fields = line.split_whitespace();
+            let (key, value) = match (fields.next(), fields.next()) {
+                (Some(key), Some(value)) => (key, value),
+                _ => continue,
+            };
+            if let Some(setting) = Setting::from_key(key) {
+                let _ = settings.set(setting, value);
+            }
+        }
+        settings
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use storage::MemoryStorage;
+
+    #[test]
+    fn settings_round_trip() {
+        // Every one of them off its default
+        let mut settings = Settings::new();
+        for setting in ALL_SETTINGS.iter() {
+            settings.adjust(*setting, true);
+            assert!(settings.value(*setting) != Settings::new().value(*setting), "{} didn't change", setting.key());
+        }
+
+        let mut storage = MemoryStorage::new();
+        settings.save(&mut storage).unwrap();
+        assert_eq!(Settings::load(&storage).unwrap(), settings);
+    }
+
+    #[test]
+    fn nothing_stored_is_the_defaults() {
+        assert_eq!(Settings::load(&MemoryStorage::new()).unwrap(), Settings::new());
+    }
+
+    #[test]
+    fn malformed_headers_are_rejected() {
+        for text in ["", "SETTINGS", "OPTIONS 1", "SETTINGS one", "SETTINGS 2\nshow_fps off"].iter() {
+            assert!(Settings::decode(text).is_err(), "accepted {:?}", text);
+        }
+    }
+
+    #[test]
+    fn bad_lines_keep_their_defaults() {
+        let text = "SETTINGS 1\n\
+                    music_volume 30\n\
+                    effects_volume 300\n\
+                    tilt_sensitivity lots\n\
+                    show_fps maybe\n\
+                    theme NOWHERE\n\
+                    max_resolution 123x456\n\
+                    color_vision ULTRAVIOLET\n\
+                    reduce_motion\n\
+                    no_such_setting on\n\
+                    high_contrast on\n";
+        let settings = Settings::decode(text).unwrap();
+
+        let mut expected = Settings::new();
+        expected.music_volume = 30;
+        expected.high_contrast = true;
+        assert_eq!(settings, expected);
+    }
+
+    #[test]
+    fn values_stay_in_range() {
+        let mut settings = Settings::new();
+        assert!(settings.set(Setting::MusicVolume, "101").is_err());
+        assert!(settings.set(Setting::FontSize, "10").is_err());
+        assert!(settings.set(Setting::BoostMultiplier, "NaN").is_err());
+        assert!(settings.set(Setting::Language, "xx").is_err());
+        assert_eq!(settings, Settings::new());
+
+        for _ in 0..20 {
+            settings.adjust(Setting::MusicVolume, true);
+            settings.adjust(Setting::TiltSensitivity, false);
+        }
+        assert_eq!(settings.music_volume, VOLUME.1);
+        assert_eq!(settings.tilt_sensitivity, SENSITIVITY.0);
+    }
+}
@@ -0,0 +1,234 @@
+// Checks on levels, for catching mistakes before anyone plays them.
+// Blocks get laid out the same way the game does it, on every mode,
+// since versus puts the grid somewhere else.
+
+use std::fmt;
+
+use sim::{Game, GameMode, Level, LevelSet, PaddleEdge, FIELD_SIZE, GRID_COLUMNS, GRID_ROWS};
+
+const ALL_MODES: [GameMode; 3] = [GameMode::Single, GameMode::Coop, GameMode::Versus];
+
+// Blocks side by side share an edge, give or take some rounding
+const TOLERANCE: f32 = 0.01;
+
+// Cells are (column, row)
+#[derive(PartialEq, Clone, Debug)]
+pub enum Problem {
+    // The level is cleared the moment it starts
+    NothingToBreak,
+    // Two blocks on the same spot. Files can't do that, but levels
+    // made by code can.
+    Overlapping((usize, usize), (usize, usize)),
+    // Partly or fully off the field, or down where the paddles go
+    OutOfField((usize, usize), GameMode),
+    // Walled in by solid blocks, so the level can never be cleared
+    Unreachable((usize, usize)),
+}
+
+fn cell_name(cell: (usize, usize)) -> String {
+    format!("column {}, row {}", cell.0 + 1, cell.1 + 1)
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Problem::NothingToBreak =>
+                write!(f, "nothing to break"),
+            Problem::Overlapping(first, second) =>
+                write!(f, "blocks on {} and {} overlap", cell_name(first), cell_name(second)),
+            Problem::OutOfField(cell, mode) =>
+                write!(f, "block on {} is out of the field on {}", cell_name(cell), mode.name()),
+            Problem::Unreachable(cell) =>
+                write!(f, "block on {} can't be reached", cell_name(cell)),
+        }
+    }
+}
+
+// The level on its own, laid out for a mode
+fn layout(level: &Level, mode: GameMode) -> Game {
+    let levels = LevelSet {
+        name:   "CHECK".to_string(),
+        levels: vec![level.clone()],
+    };
+    Game::with_levels(mode, FIELD_SIZE, 0, levels)
+}
+
+// Whether two (left, top, right, bottom) rectangles share any area
+fn intersects(a: (f32, f32, f32, f32), b: (f32, f32, f32, f32)) -> bool {
+    a.0 < b.2 - TOLERANCE && b.0 < a.2 - TOLERANCE
+        && a.1 < b.3 - TOLERANCE && b.1 < a.3 - TOLERANCE
+}
+
+pub fn check_level(level: &Level) -> Vec<Problem> {
+    let mut problems = vec![];
+
+    if level.breakable_count() == 0 {
+        problems.push(Problem::NothingToBreak);
+    }
+
+    // Blocks come out of the layout in the level's order
+    for (mode_index, &mode) in ALL_MODES.iter().enumerate() {
+        let game = layout(level, mode);
+        let half = (game.block_size.0 / 2.0, game.block_size.1 / 2.0);
+        let rects: Vec<(f32, f32, f32, f32)> = game.level_blocks.iter()
+            .map(|block| (block.pos.0 - half.0, block.pos.1 - half.1,
+                          block.pos.0 + half.0, block.pos.1 + half.1))
+            .collect();
+
+        // The same on every mode, so once is enough
+        if mode_index == 0 {
+            for i in 0..rects.len() {
+                for j in (i + 1)..rects.len() {
+                    if intersects(rects[i], rects[j]) {
+                        problems.push(Problem::Overlapping(level.blocks[i].cell, level.blocks[j].cell));
+                    }
+                }
+            }
+        }
+
+        // Past the paddles, the ball is as good as lost
+        let (mut top, mut bottom) = (0.0, game.field.1);
+        for paddle in &game.paddles {
+            match paddle.edge {
+                PaddleEdge::Bottom => bottom = paddle.ypos,
+                PaddleEdge::Top    => top = paddle.ypos + paddle.sz.1,
+            }
+        }
+        for (block, rect) in level.blocks.iter().zip(rects.iter()) {
+            let reported = problems.iter().any(|problem| match *problem {
+                Problem::OutOfField(cell, _) => cell == block.cell,
+                _ => false,
+            });
+            let inside = rect.0 >= -TOLERANCE && rect.2 <= game.field.0 + TOLERANCE
+                && rect.1 >= top - TOLERANCE && rect.3 <= bottom + TOLERANCE;
+            if !inside && !reported {
+                problems.push(Problem::OutOfField(block.cell, mode));
+            }
+        }
+    }
+
+    for cell in unreachable(level) {
+        problems.push(Problem::Unreachable(cell));
+    }
+    problems
+}
+
+// Cells the ball can get to, row by row. It comes from outside the
+// grid and goes through anything but solid blocks, breaking its way
+// in. Corners are too tight to squeeze through.
+pub fn reachable(level: &Level) -> Vec<bool> {
+    let mut solid = vec![false; GRID_COLUMNS * GRID_ROWS];
+    for block in &level.blocks {
+        if on_grid(block.cell) && !block.kind.is_breakable() {
+            solid[(block.cell.1 * GRID_COLUMNS) + block.cell.0] = true;
+        }
+    }
+
+    // Flood fill, starting from every open cell along the edges
+    let mut reached = vec![false; GRID_COLUMNS * GRID_ROWS];
+    let mut pending: Vec<(usize, usize)> = vec![];
+    for row in 0..GRID_ROWS {
+        for column in 0..GRID_COLUMNS {
+            if column == 0 || row == 0 || column == GRID_COLUMNS - 1 || row == GRID_ROWS - 1 {
+                pending.push((column, row));
+            }
+        }
+    }
+    while let Some((column, row)) = pending.pop() {
+        let index = (row * GRID_COLUMNS) + column;
+        if reached[index] || solid[index] {
+            continue;
+        }
+        reached[index] = true;
+
+        if column > 0 {
+            pending.push((column - 1, row));
+        }
+        if column + 1 < GRID_COLUMNS {
+            pending.push((column + 1, row));
+        }
+        if row > 0 {
+            pending.push((column, row - 1));
+        }
+        if row + 1 < GRID_ROWS {
+            pending.push((column, row + 1));
+        }
+    }
+    reached
+}
+
+fn on_grid(cell: (usize, usize)) -> bool {
+    cell.0 < GRID_COLUMNS && cell.1 < GRID_ROWS
+}
+
+// Breakable blocks the ball can't get to
+fn unreachable(level: &Level) -> Vec<(usize, usize)> {
+    let reached = reachable(level);
+    level.blocks.iter()
+        .filter(|block| on_grid(block.cell) && block.kind.is_breakable())
+        .filter(|block| !reached[(block.cell.1 * GRID_COLUMNS) + block.cell.0])
+        .map(|block| block.cell)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sim::{LevelBlock, BlockKind};
+
+    fn block(cell: (usize, usize), kind: BlockKind) -> LevelBlock {
+        LevelBlock {
+            cell:  cell,
+            kind:  kind,
+            hp:    1,
+            color: "#FFF".to_string(),
+        }
+    }
+
+    fn level(blocks: Vec<LevelBlock>) -> Level {
+        let mut level = Level::new("CHECK");
+        level.blocks = blocks;
+        level
+    }
+
+    #[test]
+    fn good_levels_pass() {
+        let level = level(vec![block((0, 0), BlockKind::Normal),
+                               block((7, 4), BlockKind::Solid),
+                               block((14, 9), BlockKind::Normal)]);
+        assert_eq!(check_level(&level), vec![]);
+    }
+
+    #[test]
+    fn levels_need_something_to_break() {
+        assert_eq!(check_level(&level(vec![])), vec![Problem::NothingToBreak]);
+        assert_eq!(check_level(&level(vec![block((3, 3), BlockKind::Solid)])),
+                   vec![Problem::NothingToBreak]);
+    }
+
+    #[test]
+    fn overlapping_blocks_are_found() {
+        let level = level(vec![block((2, 2), BlockKind::Normal), block((2, 2), BlockKind::Normal)]);
+        assert_eq!(check_level(&level), vec![Problem::Overlapping((2, 2), (2, 2))]);
+    }
+
+    #[test]
+    fn blocks_past_the_paddles_are_out() {
+        // Reported once, on the first mode it's out on
+        let level = level(vec![block((0, 0), BlockKind::Normal), block((0, 20), BlockKind::Normal)]);
+        assert_eq!(check_level(&level), vec![Problem::OutOfField((0, 20), GameMode::Single)]);
+    }
+
+    #[test]
+    fn walled_in_blocks_are_unreachable() {
+        let mut blocks = vec![block((7, 5), BlockKind::Normal)];
+        for &cell in &[(6, 5), (8, 5), (7, 4), (7, 6)] {
+            blocks.push(block(cell, BlockKind::Solid));
+        }
+        assert_eq!(check_level(&level(blocks.clone())), vec![Problem::Unreachable((7, 5))]);
+
+        // A breakable wall lets the ball through
+        blocks[1].kind = BlockKind::Normal;
+        assert_eq!(check_level(&level(blocks)), vec![]);
+    }
+}
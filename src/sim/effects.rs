@@ -0,0 +1,69 @@
+// Power-ups. Some blocks drop a capsule when destroyed, and whoever
+// catches it with their paddle gets its effect for a while.
+
+// How much the Wide effect stretches a paddle
+pub const WIDE_FACTOR: f32 = 1.5;
+
+// How much the Slow effect holds the ball back
+pub const SLOW_FACTOR: f32 = 0.7;
+
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum Effect {
+    Wide,
+    Slow,
+    ExtraLife,
+}
+
+impl Effect {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Effect::Wide      => "wide",
+            Effect::Slow      => "slow",
+            Effect::ExtraLife => "life",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Effect> {
+        match name {
+            "wide" => Some(Effect::Wide),
+            "slow" => Some(Effect::Slow),
+            "life" => Some(Effect::ExtraLife),
+            _ => None,
+        }
+    }
+
+    // Color of its capsule
+    pub fn color(&self) -> &'static str {
+        match *self {
+            Effect::Wide      => "#4F4",
+            Effect::Slow      => "#4FF",
+            Effect::ExtraLife => "#F4F",
+        }
+    }
+
+    // In ticks. Instant effects don't stick around at all.
+    pub fn duration(&self) -> u32 {
+        match *self {
+            Effect::Wide      => 900,
+            Effect::Slow      => 600,
+            Effect::ExtraLife => 0,
+        }
+    }
+}
+
+#[derive(PartialEq, Clone, Debug)]
+pub struct ActiveEffect {
+    pub effect:    Effect,
+    // Whoever caught it
+    pub player:    usize,
+    pub remaining: u32,
+}
+
+// Capsule on its way to a paddle
+#[derive(PartialEq, Clone, Debug)]
+pub struct PowerUp {
+    pub pos:    (f32, f32),
+    pub effect: Effect,
+    // Vertical speed. Capsules head to the edge of whoever broke the block.
+    pub spd:    f32,
+}
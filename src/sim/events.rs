@@ -0,0 +1,21 @@
+use sim::state::BlockKind;
+use sim::effects::Effect;
+
+// Something worth showing or hearing which happened during a tick.
+// Events never feed back into the game, so they're left out of
+// snapshots and checksums.
+#[derive(PartialEq, Clone, Debug)]
+pub enum GameEvent {
+    // Ball bounced off a paddle
+    PaddleHit { player: usize, pos: (f32, f32) },
+    // Ball bounced off the sides, or off the top when it's not a goal
+    WallHit { pos: (f32, f32) },
+    // Block took a hit but is still standing. Solid blocks always are.
+    BlockHit { pos: (f32, f32), kind: BlockKind },
+    BlockBroken { pos: (f32, f32), kind: BlockKind, color: String },
+    // Ball got past a paddle: a life lost, or a goal on versus
+    BallLost { pos: (f32, f32) },
+    PowerUpCaught { effect: Effect, player: usize, pos: (f32, f32) },
+    // Index of the level that was just cleared
+    LevelCleared { level: usize },
+}
@@ -0,0 +1,417 @@
+// Levels made up from a seed.
+// The same seed (any text at all) and difficulty always make the same
+// level, so a seed is all it takes to share one. Difficulty goes from
+// MIN_DIFFICULTY to MAX_DIFFICULTY, and brings more blocks, more hit
+// points, more solid blocks and fewer power-ups.
+//
+// Whatever comes out gets opened up wherever the ball couldn't get in,
+// so every level can be cleared.
+
+use sim::{Rng, Level, LevelBlock, BlockKind, Effect, GRID_COLUMNS, GRID_ROWS};
+use sim::check::reachable;
+
+pub const MIN_DIFFICULTY: u32 = 1;
+pub const MAX_DIFFICULTY: u32 = 10;
+
+// Same as the built-in levels
+const COLORS: [&'static str; 7] = ["#F44", "#FA4", "#FF4", "#4F4", "#4FF", "#4AF", "#F4F"];
+const SOLID_COLOR: &'static str = "#888";
+
+// Column the grid mirrors around
+const MIDDLE: usize = GRID_COLUMNS / 2;
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum Pattern {
+    // Random blocks, mirrored left to right
+    Mirror,
+    Diamond,
+    Ring,
+    Pyramid,
+    Cross,
+    // Corridors of solid blocks, with a few ways in
+    Maze,
+}
+
+pub const ALL_PATTERNS: [Pattern; 6] = [
+    Pattern::Mirror,
+    Pattern::Diamond,
+    Pattern::Ring,
+    Pattern::Pyramid,
+    Pattern::Cross,
+    Pattern::Maze,
+];
+
+impl Pattern {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Pattern::Mirror  => "MIRROR",
+            Pattern::Diamond => "DIAMOND",
+            Pattern::Ring    => "RING",
+            Pattern::Pyramid => "PYRAMID",
+            Pattern::Cross   => "CROSS",
+            Pattern::Maze    => "MAZE",
+        }
+    }
+
+    // Whether a cell is part of a shape, given where it is across the
+    // shape's box, from -1.0 to 1.0 both ways
+    fn contains(&self, x: f32, y: f32) -> bool {
+        match *self {
+            Pattern::Diamond => x.abs() + y.abs() <= 1.0,
+            Pattern::Ring    => {
+                let distance = ((x * x) + (y * y)).sqrt();
+                distance >= 0.5 && distance <= 1.05
+            },
+            Pattern::Pyramid => x.abs() <= (y + 1.0) / 2.0 + 0.1,
+            Pattern::Cross   => x.abs() <= 0.25 || y.abs() <= 0.3,
+            Pattern::Mirror | Pattern::Maze => true,
+        }
+    }
+}
+
+#[derive(PartialEq, Clone, Copy, Debug)]
+enum Cell {
+    Empty,
+    Breakable,
+    Solid,
+}
+
+// FNV-1a, which is plenty for turning text into a seed
+pub fn seed_from_text(text: &str) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for &byte in text.as_bytes() {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    hash
+}
+
+// Index in [0, count)
+fn pick(rng: &mut Rng, count: usize) -> usize {
+    ((rng.next_f32() * count as f32) as usize).min(count - 1)
+}
+
+fn chance(rng: &mut Rng, odds: f32) -> bool {
+    rng.next_f32() < odds
+}
+
+pub fn generate(seed: &str, difficulty: u32) -> Level {
+    let difficulty = difficulty.max(MIN_DIFFICULTY).min(MAX_DIFFICULTY);
+    let mut rng = Rng::new(seed_from_text(&format!("{}/{}", seed, difficulty)));
+    // From 0.0 on the easiest to 1.0 on the hardest
+    let hardness = (difficulty - MIN_DIFFICULTY) as f32 / (MAX_DIFFICULTY - MIN_DIFFICULTY) as f32;
+
+    let pattern = ALL_PATTERNS[pick(&mut rng, ALL_PATTERNS.len())];
+    let rows = 5 + (hardness * 4.0).round() as usize;
+    let density = 0.4 + (hardness * 0.5);
+
+    let mut cells = match pattern {
+        Pattern::Maze => maze(&mut rng, rows, density, hardness),
+        _ => shape(&mut rng, pattern, rows, density, hardness),
+    };
+    if !cells.iter().any(|&cell| cell == Cell::Breakable) {
+        cells[(rows / 2 * GRID_COLUMNS) + MIDDLE] = Cell::Breakable;
+    }
+
+    // Spaced out the way level files write names, so blank seeds leave
+    // the pattern on its own
+    let mut words = vec![pattern.name().to_string()];
+    words.extend(seed.split_whitespace().map(|word| word.to_uppercase()));
+    let name = words.join(" ");
+    let color_offset = pick(&mut rng, COLORS.len());
+    let mut level = dress(&mut rng, &name, &cells, difficulty, color_offset);
+    open_up(&mut rng, &mut level, color_offset);
+    level
+}
+
+// Colors go in bands, a row each
+fn band_color(row: usize, color_offset: usize) -> &'static str {
+    COLORS[(row + color_offset) % COLORS.len()]
+}
+
+// Shapes are mirrored, so only the left half (and the middle column)
+// gets rolled for
+fn shape(rng: &mut Rng, pattern: Pattern, rows: usize, density: f32, hardness: f32) -> Vec<Cell> {
+    let mut cells = vec![Cell::Empty; GRID_COLUMNS * GRID_ROWS];
+    // Shapes read better with most of their blocks in
+    let fill = if pattern == Pattern::Mirror { density } else { (density + 0.35).min(1.0) };
+    let solid_odds = hardness * 0.25;
+
+    for row in 0..rows {
+        for column in 0..(MIDDLE + 1) {
+            let x = (column as f32 - MIDDLE as f32) / MIDDLE as f32;
+            let y = ((row as f32 / (rows - 1) as f32) * 2.0) - 1.0;
+            if !pattern.contains(x, y) || !chance(rng, fill) {
+                continue;
+            }
+
+            // Shapes get solid edges, and mirrored patterns get them
+            // anywhere
+            let edge = [(-1.0, 0.0), (1.0, 0.0), (0.0, -1.0), (0.0, 1.0)].iter().any(|&(dx, dy)| {
+                !pattern.contains(x + (dx / MIDDLE as f32), y + (dy * 2.0 / (rows - 1) as f32))
+            });
+            let cell = if (edge || pattern == Pattern::Mirror) && chance(rng, solid_odds) {
+                Cell::Solid
+            } else {
+                Cell::Breakable
+            };
+            cells[(row * GRID_COLUMNS) + column] = cell;
+            cells[(row * GRID_COLUMNS) + (GRID_COLUMNS - 1 - column)] = cell;
+        }
+    }
+    cells
+}
+
+// Walls everywhere, with corridors dug between every other cell so
+// they all connect, and holes made on the outer wall
+fn maze(rng: &mut Rng, rows: usize, density: f32, hardness: f32) -> Vec<Cell> {
+    let mut open = vec![false; GRID_COLUMNS * GRID_ROWS];
+    let rooms = ((GRID_COLUMNS - 1) / 2, (rows - 1) / 2);
+    let room_cell = |room: (usize, usize)| ((room.0 * 2) + 1, (room.1 * 2) + 1);
+
+    // Depth first, backing up at dead ends
+    let mut visited = vec![false; rooms.0 * rooms.1];
+    let mut path = vec![(pick(rng, rooms.0), pick(rng, rooms.1))];
+    visited[(path[0].1 * rooms.0) + path[0].0] = true;
+    while let Some(&room) = path.last() {
+        let cell = room_cell(room);
+        open[(cell.1 * GRID_COLUMNS) + cell.0] = true;
+
+        let mut next = vec![];
+        if room.0 > 0 { next.push((room.0 - 1, room.1)); }
+        if room.0 + 1 < rooms.0 { next.push((room.0 + 1, room.1)); }
+        if room.1 > 0 { next.push((room.0, room.1 - 1)); }
+        if room.1 + 1 < rooms.1 { next.push((room.0, room.1 + 1)); }
+        next.retain(|&(column, row)| !visited[(row * rooms.0) + column]);
+
+        if next.is_empty() {
+            path.pop();
+            continue;
+        }
+        let chosen = next[pick(rng, next.len())];
+        visited[(chosen.1 * rooms.0) + chosen.0] = true;
+        // The wall between them goes
+        let (from, to) = (room_cell(room), room_cell(chosen));
+        open[(((from.1 + to.1) / 2) * GRID_COLUMNS) + ((from.0 + to.0) / 2)] = true;
+        path.push(chosen);
+    }
+
+    // Fewer ways in on harder levels
+    let height = (rooms.1 * 2) + 1;
+    let openings = 4 - (hardness * 3.0).round() as usize;
+    for _ in 0..openings {
+        let row = (pick(rng, rooms.1) * 2) + 1;
+        let column = if chance(rng, 0.5) { 0 } else { GRID_COLUMNS - 1 };
+        open[(row * GRID_COLUMNS) + column] = true;
+    }
+
+    // Corridors hold blocks to break. Walls are solid, mostly, but
+    // easier levels leave some of them breakable.
+    let solid_odds = 0.5 + (hardness * 0.5);
+    let mut cells = vec![Cell::Empty; GRID_COLUMNS * GRID_ROWS];
+    for row in 0..height.min(GRID_ROWS) {
+        for column in 0..GRID_COLUMNS {
+            let index = (row * GRID_COLUMNS) + column;
+            cells[index] = if open[index] {
+                if chance(rng, density) { Cell::Breakable } else { Cell::Empty }
+            } else if chance(rng, solid_odds) {
+                Cell::Solid
+            } else {
+                Cell::Breakable
+            };
+        }
+    }
+    cells
+}
+
+// Gives blocks their hit points, colors and power-ups. Mirrored blocks
+// come out the same.
+fn dress(rng: &mut Rng, name: &str, cells: &[Cell], difficulty: u32, color_offset: usize) -> Level {
+    let mut level = Level::new(name);
+    let max_hp = 1 + (difficulty / 2);
+    let powerup_odds = 0.12 - (difficulty as f32 * 0.008);
+
+    for row in 0..GRID_ROWS {
+        let start = level.blocks.len();
+        for column in 0..GRID_COLUMNS {
+            let index = (row * GRID_COLUMNS) + column;
+            let mirror = GRID_COLUMNS - 1 - column;
+            if column > MIDDLE && cells[index] == cells[(row * GRID_COLUMNS) + mirror] {
+                let twin = level.blocks[start..].iter().find(|block| block.cell.0 == mirror).cloned();
+                if let Some(mut block) = twin {
+                    block.cell = (column, row);
+                    level.blocks.push(block);
+                    continue;
+                }
+            }
+
+            let (kind, hp, color) = match cells[index] {
+                Cell::Empty => continue,
+                Cell::Solid => (BlockKind::Solid, 1, SOLID_COLOR.to_string()),
+                Cell::Breakable => if chance(rng, powerup_odds) {
+                    // Extra lives are the rarest
+                    let effect = match pick(rng, 5) {
+                        0 | 1 => Effect::Wide,
+                        2 | 3 => Effect::Slow,
+                        _ => Effect::ExtraLife,
+                    };
+                    (BlockKind::PowerUp(effect), 1, effect.color().to_string())
+                } else {
+                    let hp = 1 + pick(rng, max_hp as usize) as u32;
+                    (BlockKind::Normal, hp, band_color(row, color_offset).to_string())
+                },
+            };
+            level.blocks.push(LevelBlock {
+                cell:  (column, row),
+                kind:  kind,
+                hp:    hp,
+                color: color,
+            });
+        }
+    }
+    level
+}
+
+// Breaks solid blocks off walls until the ball can get to every
+// breakable block. Walls that lead somewhere new go first, and their
+// mirrored twins go along with them.
+fn open_up(rng: &mut Rng, level: &mut Level, color_offset: usize) {
+    let at = |cell: (usize, usize)| (cell.1 * GRID_COLUMNS) + cell.0;
+
+    loop {
+        let reached = reachable(level);
+        let stuck = level.blocks.iter()
+            .any(|block| block.kind.is_breakable() && !reached[at(block.cell)]);
+        if !stuck {
+            return;
+        }
+
+        // Solid blocks with the ball on one side (or the outside, on
+        // the edges), and with something it can't get to on another
+        let mut walls: Vec<(usize, bool)> = vec![];
+        for (i, block) in level.blocks.iter().enumerate() {
+            if block.kind.is_breakable() {
+                continue;
+            }
+            let (column, row) = block.cell;
+            let mut neighbors = vec![];
+            if column > 0 { neighbors.push((column - 1, row)); }
+            if column + 1 < GRID_COLUMNS { neighbors.push((column + 1, row)); }
+            if row > 0 { neighbors.push((column, row - 1)); }
+            if row + 1 < GRID_ROWS { neighbors.push((column, row + 1)); }
+
+            let on_edge = neighbors.len() < 4;
+            let solid = |cell: (usize, usize)| level.blocks.iter()
+                .any(|other| other.cell == cell && !other.kind.is_breakable());
+            let outside = on_edge || neighbors.iter().any(|&cell| reached[at(cell)]);
+            let leads_in = neighbors.iter().any(|&cell| !reached[at(cell)] && !solid(cell));
+            if outside {
+                walls.push((i, leads_in));
+            }
+        }
+
+        let useful: Vec<usize> = walls.iter().filter(|wall| wall.1).map(|wall| wall.0).collect();
+        let candidates: Vec<usize> = if useful.is_empty() {
+            walls.iter().map(|wall| wall.0).collect()
+        } else {
+            useful
+        };
+        // Nothing solid left to break can't leave anything stuck, but
+        // this stays safe either way
+        if candidates.is_empty() {
+            return;
+        }
+        let wall = candidates[pick(rng, candidates.len())];
+        let (column, row) = level.blocks[wall].cell;
+        let mirror = (GRID_COLUMNS - 1 - column, row);
+        for block in &mut level.blocks {
+            if (block.cell == (column, row) || block.cell == mirror) && !block.kind.is_breakable() {
+                block.kind = BlockKind::Normal;
+                block.color = band_color(row, color_offset).to_string();
+            }
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sim::check::{check_level, Problem};
+
+    const SEEDS: [&'static str; 8] = ["", "a", "brick", "hello world", "12345",
+                                      "MAZE", "the quick brown fox", "\u{e9}t\u{e9}"];
+
+    fn every_level() -> Vec<Level> {
+        let mut levels = vec![];
+        for seed in &SEEDS {
+            for difficulty in MIN_DIFFICULTY..(MAX_DIFFICULTY + 1) {
+                levels.push(generate(seed, difficulty));
+            }
+        }
+        levels
+    }
+
+    #[test]
+    fn seeds_make_the_same_level() {
+        for seed in &SEEDS {
+            for difficulty in MIN_DIFFICULTY..(MAX_DIFFICULTY + 1) {
+                assert_eq!(generate(seed, difficulty), generate(seed, difficulty));
+            }
+        }
+        assert!(generate("a", 5) != generate("b", 5));
+    }
+
+    #[test]
+    fn levels_can_be_cleared() {
+        for level in every_level() {
+            let problems: Vec<Problem> = check_level(&level).into_iter().filter(|problem| match *problem {
+                Problem::NothingToBreak | Problem::Unreachable(_) => true,
+                _ => false,
+            }).collect();
+            assert!(problems.is_empty(), "{}: {:?}", level.name, problems);
+        }
+    }
+
+    #[test]
+    fn rows_keep_their_band() {
+        for level in every_level() {
+            for block in &level.blocks {
+                if block.kind != BlockKind::Normal {
+                    continue;
+                }
+                let band = level.blocks.iter()
+                    .find(|other| other.kind == BlockKind::Normal && other.cell.1 == block.cell.1)
+                    .unwrap();
+                assert_eq!(block.color, band.color, "{}: {:?}", level.name, block.cell);
+            }
+        }
+    }
+
+    #[test]
+    fn shapes_stay_mirrored() {
+        for level in every_level() {
+            if level.name.starts_with(Pattern::Maze.name()) {
+                continue;
+            }
+            for block in &level.blocks {
+                let mirror = (GRID_COLUMNS - 1 - block.cell.0, block.cell.1);
+                let twin = level.blocks.iter().find(|other| other.cell == mirror);
+                let twin = twin.expect(&format!("{}: {:?} has no twin", level.name, block.cell));
+                assert_eq!((twin.kind, twin.hp, &twin.color), (block.kind, block.hp, &block.color),
+                           "{}: {:?}", level.name, block.cell);
+            }
+        }
+    }
+
+    #[test]
+    fn names_have_no_stray_spaces() {
+        for seed in &["", "   ", " two  words "] {
+            let level = generate(seed, MIN_DIFFICULTY);
+            assert_eq!(level.name, level.name.split_whitespace().collect::<Vec<&str>>().join(" "));
+            assert_eq!(Level::parse(&level.to_string()).unwrap().name, level.name);
+        }
+        let blank = generate("", MIN_DIFFICULTY);
+        assert!(ALL_PATTERNS.iter().any(|pattern| pattern.name() == blank.name));
+    }
+}
@@ -0,0 +1,512 @@
+use std::fmt;
+
+use sim::state::BlockKind;
+use sim::generate::{generate, MIN_DIFFICULTY, MAX_DIFFICULTY};
+
+// Blocks sit on a grid of block-sized cells, centered on the field
+pub const GRID_COLUMNS: usize = 15;
+pub const GRID_ROWS:    usize = 10;
+
+pub const DEFAULT_LEVEL_SET: &'static str = "CLASSIC";
+
+// Generated level sets are named after this and their seed, as in
+// "RANDOM:pineapple", which is all it takes to make them again
+pub const RANDOM_LEVEL_SET: &'static str = "RANDOM";
+const RANDOM_LEVELS: usize = 50;
+// Levels played on each difficulty before it goes up
+const LEVELS_PER_DIFFICULTY: usize = 3;
+
+// Characters used for keys when writing levels out
+const KEY_CHARS: &'static str = "123456789abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ";
+
+// Most kinds of block a level can have and still be written out,
+// one for each of KEY_CHARS
+pub const MAX_KEYS: usize = 61;
+
+// Packed levels start with these, then the version of the format
+pub const PACKED_MAGIC: &'static [u8] = b"BBLV";
+const PACKED_VERSION: u8 = 1;
+
+// Block kinds on packed levels, by their number
+const PACKED_KINDS: [&'static str; 5] = ["normal", "solid", "wide", "slow", "life"];
+
+#[derive(PartialEq, Clone, Debug)]
+pub struct LevelBlock {
+    pub cell:  (usize, usize), // (column, row)
+    pub kind:  BlockKind,
+    pub hp:    u32,
+    pub color: String,
+}
+
+impl LevelBlock {
+    // Whether both get written out with the same key
+    pub fn same_key(&self, other: &LevelBlock) -> bool {
+        self.kind == other.kind && self.hp == other.hp && self.color == other.color
+    }
+}
+
+// A level, as written on a text file:
+//
+//     # Comments start with a hash
+//     name FIRST STEPS
+//     key 1 normal 1 #FFF
+//     key # solid 1 #888
+//     key w wide 1 #4F4
+//     grid
+//     ...............
+//     ...111w1111....
+//     ...#.......#...
+//
+// Each key maps a character to a block kind, its hit points and its
+// color. Below `grid`, every line is a row of cells, and dots (or
+// spaces) are empty cells.
+#[derive(PartialEq, Clone, Debug)]
+pub struct Level {
+    pub name:   String,
+    pub blocks: Vec<LevelBlock>,
+}
+
+impl Level {
+    pub fn new(name: &str) -> Level {
+        Level {
+            name:   name.to_string(),
+            blocks: vec![],
+        }
+    }
+
+    pub fn block_at(&self, cell: (usize, usize)) -> Option<&LevelBlock> {
+        self.blocks.iter().find(|block| block.cell == cell)
+    }
+
+    // Different kinds of block (counting hit points and color), each
+    // of which needs a key of its own on the file
+    pub fn key_count(&self) -> usize {
+        let mut keys: Vec<&LevelBlock> = vec![];
+        for block in &self.blocks {
+            if !keys.iter().any(|key| block.same_key(key)) {
+                keys.push(block);
+            }
+        }
+        keys.len()
+    }
+
+    // Number of blocks that have to go for the level to be cleared
+    pub fn breakable_count(&self) -> usize {
+        self.blocks.iter().filter(|block| block.kind.is_breakable()).count()
+    }
+
+    pub fn parse(text: &str) -> Result<Level, String> {
+        let mut level = Level::new("UNTITLED");
+        let mut keys: Vec<(char, BlockKind, u32, String)> = vec![];
+        let mut row = None;
+
+        for (number, line) in text.lines().enumerate() {
+            let number = number + 1;
+
+            if let Some(current) = row {
+                if current >= GRID_ROWS && !line.trim().is_empty() {
+                    return Err(format!("line {}: more than {} rows", number, GRID_ROWS));
+                }
+                for (column, cell) in line.chars().enumerate() {
+                    if cell == '.' || cell.is_whitespace() {
+                        continue;
+                    }
+                    if column >= GRID_COLUMNS {
+                        return Err(format!("line {}: more than {} columns", number, GRID_COLUMNS));
+                    }
+                    let key = keys.iter().find(|key| key.0 == cell)
+                        .ok_or_else(|| format!("line {}: unknown key '{}'", number, cell))?;
+                    level.blocks.push(LevelBlock {
+                        cell:  (column, current),
+                        kind:  key.1,
+                        hp:    key.2,
+                        color: key.3.clone(),
+                    });
+                }
+                row = Some(current + 1);
+                continue;
+            }
+
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let mut fields = line.split_whitespace();
+            match fields.next() {
+                Some("name") => {
+                    let name: Vec<&str> = fields.collect();
+                    level.name = name.join(" ");
+                },
+                Some("key") => {
+                    let fields: Vec<&str> = fields.collect();
+                    if fields.len() != 4 || fields[0].chars().count() != 1 {
+                        return Err(format!("line {}: expected key <char> <kind> <hp> <color>", number));
+                    }
+                    let cell = fields[0].chars().next().unwrap();
+                    if cell == '.' {
+                        return Err(format!("line {}: '.' is an empty cell", number));
+                    }
+                    let kind = BlockKind::from_name(fields[1])
+                        .ok_or_else(|| format!("line {}: unknown block kind {}", number, fields[1]))?;
+                    let hp: u32 = fields[2].parse()
+                        .map_err(|_| format!("line {}: invalid hit points {}", number, fields[2]))?;
+                    if hp == 0 {
+                        return Err(format!("line {}: blocks need at least 1 hit point", number));
+                    }
+                    keys.retain(|key| key.0 != cell);
+                    keys.push((cell, kind, hp, fields[3].to_string()));
+                },
+                Some("grid") => row = Some(0),
+                Some(other) => return Err(format!("line {}: unknown field {}", number, other)),
+                None => {},
+            }
+        }
+
+        if row.is_none() {
+            return Err("missing grid".to_string());
+        }
+        Ok(level)
+    }
+
+    // The same level on a compact binary format, for shipping:
+    //
+    //     "BBLV" and the version    5 bytes
+    //     name                      length (1 byte), then UTF-8
+    //     keys                      how many (1 byte), then for each one its
+    //                               kind, hit points (1 byte each) and color
+    //                               (length and UTF-8)
+    //     grid                      runs of cells, row by row: how many
+    //                               (1 byte), then which key (1 byte,
+    //                               counting from 1, or 0 for empty cells)
+    pub fn pack(&self) -> Result<Vec<u8>, String> {
+        fn push_text(out: &mut Vec<u8>, what: &str, text: &str) -> Result<(), String> {
+            if text.len() > 255 {
+                return Err(format!("{} longer than 255 bytes", what));
+            }
+            out.push(text.len() as u8);
+            out.extend_from_slice(text.as_bytes());
+            Ok(())
+        }
+
+        let mut out = PACKED_MAGIC.to_vec();
+        out.push(PACKED_VERSION);
+        push_text(&mut out, "name", &self.name)?;
+
+        let mut keys: Vec<&LevelBlock> = vec![];
+        let mut cells = vec![0u8; GRID_COLUMNS * GRID_ROWS];
+        for block in &self.blocks {
+            let (column, row) = block.cell;
+            if column >= GRID_COLUMNS || row >= GRID_ROWS {
+                return Err(format!("block outside the grid, on column {}, row {}", column + 1, row + 1));
+            }
+            if cells[(row * GRID_COLUMNS) + column] != 0 {
+                return Err(format!("more than one block on column {}, row {}", column + 1, row + 1));
+            }
+
+            let key = match keys.iter().position(|key| key.same_key(block)) {
+                Some(key) => key,
+                None => {
+                    keys.push(block);
+                    keys.len() - 1
+                },
+            };
+            if key >= 255 {
+                return Err("more than 255 kinds of block".to_string());
+            }
+            cells[(row * GRID_COLUMNS) + column] = key as u8 + 1;
+        }
+
+        out.push(keys.len() as u8);
+        for key in &keys {
+            if key.hp > 255 {
+                return Err(format!("more than 255 hit points on a {} block", key.kind.name()));
+            }
+            out.push(PACKED_KINDS.iter().position(|&name| name == key.kind.name()).unwrap() as u8);
+            out.push(key.hp as u8);
+            push_text(&mut out, "color", &key.color)?;
+        }
+
+        let mut runs: Vec<(u8, u8)> = vec![];
+        for &cell in &cells {
+            let same = match runs.last() {
+                Some(&(count, key)) => key == cell && count < 255,
+                None => false,
+            };
+            if same {
+                runs.last_mut().unwrap().0 += 1;
+            } else {
+                runs.push((1, cell));
+            }
+        }
+        for &(count, key) in &runs {
+            out.push(count);
+            out.push(key);
+        }
+        Ok(out)
+    }
+
+    pub fn unpack(data: &[u8]) -> Result<Level, String> {
+        struct Reader<'a> {
+            data: &'a [u8],
+            pos:  usize,
+        }
+
+        impl<'a> Reader<'a> {
+            fn byte(&mut self) -> Result<u8, String> {
+                let byte = *self.data.get(self.pos).ok_or("file ends too early".to_string())?;
+                self.pos += 1;
+                Ok(byte)
+            }
+
+            fn text(&mut self) -> Result<String, String> {
+                let len = self.byte()? as usize;
+                if self.pos + len > self.data.len() {
+                    return Err("file ends too early".to_string());
+                }
+                let text = String::from_utf8(self.data[self.pos..(self.pos + len)].to_vec())
+                    .map_err(|_| "text is not UTF-8".to_string())?;
+                self.pos += len;
+                Ok(text)
+            }
+        }
+
+        if !data.starts_with(PACKED_MAGIC) {
+            return Err("not a packed level".to_string());
+        }
+        let mut reader = Reader { data: data, pos: PACKED_MAGIC.len() };
+        let version = reader.byte()?;
+        if version != PACKED_VERSION {
+            return Err(format!("unknown version {}", version));
+        }
+
+        let mut level = Level::new(&reader.text()?);
+        let mut keys: Vec<(BlockKind, u32, String)> = vec![];
+        for _ in 0..reader.byte()? {
+            let kind = reader.byte()?;
+            let kind = PACKED_KINDS.get(kind as usize).and_then(|&name| BlockKind::from_name(name))
+                .ok_or_else(|| format!("unknown block kind {}", kind))?;
+            let hp = reader.byte()? as u32;
+            if hp == 0 {
+                return Err("blocks need at least 1 hit point".to_string());
+            }
+            keys.push((kind, hp, reader.text()?));
+        }
+
+        let mut index = 0;
+        while index < GRID_COLUMNS * GRID_ROWS {
+            let (count, key) = (reader.byte()? as usize, reader.byte()? as usize);
+            if count == 0 || index + count > GRID_COLUMNS * GRID_ROWS {
+                return Err("grid doesn't fit".to_string());
+            }
+            if key > keys.len() {
+                return Err(format!("unknown key {}", key));
+            }
+            if key > 0 {
+                let key = &keys[key - 1];
+                for cell in index..(index + count) {
+                    level.blocks.push(LevelBlock {
+                        cell:  (cell % GRID_COLUMNS, cell / GRID_COLUMNS),
+                        kind:  key.0,
+                        hp:    key.1,
+                        color: key.2.clone(),
+                    });
+                }
+            }
+            index += count;
+        }
+        if reader.pos != data.len() {
+            return Err("extra bytes after the grid".to_string());
+        }
+        Ok(level)
+    }
+}
+
+impl fmt::Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "name {}", self.name)?;
+
+        // One key for each different kind of block
+        let mut keys: Vec<(char, &LevelBlock)> = vec![];
+        for block in &self.blocks {
+            let known = keys.iter().any(|&(_, key)| key.same_key(block));
+            if !known {
+                let cell = match KEY_CHARS.chars().nth(keys.len()) {
+                    Some(cell) => cell,
+                    None => return Err(fmt::Error),
+                };
+                keys.push((cell, block));
+            }
+        }
+        for &(cell, block) in &keys {
+            writeln!(f, "key {} {} {} {}", cell, block.kind.name(), block.hp, block.color)?;
+        }
+
+        writeln!(f, "grid")?;
+        for row in 0..GRID_ROWS {
+            let line: String = (0..GRID_COLUMNS).map(|column| {
+                match self.block_at((column, row)) {
+                    Some(block) => keys.iter()
+                        .find(|&&(_, key)| key.same_key(block))
+                        .map_or('.', |&(cell, _)| cell),
+                    None => '.',
+                }
+            }).collect();
+            writeln!(f, "{}", line)?;
+        }
+        Ok(())
+    }
+}
+
+
+
+#[derive(Clone)]
+pub struct LevelSet {
+    // Never has whitespace in it, since saved games and high scores
+    // keep it between spaces
+    pub name:   String,
+    pub levels: Vec<Level>,
+}
+
+impl LevelSet {
+    // The levels shipped with the game
+    pub fn builtin() -> LevelSet {
+        let files = [
+            include_str!("../../levels/classic/01.txt"),
+            include_str!("../../levels/classic/02.txt"),
+            include_str!("../../levels/classic/03.txt"),
+            include_str!("../../levels/classic/04.txt"),
+        ];
+
+        LevelSet {
+            name:   DEFAULT_LEVEL_SET.to_string(),
+            levels: files.iter()
+                .map(|text| Level::parse(text).expect("built-in level is broken"))
+                .collect(),
+        }
+    }
+
+    // Made up from a seed, and getting harder as they go
+    pub fn random(seed: &str) -> LevelSet {
+        LevelSet {
+            name:   format!("{}:{}", RANDOM_LEVEL_SET, escape_seed(seed)),
+            levels: (0..RANDOM_LEVELS).map(|i| {
+                let difficulty = MIN_DIFFICULTY + (i / LEVELS_PER_DIFFICULTY) as u32;
+                generate(&format!("{}#{}", seed, i + 1), difficulty.min(MAX_DIFFICULTY))
+            }).collect(),
+        }
+    }
+
+    pub fn by_name(name: &str) -> Option<LevelSet> {
+        let random = format!("{}:", RANDOM_LEVEL_SET);
+        match name {
+            DEFAULT_LEVEL_SET => Some(LevelSet::builtin()),
+            _ if name.starts_with(&random) => {
+                unescape_seed(&name[random.len()..]).map(|seed| LevelSet::random(&seed))
+            },
+            _ => None,
+        }
+    }
+
+    // Levels go round once the last one is cleared
+    pub fn get(&self, index: usize) -> &Level {
+        &self.levels[index % self.levels.len()]
+    }
+}
+
+// Seeds may be any text at all, but set names can't have whitespace.
+// That, and the escape itself, become "%" and the bytes in hex.
+fn escape_seed(seed: &str) -> String {
+    let mut escaped = String::new();
+    for c in seed.chars() {
+        if c.is_whitespace() || c == '%' {
+            let mut bytes = [0; 4];
+            for byte in c.encode_utf8(&mut bytes).bytes() {
+                escaped.push_str(&format!("%{:02X}", byte));
+            }
+        } else {
+            escaped.push(c);
+        }
+    }
+    escaped
+}
+
+fn unescape_seed(escaped: &str) -> Option<String> {
+    let mut bytes = vec![];
+    let mut rest = escaped.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        if byte == b'%' {
+            if tail.len() < 2 || !tail[..2].iter().all(|digit| digit.is_ascii_hexdigit()) {
+                return None;
+            }
+            let hex = String::from_utf8(tail[..2].to_vec()).ok()?;
+            bytes.push(u8::from_str_radix(&hex, 16).ok()?);
+            rest = &tail[2..];
+        } else {
+            bytes.push(byte);
+            rest = tail;
+        }
+    }
+    String::from_utf8(bytes).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sim::Effect;
+
+    // One of every kind of block, in the order unpacking lays them out
+    fn every_kind() -> Level {
+        let mut level = Level::new("ALL KINDS");
+        let kinds = [
+            (BlockKind::Normal, 3, "#F44"),
+            (BlockKind::Solid, 1, "#888"),
+            (BlockKind::PowerUp(Effect::Wide), 1, "#4F4"),
+            (BlockKind::PowerUp(Effect::Slow), 2, "#44F"),
+            (BlockKind::PowerUp(Effect::ExtraLife), 1, "#F4F"),
+        ];
+        for (i, &(kind, hp, color)) in kinds.iter().enumerate() {
+            for &column in &[i, GRID_COLUMNS - 1 - i] {
+                level.blocks.push(LevelBlock {
+                    cell:  (column, i),
+                    kind:  kind,
+                    hp:    hp,
+                    color: color.to_string(),
+                });
+            }
+        }
+        level
+    }
+
+    #[test]
+    fn levels_pack_and_unpack() {
+        let level = every_kind();
+        let packed = level.pack().unwrap();
+        assert!(packed.starts_with(PACKED_MAGIC));
+        assert_eq!(Level::unpack(&packed), Ok(level));
+
+        let empty = Level::new("");
+        assert_eq!(Level::unpack(&empty.pack().unwrap()), Ok(empty));
+    }
+
+    #[test]
+    fn bad_magic_is_rejected() {
+        let mut packed = every_kind().pack().unwrap();
+        packed[0] = b'X';
+        assert!(Level::unpack(&packed).is_err());
+        assert!(Level::unpack(b"").is_err());
+        assert!(Level::unpack(b"name ALL KINDS").is_err());
+    }
+
+    #[test]
+    fn truncated_levels_are_rejected() {
+        let packed = every_kind().pack().unwrap();
+        for len in 0..packed.len() {
+            assert!(Level::unpack(&packed[..len]).is_err(), "{} bytes", len);
+        }
+
+        let mut longer = packed.clone();
+        longer.push(0);
+        assert!(Level::unpack(&longer).is_err());
+    }
+}
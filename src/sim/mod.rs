@@ -0,0 +1,801 @@
+// Headless game simulation.
+// Nothing in here may touch the browser. Given the same seed and the
+// same inputs on every tick, every client must end up on the exact same
+// state, which is what networked play relies on.
+
+mod state;
+mod physics;
+mod versus;
+mod rng;
+mod snapshot;
+mod effects;
+mod level;
+mod events;
+pub mod check;
+pub mod generate;
+
+pub use self::state::{BallState, PaddleState, PaddleEdge, Block, BlockKind, GameMode};
+pub use self::versus::{MatchFormat, MatchState};
+pub use self::effects::{Effect, ActiveEffect, PowerUp, WIDE_FACTOR, SLOW_FACTOR};
+pub use self::level::{Level, LevelBlock, LevelSet, GRID_COLUMNS, GRID_ROWS, DEFAULT_LEVEL_SET, MAX_KEYS,
+                      PACKED_MAGIC, RANDOM_LEVEL_SET};
+pub use self::rng::Rng;
+pub use self::snapshot::{Snapshot, SnapshotBlock};
+pub use self::events::GameEvent;
+pub use self::check::{check_level, Problem};
+pub use self::generate::{generate, MIN_DIFFICULTY, MAX_DIFFICULTY};
+use self::physics::Collision;
+
+// Points awarded for each destroyed block
+const BLOCK_SCORE: u32 = 10;
+
+// Balls a team may lose before it's game over. Versus is played on points.
+pub const STARTING_LIVES: u32 = 3;
+
+// Paddle speed multiplier while boosting, unless the player picked another
+pub const BOOST_FACTOR: f32 = 2.0;
+
+// Games are played on a field of this size, whatever the size of the
+// screen, so physics never change with the window. Only games saved
+// before it was fixed may have a different one.
+pub const FIELD_SIZE: (f32, f32) = (1280.0, 720.0);
+
+// Sizes, as fractions of the field's (width, height)
+const BALL_SIZE:   f32        = 0.034723;
+const PADDLE_SIZE: (f32, f32) = (0.12, 0.034723);
+const BLOCK_SIZE:  (f32, f32) = (0.06, 0.0520845);
+
+// Ticks the ball takes to go from top to bottom, at its base speed.
+// Paddles go at three quarters of that.
+const BALL_CROSSING: f32 = 72.0;
+
+
+// What a single player is doing on a given tick.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct PlayerInput {
+    pub left:   bool,
+    pub right:  bool,
+    pub launch: bool,
+    pub boost:  bool,
+    // Absolute paddle position from device tilt, in thousandths of
+    // [-1.0, 1.0]. It is quantized so it survives the trip over
+    // the network unchanged.
+    pub steer:  Option<i16>,
+}
+
+impl PlayerInput {
+    pub fn new() -> PlayerInput {
+        PlayerInput {
+            left:   false,
+            right:  false,
+            launch: false,
+            boost:  false,
+            steer:  None,
+        }
+    }
+
+    pub fn set_steer(&mut self, axis: f32) {
+        let axis = axis.max(-1.0).min(1.0);
+        self.steer = Some((axis * 1000.0).round() as i16);
+    }
+
+    pub fn steer_axis(&self) -> Option<f32> {
+        self.steer.map(|steer| steer as f32 / 1000.0)
+    }
+
+    pub fn bits(&self) -> u32 {
+        (self.left   as u32)
+            | (self.right  as u32) << 1
+            | (self.launch as u32) << 2
+            | (self.boost  as u32) << 3
+    }
+
+    pub fn from_bits(bits: u32, steer: Option<i16>) -> PlayerInput {
+        PlayerInput {
+            left:   bits & 1 != 0,
+            right:  bits & 2 != 0,
+            launch: bits & 4 != 0,
+            boost:  bits & 8 != 0,
+            steer:  steer,
+        }
+    }
+}
+
+
+
+
+pub struct Game {
+    pub mode:         GameMode,
+    pub field:        (f32, f32),
+    pub tick:         u64,
+    pub rng:          Rng,
+
+    pub ball_state:   BallState,
+    pub paddles:      Vec<PaddleState>,
+    pub score:        u32,
+    pub lives:        u32,
+    pub versus:       MatchState,
+
+    pub levels:       LevelSet,
+    pub level:        usize,
+    pub effects:      Vec<ActiveEffect>,
+    pub powerups:     Vec<PowerUp>,
+
+    pub block_size:   (f32, f32),
+    pub level_blocks: Vec<Block>,
+    pub collided:     bool,
+    // What happened on the last tick
+    pub events:       Vec<GameEvent>,
+
+    // How much faster paddles go while boosting. Left alone online,
+    // since every peer has to agree on it.
+    pub boost_factor: f32,
+}
+
+
+impl Game {
+    pub fn new(mode: GameMode, field: (f32, f32), seed: u64) -> Game {
+        Game::with_levels(mode, field, seed, LevelSet::builtin())
+    }
+
+    pub fn with_levels(mode: GameMode, field: (f32, f32), seed: u64, levels: LevelSet) -> Game {
+        let mut game = Game {
+            mode:         mode,
+            field:        field,
+            tick:         0,
+            rng:          Rng::new(seed),
+
+            ball_state:   BallState::new(),
+            paddles:      vec![],
+            score:        0,
+            lives:        STARTING_LIVES,
+            versus:       MatchState::new(MatchFormat::new()),
+
+            levels:       levels,
+            level:        0,
+            effects:      vec![],
+            powerups:     vec![],
+
+            block_size:   (0.0, 0.0),
+            level_blocks: vec![],
+            collided:     false,
+            events:       vec![],
+
+            boost_factor: BOOST_FACTOR,
+        };
+
+        game.setup_paddles();
+        game.setup_blocks();
+        game
+    }
+
+    // Lays out the blocks of the current level
+    pub fn setup_blocks(&mut self) {
+        self.block_size = (self.field.0 * BLOCK_SIZE.0,
+                           self.field.1 * BLOCK_SIZE.1);
+
+        let blocks: Vec<Block> = self.levels.get(self.level).blocks.iter().map(|block| {
+            Block {
+                pos:    self.cell_position(block.cell),
+                color:  block.color.clone(),
+                active: true,
+                kind:   block.kind,
+                hp:     block.hp,
+            }
+        }).collect();
+        self.level_blocks = blocks;
+    }
+
+    // Center of a grid cell on the field
+    pub fn cell_position(&self, cell: (usize, usize)) -> (f32, f32) {
+        let left = (self.field.0 - (GRID_COLUMNS as f32 * self.block_size.0)) / 2.0
+            + (self.block_size.0 / 2.0);
+
+        // On versus, the grid sits right in the middle so
+        // both players are at the same distance from it
+        let top = match self.mode {
+            GameMode::Versus => (self.field.1 - (GRID_ROWS as f32 * self.block_size.1)) / 2.0
+                + (self.block_size.1 / 2.0),
+            _ => self.field.1 / 4.0,
+        };
+
+        (left + (cell.0 as f32 * self.block_size.0),
+         top + (cell.1 as f32 * self.block_size.1))
+    }
+
+    // Grid cell a point on the field falls in, if any
+    pub fn cell_at(&self, pos: (f32, f32)) -> Option<(usize, usize)> {
+        let first = self.cell_position((0, 0));
+        let column = ((pos.0 - first.0) / self.block_size.0 + 0.5).floor();
+        let row = ((pos.1 - first.1) / self.block_size.1 + 0.5).floor();
+        if column < 0.0 || row < 0.0
+            || column >= GRID_COLUMNS as f32 || row >= GRID_ROWS as f32 {
+                return None;
+            }
+        Some((column as usize, row as usize))
+    }
+
+    // Whether there's anything left to clear
+    pub fn breakable_left(&self) -> usize {
+        self.level_blocks.iter().filter(|block| block.kind.is_breakable()).count()
+    }
+
+    // Starts over on the current mode
+    pub fn reset(&mut self) {
+        self.score = 0;
+        self.lives = STARTING_LIVES;
+        self.level = 0;
+        self.effects.clear();
+        self.powerups.clear();
+        self.versus.reset();
+        self.setup_paddles();
+        self.setup_blocks();
+    }
+
+    // Fix some values which are field-dependent
+    fn fit_field(&mut self) {
+        self.ball_state.diameter = self.field.1 * BALL_SIZE;
+        self.ball_state.basespd = self.field.1 / BALL_CROSSING;
+
+        for paddle in &mut self.paddles {
+            paddle.ypos = match paddle.edge {
+                PaddleEdge::Bottom => 11.0 * self.field.1 / 12.0,
+                PaddleEdge::Top    => (self.field.1 / 12.0) - (self.field.1 * PADDLE_SIZE.1),
+            };
+            paddle.basespd = self.field.1 / BALL_CROSSING * 0.75;
+            paddle.spd = paddle.basespd;
+        }
+        self.fit_paddle_sizes();
+    }
+
+    fn fit_paddle_sizes(&mut self) {
+        for (i, paddle) in self.paddles.iter_mut().enumerate() {
+            let wide = self.effects.iter()
+                .any(|active| active.effect == Effect::Wide && active.player == i);
+            let stretch = if wide { WIDE_FACTOR } else { 1.0 };
+            paddle.sz = (self.field.0 * PADDLE_SIZE.0 * stretch,
+                         self.field.1 * PADDLE_SIZE.1);
+        }
+    }
+
+    pub fn is_slowed(&self) -> bool {
+        self.effects.iter().any(|active| active.effect == Effect::Slow)
+    }
+
+    // How fast the ball goes when launched or bounced
+    pub fn ball_speed(&self) -> f32 {
+        if self.is_slowed() {
+            self.ball_state.basespd * SLOW_FACTOR
+        } else {
+            self.ball_state.basespd
+        }
+    }
+
+    pub fn apply_effect(&mut self, effect: Effect, player: usize) {
+        match effect {
+            Effect::ExtraLife => {
+                if self.mode != GameMode::Versus {
+                    self.lives += 1;
+                }
+                return;
+            },
+            Effect::Slow => {
+                if !self.is_slowed() {
+                    self.ball_state.spd.0 *= SLOW_FACTOR;
+                    self.ball_state.spd.1 *= SLOW_FACTOR;
+                }
+            },
+            Effect::Wide => {},
+        }
+
+        // Catching the same thing again only makes it last longer
+        self.effects.retain(|active| !(active.effect == effect && active.player == player));
+        self.effects.push(ActiveEffect {
+            effect:    effect,
+            player:    player,
+            remaining: effect.duration(),
+        });
+        self.fit_paddle_sizes();
+    }
+
+    // Drops every effect and capsule. Only safe while the ball is
+    // stopped, since the ball's speed is left alone.
+    fn clear_effects(&mut self) {
+        self.effects.clear();
+        self.powerups.clear();
+        self.fit_paddle_sizes();
+    }
+
+    fn update_effects(&mut self) {
+        let was_slowed = self.is_slowed();
+        for active in &mut self.effects {
+            active.remaining = active.remaining.saturating_sub(1);
+        }
+        self.effects.retain(|active| active.remaining > 0);
+
+        if was_slowed && !self.is_slowed() {
+            self.ball_state.spd.0 /= SLOW_FACTOR;
+            self.ball_state.spd.1 /= SLOW_FACTOR;
+        }
+        self.fit_paddle_sizes();
+    }
+
+    fn update_powerups(&mut self) {
+        let mut caught = vec![];
+        {
+            let paddles = &self.paddles;
+            let height = self.field.1;
+            for powerup in &mut self.powerups {
+                powerup.pos.1 += powerup.spd;
+            }
+            self.powerups.retain(|powerup| {
+                for (i, paddle) in paddles.iter().enumerate() {
+                    let bounds = paddle.bounds();
+                    if powerup.pos.0 >= bounds.0 && powerup.pos.0 <= bounds.1
+                        && powerup.pos.1 >= bounds.2 && powerup.pos.1 <= bounds.3 {
+                            caught.push((powerup.effect, i, powerup.pos));
+                            return false;
+                        }
+                }
+                // Missed ones are gone for good
+                powerup.pos.1 >= 0.0 && powerup.pos.1 <= height
+            });
+        }
+
+        for (effect, player, pos) in caught {
+            self.apply_effect(effect, player);
+            self.events.push(GameEvent::PowerUpCaught {
+                effect: effect,
+                player: player,
+                pos:    pos,
+            });
+        }
+    }
+
+    // Creates paddles for the current game mode, evenly spread
+    // along the edges they defend.
+    pub fn setup_paddles(&mut self) {
+        let count = self.mode.paddle_count();
+        self.paddles.clear();
+        for i in 0..count {
+            let edge = self.mode.edge_for(i);
+            let mut paddle = PaddleState::new(edge);
+
+            let sharing = (0..count).filter(|&p| self.mode.edge_for(p) == edge).count();
+            let slot = (0..i).filter(|&p| self.mode.edge_for(p) == edge).count();
+            paddle.xpos = self.field.0 * (slot + 1) as f32 / (sharing + 1) as f32;
+            self.paddles.push(paddle);
+        }
+
+        self.ball_state.stopped = true;
+        self.ball_state.owner = 0;
+        self.fit_field();
+    }
+
+    // Clamps paddles to the field and pushes them apart so they never
+    // overlap. Paddles on the same edge are kept in order, from left to right.
+    fn separate_paddles(&mut self) {
+        let width = self.field.0;
+
+        for paddle in &mut self.paddles {
+            let halfwidth = paddle.sz.0 / 2.0;
+            paddle.xpos = paddle.xpos.max(halfwidth).min(width - halfwidth);
+        }
+
+        for i in 1..self.paddles.len() {
+            if self.paddles[i - 1].edge != self.paddles[i].edge {
+                continue;
+            }
+            let overlap = self.paddles[i - 1].bounds().1 - self.paddles[i].bounds().0;
+            if overlap > 0.0 {
+                self.paddles[i - 1].xpos -= overlap / 2.0;
+                self.paddles[i].xpos     += overlap / 2.0;
+            }
+        }
+
+        // Pushing may have sent someone offscreen, so walk back from both
+        // edges, shoving neighbours along
+        let count = self.paddles.len();
+        for i in 0..count {
+            let min_left = if i == 0 || self.paddles[i - 1].edge != self.paddles[i].edge {
+                0.0
+            } else {
+                self.paddles[i - 1].bounds().1
+            };
+            let halfwidth = self.paddles[i].sz.0 / 2.0;
+            if self.paddles[i].xpos - halfwidth < min_left {
+                self.paddles[i].xpos = min_left + halfwidth;
+            }
+        }
+        for i in (0..count).rev() {
+            let max_right = if i == count - 1 || self.paddles[i + 1].edge != self.paddles[i].edge {
+                width
+            } else {
+                self.paddles[i + 1].bounds().0
+            };
+            let halfwidth = self.paddles[i].sz.0 / 2.0;
+            if self.paddles[i].xpos + halfwidth > max_right {
+                self.paddles[i].xpos = max_right - halfwidth;
+            }
+        }
+    }
+
+
+
+
+
+
+    // Advances the simulation by one tick. `inputs` holds one entry per
+    // player; missing entries are treated as idle players.
+    pub fn step(&mut self, inputs: &[PlayerInput]) {
+        self.tick += 1;
+        self.events.clear();
+
+        // Process new input
+        let mut launch = false;
+        let boost_factor = self.boost_factor;
+        for (i, paddle) in self.paddles.iter_mut().enumerate() {
+            let input = match inputs.get(i) {
+                Some(input) => *input,
+                None => PlayerInput::new(),
+            };
+
+            // Paddle move speed depends on whether you're holding boost or not
+            paddle.spd = if input.boost {
+                paddle.basespd * boost_factor
+            } else {
+                paddle.basespd
+            };
+
+            if input.left {
+                paddle.xpos -= paddle.spd;
+            }
+            if input.right {
+                paddle.xpos += paddle.spd;
+            }
+
+            // Only whoever holds the ball can serve it
+            if input.launch
+                && self.ball_state.stopped
+                && self.ball_state.owner == i {
+                    launch = true;
+                }
+        }
+
+        // Once a game is over, serving starts a new one
+        if launch && self.is_over() {
+            self.reset();
+            launch = false;
+        }
+
+        if launch {
+            let initial_angle = self.rng.range(67.5, 113.5);
+            let speed = self.ball_speed();
+            self.ball_state.spd =
+                (speed * f32::cos(initial_angle.to_radians()),
+                 -speed * f32::sin(initial_angle.to_radians()) );
+
+            // Serving from the top goes downwards
+            if self.paddles[self.ball_state.owner].edge == PaddleEdge::Top {
+                self.ball_state.spd.1 *= -1.0;
+            }
+
+            self.ball_state.stopped = false;
+        }
+
+        // Process mobile input
+        {
+            // Axis goes from -1.0 (left edge) to 1.0 (right edge),
+            // so the paddle is centered on neutral pose
+            let halfwidth = self.field.0 / 2.0;
+            for (i, paddle) in self.paddles.iter_mut().enumerate() {
+                let steer = inputs.get(i).and_then(|input| input.steer_axis());
+                if let Some(axis) = steer {
+                    paddle.xpos = halfwidth + (halfwidth * axis);
+                }
+            }
+        }
+
+        // Clamp paddle positions
+        self.separate_paddles();
+
+        // Handle ball state
+        if self.ball_state.stopped {
+            self.ball_state.pos.0 = self.paddles[self.ball_state.owner].xpos;
+            self.ball_state.pos.1 = match self.paddles[self.ball_state.owner].edge {
+                PaddleEdge::Bottom => 21.0 * self.field.1 / 24.0,
+                PaddleEdge::Top    => 3.0 * self.field.1 / 24.0,
+            };
+            if self.ball_state.afterimages.len() > 0 {
+                self.ball_state.afterimages.clear();
+            }
+        } else {
+            // Transform position
+            self.ball_state.pos.0 += self.ball_state.spd.0;
+            self.ball_state.pos.1 += self.ball_state.spd.1;
+
+            // Handle basic boundary collision
+            let ball_radius = self.ball_state.diameter / 2.0;
+            let ball_boundary = (self.ball_state.pos.0 - ball_radius,   // left
+                                 self.ball_state.pos.0 + ball_radius,   // right
+                                 self.ball_state.pos.1 - ball_radius,   // top
+                                 self.ball_state.pos.1 + ball_radius ); // bottom
+
+            // Handle X axis
+            if ball_boundary.0 < 0.0 && self.ball_state.spd.0 < 0.0 {
+                self.ball_state.pos.0 = ball_radius;
+                self.ball_state.spd.0 *= -1.0;
+                self.events.push(GameEvent::WallHit { pos: self.ball_state.pos });
+            } else if ball_boundary.1 > self.field.0 && self.ball_state.spd.0 > 0.0 {
+                self.ball_state.pos.0 = self.field.0 - ball_radius;
+                self.ball_state.spd.0 *= -1.0;
+                self.events.push(GameEvent::WallHit { pos: self.ball_state.pos });
+            }
+
+            // Handle Y axis
+            // On versus, the top edge is the second player's goal
+            let top_is_goal = self.mode == GameMode::Versus;
+            if !top_is_goal && ball_boundary.2 < 0.0 && self.ball_state.spd.1 < 0.0 {
+                self.ball_state.pos.1 = ball_radius;
+                self.ball_state.spd.1 *= -1.0;
+                self.events.push(GameEvent::WallHit { pos: self.ball_state.pos });
+            } else if top_is_goal && ball_boundary.3 < 0.0 && self.ball_state.spd.1 < 0.0 {
+                // Bottom player scores, top player serves
+                self.events.push(GameEvent::BallLost { pos: self.ball_state.pos });
+                self.versus.goal(0);
+                self.ball_state.owner = 1;
+                self.ball_state.stopped = true;
+            } else if ball_boundary.2 > self.field.1 && self.ball_state.spd.1 > 0.0 {
+                self.events.push(GameEvent::BallLost { pos: self.ball_state.pos });
+                if self.mode == GameMode::Versus {
+                    self.versus.goal(1);
+                    self.ball_state.owner = 0;
+                } else if self.lives > 0 {
+                    self.lives -= 1;
+                }
+
+                // Respawn ball
+                self.ball_state.stopped = true;
+                if self.mode != GameMode::Versus {
+                    self.clear_effects();
+                }
+            }
+
+            // Handle paddle collision
+            let ball_speed = self.ball_speed();
+            for (i, paddle) in self.paddles.iter().enumerate() {
+                let paddle_bounds = paddle.bounds();
+
+                // Check if we're within Y and X range, respectively.
+                // Top paddles are mirrored: the ball has to be climbing, and
+                // its top boundary is what hits them.
+                let hits = match paddle.edge {
+                    PaddleEdge::Bottom =>
+                        self.ball_state.spd.1 > 0.0 // If we're descending, and...
+                        && ((ball_boundary.3 >= paddle_bounds.2) // We're at least intersecting...
+                            && (ball_boundary.3 <= paddle_bounds.3)), // the paddle in any way...
+                    PaddleEdge::Top =>
+                        self.ball_state.spd.1 < 0.0
+                        && ((ball_boundary.2 >= paddle_bounds.2)
+                            && (ball_boundary.2 <= paddle_bounds.3)),
+                };
+
+                if hits
+                    // Then we verify if we're within X range...
+                    && (ball_boundary.1 >= paddle_bounds.0 && ball_boundary.0 <= paddle_bounds.1) {
+                        // We kind of bounce proportionally to the relative paddle position.
+                        // The further away from the center of the paddle, the more open the
+                        // bouncing angle is, scaling to 0.0 to 45.0 towards the edge.
+                        // We first calculate a ratio [-1.0, 1.0], 0.0 being the paddle center.
+                        let ratio = (-2.0 * ((self.ball_state.pos.0 - paddle_bounds.0)
+                                             / (paddle_bounds.1 - paddle_bounds.0)))
+                            + 1.0;
+
+                        // We compute the angle by assuming 90 degrees and then adding an angle
+                        // in range [-45, 45]
+                        let theta: f32 = ((90.0 + (ratio * 45.0)) as f32).to_radians();
+
+                        // And now we apply theta to our ball's base speed, distributing it to
+                        // the axis
+                        self.ball_state.spd = ( ball_speed * f32::cos(theta),
+                                                -ball_speed * f32::sin(theta) );
+                        if paddle.edge == PaddleEdge::Top {
+                            self.ball_state.spd.1 *= -1.0;
+                        }
+                        self.ball_state.owner = i;
+                        self.events.push(GameEvent::PaddleHit {
+                            player: i,
+                            pos:    self.ball_state.pos,
+                        });
+                        break;
+                    }
+            }
+
+            // Afterimages
+            if self.ball_state.afterimages.len() >= 7 {
+                self.ball_state.afterimages.drain(0..1);
+            }
+            self.ball_state.afterimages.push(self.ball_state.pos);
+        } // End of moving ball events
+
+        self.update_powerups();
+        self.update_effects();
+
+
+
+        // Basic collision
+        let tilesz = self.block_size;
+
+        self.collided = false;
+
+        // We iterate over all blocks, hurting the ones we hit,
+        // and then only keep those who still have some hits left.
+        // This is not the best way to handle collision,
+        // but it's enough for the amount of onscreen objs
+        let mut retrieved_collisions = vec![];
+        {
+            let ballstate = &self.ball_state;
+            let events = &mut self.events;
+            for block in &mut self.level_blocks {
+                let tile_bounds = (block.pos.0 - tilesz.0,
+                                   block.pos.0 + tilesz.0,
+                                   block.pos.1 - tilesz.1,
+                                   block.pos.1 + tilesz.1);
+                if let Some(collision) = Collision::collides(ballstate, block.pos, tile_bounds) {
+                    retrieved_collisions.push(collision);
+                    if block.kind.is_breakable() {
+                        block.hp = block.hp.saturating_sub(1);
+                    }
+                    if block.hp > 0 {
+                        events.push(GameEvent::BlockHit { pos: block.pos, kind: block.kind });
+                    }
+                }
+            }
+        }
+
+        let mut broken = vec![];
+        self.level_blocks.retain(|block| {
+            if block.hp == 0 {
+                broken.push(block.clone());
+                return false;
+            }
+            true
+        });
+        self.score += BLOCK_SCORE * broken.len() as u32;
+        for block in &broken {
+            self.events.push(GameEvent::BlockBroken {
+                pos:   block.pos,
+                kind:  block.kind,
+                color: block.color.clone(),
+            });
+        }
+
+        // Capsules head to whoever broke their block
+        for block in &broken {
+            if let BlockKind::PowerUp(effect) = block.kind {
+                let direction = match self.paddles[self.ball_state.owner].edge {
+                    PaddleEdge::Bottom => 1.0,
+                    PaddleEdge::Top    => -1.0,
+                };
+                self.powerups.push(PowerUp {
+                    pos:    block.pos,
+                    effect: effect,
+                    spd:    direction * self.field.1 / 240.0,
+                });
+            }
+        }
+
+        // On versus, blocks count towards whoever touched the ball last,
+        // and the field is refilled so the match can go on
+        if self.mode == GameMode::Versus {
+            self.versus.blocks_destroyed(self.ball_state.owner,
+                                         broken.len() as u32);
+            if self.breakable_left() == 0 {
+                self.setup_blocks();
+            }
+            if self.versus.is_over() {
+                self.ball_state.stopped = true;
+            }
+        } else if !broken.is_empty() && self.breakable_left() == 0 {
+            // On to the next level, starting over from a serve
+            self.events.push(GameEvent::LevelCleared { level: self.level });
+            self.level += 1;
+            self.setup_blocks();
+            self.ball_state.stopped = true;
+            self.clear_effects();
+        }
+
+        // Calculate resulting vector
+        // Multiblock consensus
+        let final_collision = retrieved_collisions.iter()
+            .fold(Collision::new(),
+                  |acc, ref val| {
+                      let mut acc = acc;
+                      acc.valid = true;
+                      acc.vector.0 += val.vector.0;
+                      acc.vector.1 += val.vector.1;
+                      acc.vector.0 = acc.vector.0.signum();
+                      acc.vector.1 = acc.vector.1.signum();
+                      acc
+                  });
+
+        // Single-block "consensus"
+        //let final_collision = match retrieved_collisions.first() {
+        //    Some(collision) => collision.clone(),
+        //    None => Collision::new(),
+        //};
+
+        // Apply result to ball.
+        // Notice that the resulting vector only ensures that
+        // the ball's speeds have the same signal as the result
+        // vector.
+        if final_collision.valid {
+            let mut ball_spd = self.ball_state.spd;
+            if final_collision.vector.0 != 0.0
+                && ball_spd.0.signum() != final_collision.vector.0.signum() {
+                ball_spd.0 *= -1.0;
+            }
+            if final_collision.vector.1 != 0.0
+                && ball_spd.1.signum() != final_collision.vector.1.signum() {
+                ball_spd.1 *= -1.0;
+            }
+            self.ball_state.spd = ball_spd;
+        }
+    }
+
+
+
+    // Out of lives, or somebody won the match
+    pub fn is_over(&self) -> bool {
+        match self.mode {
+            GameMode::Versus => self.versus.is_over(),
+            _ => self.lives == 0,
+        }
+    }
+
+    // Cheap fingerprint of everything which affects the outcome of the game.
+    // Clients compare these to find out whether they have drifted apart.
+    pub fn checksum(&self) -> u64 {
+        // FNV-1a
+        let mut hash: u64 = 0xCBF2_9CE4_8422_2325;
+        {
+            let mut feed = |value: u64| {
+                for i in 0..8 {
+                    hash ^= (value >> (i * 8)) & 0xFF;
+                    hash = hash.wrapping_mul(0x0000_0100_0000_01B3);
+                }
+            };
+
+            feed(self.tick);
+            feed(self.rng.state());
+            feed(self.ball_state.pos.0.to_bits() as u64);
+            feed(self.ball_state.pos.1.to_bits() as u64);
+            feed(self.ball_state.spd.0.to_bits() as u64);
+            feed(self.ball_state.spd.1.to_bits() as u64);
+            feed(self.ball_state.stopped as u64);
+            feed(self.ball_state.owner as u64);
+            for paddle in &self.paddles {
+                feed(paddle.xpos.to_bits() as u64);
+            }
+            feed(self.score as u64);
+            feed(self.lives as u64);
+            feed(self.level as u64);
+            for active in &self.effects {
+                feed(active.remaining as u64);
+                feed(active.player as u64);
+            }
+            for powerup in &self.powerups {
+                feed(powerup.pos.1.to_bits() as u64);
+            }
+            feed(self.versus.points[0] as u64);
+            feed(self.versus.points[1] as u64);
+            feed(self.level_blocks.len() as u64);
+            for block in &self.level_blocks {
+                feed(block.pos.0.to_bits() as u64);
+                feed(block.pos.1.to_bits() as u64);
+                feed(block.hp as u64);
+            }
+        }
+        hash
+    }
+}
@@ -1,4 +1,4 @@
-use world::state::BallState;
+use sim::state::BallState;
 
 #[derive(PartialEq, PartialOrd, Clone)]
 pub struct Collision {
@@ -114,15 +114,10 @@ impl Collision {
                     // Top quadrant.
                     // Make ball move up (negative Y)
                     vector.1 = -1.0;
-                    js! { console.log("TOP!"); }
                 },
 
                 // What the heck
-                _ => {
-                    js! {
-                        console.log("Errr, what the heck is going on? Angle is " + @{collision_angle});
-                    }
-                } // No transformation needed
+                _ => {} // No transformation needed
             }
             
             Some(Collision::from(ball_pos, vector))
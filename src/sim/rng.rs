@@ -0,0 +1,47 @@
+// Small xorshift generator. We can't rely on Math.random() anymore,
+// since every client must roll the exact same numbers.
+#[derive(Clone)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Rng {
+        // Zero would get the generator stuck
+        let state = seed ^ 0x9E37_79B9_7F4A_7C15;
+        Rng {
+            state: if state == 0 { 0x2545_F491_4F6C_DD1D } else { state },
+        }
+    }
+
+    // Picks up exactly where another generator was
+    pub fn from_state(state: u64) -> Rng {
+        if state == 0 {
+            Rng::new(0)
+        } else {
+            Rng { state: state }
+        }
+    }
+
+    pub fn state(&self) -> u64 {
+        self.state
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        let mut x = self.state;
+        x ^= x >> 12;
+        x ^= x << 25;
+        x ^= x >> 27;
+        self.state = x;
+        x.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    // Uniform in [0.0, 1.0)
+    pub fn next_f32(&mut self) -> f32 {
+        (self.next_u64() >> 40) as f32 / (1u64 << 24) as f32
+    }
+
+    pub fn range(&mut self, min: f32, max: f32) -> f32 {
+        min + (self.next_f32() * (max - min))
+    }
+}
@@ -0,0 +1,381 @@
+use std::fmt;
+
+use sim::{Game, Block, BlockKind, Rng, Effect, ActiveEffect, PowerUp};
+
+// Everything about a game which changes while playing. Sizes and speeds
+// are left out, since they only depend on the field, so restoring a
+// snapshot into a game with the same mode and field puts that game on
+// the exact same state it was taken from.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Snapshot {
+    pub tick:    u64,
+    pub rng:     u64,
+
+    // From BallState
+    pub ball_pos:     (f32, f32),
+    pub ball_spd:     (f32, f32),
+    pub ball_stopped: bool,
+    pub ball_owner:   usize,
+
+    // PaddleState::xpos and PaddleState::spd, for every paddle
+    pub paddles: Vec<(f32, f32)>,
+
+    pub score:   u32,
+    pub lives:   u32,
+    pub points:  [u32; 2],
+    pub winner:  Option<usize>,
+
+    pub level:    usize,
+    pub effects:  Vec<ActiveEffect>,
+    pub powerups: Vec<PowerUp>,
+
+    // Every block left in level_blocks
+    pub blocks:  Vec<SnapshotBlock>,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct SnapshotBlock {
+    pub pos:   (f32, f32),
+    pub color: String,
+    pub kind:  BlockKind,
+    pub hp:    u32,
+}
+
+impl Game {
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            tick:         self.tick,
+            rng:          self.rng.state(),
+
+            ball_pos:     self.ball_state.pos,
+            ball_spd:     self.ball_state.spd,
+            ball_stopped: self.ball_state.stopped,
+            ball_owner:   self.ball_state.owner,
+
+            paddles:      self.paddles.iter().map(|paddle| (paddle.xpos, paddle.spd)).collect(),
+
+            score:        self.score,
+            lives:        self.lives,
+            points:       self.versus.points,
+            winner:       self.versus.winner,
+
+            level:        self.level,
+            effects:      self.effects.clone(),
+            powerups:     self.powerups.clone(),
+
+            blocks:       self.level_blocks.iter().map(|block| {
+                SnapshotBlock {
+                    pos:   block.pos,
+                    color: block.color.clone(),
+                    kind:  block.kind,
+                    hp:    block.hp,
+                }
+            }).collect(),
+        }
+    }
+
+    // Fails when the snapshot was taken from a game with another number
+    // of players, leaving this one untouched
+    pub fn restore(&mut self, snapshot: &Snapshot) -> Result<(), String> {
+        if snapshot.paddles.len() != self.paddles.len() {
+            return Err(format!("snapshot is for {} players, not {}",
+                               snapshot.paddles.len(), self.paddles.len()));
+        }
+
+        self.tick = snapshot.tick;
+        self.rng = Rng::from_state(snapshot.rng);
+
+        self.ball_state.pos = snapshot.ball_pos;
+        self.ball_state.spd = snapshot.ball_spd;
+        self.ball_state.stopped = snapshot.ball_stopped;
+        self.ball_state.owner = snapshot.ball_owner;
+        self.ball_state.afterimages.clear();
+
+        for (paddle, &(xpos, spd)) in self.paddles.iter_mut().zip(snapshot.paddles.iter()) {
+            paddle.xpos = xpos;
+            paddle.spd = spd;
+        }
+
+        self.score = snapshot.score;
+        self.lives = snapshot.lives;
+        self.versus.points = snapshot.points;
+        self.versus.winner = snapshot.winner;
+
+        self.level = snapshot.level;
+        self.effects = snapshot.effects.clone();
+        self.powerups = snapshot.powerups.clone();
+        self.fit_paddle_sizes();
+
+        self.level_blocks = snapshot.blocks.iter().map(|block| {
+            Block {
+                pos:    block.pos,
+                color:  block.color.clone(),
+                active: true,
+                kind:   block.kind,
+                hp:     block.hp,
+            }
+        }).collect();
+        Ok(())
+    }
+}
+
+
+
+// Floats are written as their bits, in hex. Printing them as decimals
+// could round them, and a spectator off by a single bit would slowly
+// drift away from the players.
+fn float(value: f32) -> String {
+    format!("{:x}", value.to_bits())
+}
+
+impl fmt::Display for Snapshot {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {:x} {} {} {} {} {} {}",
+               self.tick, self.rng,
+               float(self.ball_pos.0), float(self.ball_pos.1),
+               float(self.ball_spd.0), float(self.ball_spd.1),
+               self.ball_stopped as u8, self.ball_owner)?;
+
+        write!(f, " {}", self.paddles.len())?;
+        for &(xpos, spd) in &self.paddles {
+            write!(f, " {} {}", float(xpos), float(spd))?;
+        }
+
+        match self.winner {
+            Some(winner) => write!(f, " {} {} {} {} {}", self.score, self.lives,
+                                   self.points[0], self.points[1], winner)?,
+            None         => write!(f, " {} {} {} {} -", self.score, self.lives,
+                                   self.points[0], self.points[1])?,
+        }
+
+        write!(f, " {}", self.level)?;
+        write!(f, " {}", self.effects.len())?;
+        for active in &self.effects {
+            write!(f, " {} {} {}", active.effect.name(), active.player, active.remaining)?;
+        }
+        write!(f, " {}", self.powerups.len())?;
+        for powerup in &self.powerups {
+            write!(f, " {} {} {} {}", powerup.effect.name(),
+                   float(powerup.pos.0), float(powerup.pos.1), float(powerup.spd))?;
+        }
+
+        write!(f, " {}", self.blocks.len())?;
+        for block in &self.blocks {
+            write!(f, " {} {} {} {} {}", float(block.pos.0), float(block.pos.1),
+                   block.kind.name(), block.hp, block.color)?;
+        }
+        Ok(())
+    }
+}
+
+
+
+fn field<'a, I>(fields: &mut I, name: &str) -> Result<&'a str, String>
+    where I: Iterator<Item = &'a str> {
+    fields.next().ok_or_else(|| format!("missing {}", name))
+}
+
+fn number<'a, I, T>(fields: &mut I, name: &str) -> Result<T, String>
+    where I: Iterator<Item = &'a str>,
+          T: ::std::str::FromStr {
+    let value = field(fields, name)?;
+    value.parse().map_err(|_| format!("invalid {}: {}", name, value))
+}
+
+fn parse_effect<'a, I>(fields: &mut I) -> Result<Effect, String>
+    where I: Iterator<Item = &'a str> {
+    let name = field(fields, "effect")?;
+    Effect::from_name(name).ok_or_else(|| format!("invalid effect: {}", name))
+}
+
+fn parse_float<'a, I>(fields: &mut I, name: &str) -> Result<f32, String>
+    where I: Iterator<Item = &'a str> {
+    let value = field(fields, name)?;
+    u32::from_str_radix(value, 16)
+        .map(f32::from_bits)
+        .map_err(|_| format!("invalid {}: {}", name, value))
+}
+
+impl Snapshot {
+    // Reads a snapshot off the given fields, leaving the rest alone
+    pub fn parse<'a, I>(fields: &mut I) -> Result<Snapshot, String>
+        where I: Iterator<Item = &'a str> {
+        let tick = number(fields, "tick")?;
+        let rng = field(fields, "rng state")?;
+        let rng = u64::from_str_radix(rng, 16)
+            .map_err(|_| format!("invalid rng state: {}", rng))?;
+
+        let ball_pos = (parse_float(fields, "ball x")?, parse_float(fields, "ball y")?);
+        let ball_spd = (parse_float(fields, "ball x speed")?, parse_float(fields, "ball y speed")?);
+        let ball_stopped = match field(fields, "ball stopped")? {
+            "0" => false,
+            "1" => true,
+            other => return Err(format!("invalid ball stopped: {}", other)),
+        };
+        let ball_owner = number(fields, "ball owner")?;
+
+        // Indices into the paddles are checked here, since the game
+        // would just panic on them later
+        let count: usize = number(fields, "paddle count")?;
+        if count == 0 || count > 2 {
+            return Err(format!("invalid paddle count: {}", count));
+        }
+        if ball_owner >= count {
+            return Err(format!("invalid ball owner: {}", ball_owner));
+        }
+        let mut paddles = vec![];
+        for _ in 0..count {
+            paddles.push((parse_float(fields, "paddle x")?, parse_float(fields, "paddle speed")?));
+        }
+
+        let score = number(fields, "score")?;
+        let lives = number(fields, "lives")?;
+        let points = [number(fields, "points")?, number(fields, "points")?];
+        let winner = match field(fields, "winner")? {
+            "-" => None,
+            winner => match winner.parse() {
+                Ok(player) if player < points.len() => Some(player),
+                _ => return Err(format!("invalid winner: {}", winner)),
+            },
+        };
+
+        let level = number(fields, "level")?;
+
+        let count: usize = number(fields, "effect count")?;
+        let mut effects = vec![];
+        for _ in 0..count {
+            let effect = parse_effect(fields)?;
+            let player = number(fields, "effect player")?;
+            if player >= paddles.len() {
+                return Err(format!("invalid effect player: {}", player));
+            }
+            effects.push(ActiveEffect {
+                effect:    effect,
+                player:    player,
+                remaining: number(fields, "effect ticks")?,
+            });
+        }
+
+        let count: usize = number(fields, "capsule count")?;
+        let mut powerups = vec![];
+        for _ in 0..count {
+            powerups.push(PowerUp {
+                effect: parse_effect(fields)?,
+                pos:    (parse_float(fields, "capsule x")?, parse_float(fields, "capsule y")?),
+                spd:    parse_float(fields, "capsule speed")?,
+            });
+        }
+
+        let count: usize = number(fields, "block count")?;
+        let mut blocks = vec![];
+        for _ in 0..count {
+            let pos = (parse_float(fields, "block x")?, parse_float(fields, "block y")?);
+            let kind = field(fields, "block kind")?;
+            let kind = BlockKind::from_name(kind).ok_or_else(|| format!("invalid block kind: {}", kind))?;
+            blocks.push(SnapshotBlock {
+                pos:   pos,
+                kind:  kind,
+                hp:    number(fields, "block hp")?,
+                color: field(fields, "block color")?.to_string(),
+            });
+        }
+
+        Ok(Snapshot {
+            tick:         tick,
+            rng:          rng,
+            ball_pos:     ball_pos,
+            ball_spd:     ball_spd,
+            ball_stopped: ball_stopped,
+            ball_owner:   ball_owner,
+            paddles:      paddles,
+            score:        score,
+            lives:        lives,
+            points:       points,
+            winner:       winner,
+            level:        level,
+            effects:      effects,
+            powerups:     powerups,
+            blocks:       blocks,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sim::{GameMode, FIELD_SIZE};
+
+    fn versus_snapshot() -> Snapshot {
+        let mut game = Game::new(GameMode::Versus, FIELD_SIZE, 7);
+        for _ in 0..60 {
+            game.step(&[]);
+        }
+        game.snapshot()
+    }
+
+    fn reparse(snapshot: &Snapshot) -> Result<Snapshot, String> {
+        let text = snapshot.to_string();
+        Snapshot::parse(&mut text.split_whitespace())
+    }
+
+    #[test]
+    fn snapshots_round_trip() {
+        let snapshot = versus_snapshot();
+        assert_eq!(reparse(&snapshot), Ok(snapshot));
+    }
+
+    #[test]
+    fn ball_owner_must_have_a_paddle() {
+        let mut snapshot = versus_snapshot();
+        snapshot.ball_owner = 2;
+        assert!(reparse(&snapshot).is_err());
+    }
+
+    #[test]
+    fn paddle_count_is_checked() {
+        let mut snapshot = versus_snapshot();
+        snapshot.paddles.clear();
+        snapshot.ball_owner = 0;
+        assert!(reparse(&snapshot).is_err());
+
+        let mut snapshot = versus_snapshot();
+        snapshot.paddles.push((0.0, 0.0));
+        assert!(reparse(&snapshot).is_err());
+    }
+
+    #[test]
+    fn effect_player_must_have_a_paddle() {
+        let mut snapshot = versus_snapshot();
+        snapshot.effects.push(ActiveEffect {
+            effect:    Effect::Wide,
+            player:    2,
+            remaining: 100,
+        });
+        assert!(reparse(&snapshot).is_err());
+
+        snapshot.effects[0].player = 1;
+        assert!(reparse(&snapshot).is_ok());
+    }
+
+    #[test]
+    fn winner_must_be_a_player() {
+        let mut snapshot = versus_snapshot();
+        snapshot.winner = Some(2);
+        assert!(reparse(&snapshot).is_err());
+
+        snapshot.winner = Some(1);
+        assert!(reparse(&snapshot).is_ok());
+    }
+
+    #[test]
+    fn snapshots_only_fit_games_with_as_many_players() {
+        let snapshot = versus_snapshot();
+        let mut game = Game::new(GameMode::Single, FIELD_SIZE, 7);
+        let before = game.checksum();
+
+        assert!(game.restore(&snapshot).is_err());
+        assert_eq!(game.checksum(), before);
+        // Still playable afterwards
+        game.step(&[]);
+    }
+}
@@ -0,0 +1,175 @@
+use sim::effects::Effect;
+
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum GameMode {
+    Single,
+    Coop,
+    Versus,
+}
+
+impl GameMode {
+    pub fn next(&self) -> GameMode {
+        match *self {
+            GameMode::Single => GameMode::Coop,
+            GameMode::Coop   => GameMode::Versus,
+            GameMode::Versus => GameMode::Single,
+        }
+    }
+
+    pub fn previous(&self) -> GameMode {
+        match *self {
+            GameMode::Single => GameMode::Versus,
+            GameMode::Coop   => GameMode::Single,
+            GameMode::Versus => GameMode::Coop,
+        }
+    }
+
+    // Versus is played for the win, not for points
+    pub fn keeps_high_scores(&self) -> bool {
+        *self != GameMode::Versus
+    }
+
+    pub fn paddle_count(&self) -> usize {
+        match *self {
+            GameMode::Single => 1,
+            GameMode::Coop   => 2,
+            GameMode::Versus => 2,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            GameMode::Single => "SINGLE",
+            GameMode::Coop   => "CO-OP",
+            GameMode::Versus => "VERSUS",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<GameMode> {
+        match name {
+            "SINGLE" => Some(GameMode::Single),
+            "CO-OP"  => Some(GameMode::Coop),
+            "VERSUS" => Some(GameMode::Versus),
+            _ => None,
+        }
+    }
+
+    // Which screen edge a player's paddle defends
+    pub fn edge_for(&self, player: usize) -> PaddleEdge {
+        match (*self, player) {
+            (GameMode::Versus, 1) => PaddleEdge::Top,
+            _ => PaddleEdge::Bottom,
+        }
+    }
+}
+
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum PaddleEdge {
+    Bottom,
+    Top,
+}
+
+#[derive(Clone)]
+pub struct BallState {
+    pub diameter: f32,
+    pub pos:      (f32, f32),
+    pub spd:      (f32, f32),
+    pub stopped:  bool,
+    pub basespd:  f32,
+    pub afterimages: Vec<(f32, f32)>,
+    // Index of the paddle which last touched the ball. It is also the
+    // paddle the ball rests on while stopped.
+    pub owner:    usize,
+}
+
+impl BallState {
+    pub fn new() -> BallState {
+        BallState {
+            diameter:    0.0,
+            pos:         (0.0, 0.0),
+            spd:         (0.0, 0.0),
+            stopped:     true,
+            basespd:     0.0,
+            afterimages: Vec::with_capacity(7),
+            owner:       0,
+        }
+    }
+}
+
+
+
+
+
+#[derive(Clone)]
+pub struct PaddleState {
+    pub xpos:     f32,
+    pub ypos:     f32,
+    pub spd:      f32,
+    pub sz:       (f32, f32),
+    pub basespd:  f32,
+    pub edge:     PaddleEdge,
+}
+
+impl PaddleState {
+    pub fn new(edge: PaddleEdge) -> PaddleState {
+        PaddleState {
+            xpos:     0.0,
+            ypos:     0.0,
+            spd:      0.0,
+            basespd:  0.0,
+            sz:       (0.0, 0.0),
+            edge:     edge,
+        }
+    }
+
+    // Bounds: (left, right, top, bottom)
+    pub fn bounds(&self) -> (f32, f32, f32, f32) {
+        let halfwidth = self.sz.0 / 2.0;
+        (self.xpos - halfwidth,
+         self.xpos + halfwidth,
+         self.ypos,
+         self.ypos + self.sz.1)
+    }
+}
+
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum BlockKind {
+    Normal,
+    // Can't be destroyed, and doesn't need to be for clearing a level
+    Solid,
+    // Drops a capsule with the given effect
+    PowerUp(Effect),
+}
+
+impl BlockKind {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            BlockKind::Normal          => "normal",
+            BlockKind::Solid           => "solid",
+            BlockKind::PowerUp(effect) => effect.name(),
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<BlockKind> {
+        match name {
+            "normal" => Some(BlockKind::Normal),
+            "solid"  => Some(BlockKind::Solid),
+            _ => Effect::from_name(name).map(BlockKind::PowerUp),
+        }
+    }
+
+    pub fn is_breakable(&self) -> bool {
+        *self != BlockKind::Solid
+    }
+}
+
+#[derive(Clone)]
+pub struct Block {
+    pub pos:    (f32, f32),
+    pub color:  String,
+    pub active: bool,
+    pub kind:   BlockKind,
+    // Hits left until it breaks
+    pub hp:     u32,
+}
+
@@ -0,0 +1,66 @@
+// Rules for a versus match. Players score by destroying blocks with
+// balls they touched last, and by getting the ball past the opponent's edge.
+#[derive(Clone)]
+pub struct MatchFormat {
+    pub target:       u32, // First to reach this many points wins
+    pub block_points: u32,
+    pub goal_points:  u32,
+}
+
+impl MatchFormat {
+    pub fn new() -> MatchFormat {
+        MatchFormat {
+            target:       30,
+            block_points: 1,
+            goal_points:  5,
+        }
+    }
+}
+
+pub struct MatchState {
+    pub format: MatchFormat,
+    pub points: [u32; 2],
+    pub winner: Option<usize>,
+}
+
+impl MatchState {
+    pub fn new(format: MatchFormat) -> MatchState {
+        MatchState {
+            format: format,
+            points: [0, 0],
+            winner: None,
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.points = [0, 0];
+        self.winner = None;
+    }
+
+    pub fn is_over(&self) -> bool {
+        self.winner.is_some()
+    }
+
+    pub fn blocks_destroyed(&mut self, player: usize, count: u32) {
+        let points = self.format.block_points * count;
+        self.award(player, points);
+    }
+
+    // Ball went past the opponent's edge
+    pub fn goal(&mut self, player: usize) {
+        let points = self.format.goal_points;
+        self.award(player, points);
+    }
+
+    fn award(&mut self, player: usize, points: u32) {
+        // Nothing counts once the match is decided
+        if self.is_over() || player >= self.points.len() {
+            return;
+        }
+
+        self.points[player] += points;
+        if self.points[player] >= self.format.target {
+            self.winner = Some(player);
+        }
+    }
+}
@@ -0,0 +1,46 @@
+// Persistent data.
+// The game keeps everything on the browser's localStorage, which is a
+// plain string-to-string map. Anything stored goes through the Storage
+// trait, so native tools can use an in-memory store instead.
+
+use std::collections::HashMap;
+
+pub mod scores;
+pub mod save;
+
+pub use self::scores::{HighScores, ScoreEntry};
+pub use self::save::SavedGame;
+
+pub trait Storage {
+    fn load(&self, key: &str) -> Option<String>;
+    fn save(&mut self, key: &str, value: &str) -> Result<(), String>;
+    fn remove(&mut self, key: &str);
+}
+
+// Forgets everything once dropped
+pub struct MemoryStorage {
+    entries: HashMap<String, String>,
+}
+
+impl MemoryStorage {
+    pub fn new() -> MemoryStorage {
+        MemoryStorage {
+            entries: HashMap::new(),
+        }
+    }
+}
+
+impl Storage for MemoryStorage {
+    fn load(&self, key: &str) -> Option<String> {
+        self.entries.get(key).cloned()
+    }
+
+    fn save(&mut self, key: &str, value: &str) -> Result<(), String> {
+        self.entries.insert(key.to_string(), value.to_string());
+        Ok(())
+    }
+
+    fn remove(&mut self, key: &str) {
+        self.entries.remove(key);
+    }
+}
@@ -0,0 +1,202 @@
+use sim::{Game, GameMode, LevelSet, Snapshot};
+use storage::Storage;
+
+pub const SAVE_KEY: &'static str = "brickbreak.save";
+
+// Bump this whenever the format changes, and teach decode() how to
+// read the old one
+pub const SAVE_VERSION: u32 = 1;
+
+// A game in progress, to be picked up later.
+//
+// Stored as text: a header with the format version, how the game was
+// set up, and a snapshot of everything else.
+//
+//     SAVE 1
+//     SINGLE CLASSIC 1280 720
+//     <snapshot>
+#[derive(Clone, PartialEq, Debug)]
+pub struct SavedGame {
+    pub mode:      GameMode,
+    pub level_set: String,
+    pub field:     (f32, f32),
+    pub snapshot:  Snapshot,
+}
+
+impl SavedGame {
+    pub fn from_game(game: &Game) -> SavedGame {
+        SavedGame {
+            mode:      game.mode,
+            level_set: game.levels.name.clone(),
+            field:     game.field,
+            snapshot:  game.snapshot(),
+        }
+    }
+
+    // Rebuilds the game exactly as it was saved
+    pub fn restore(&self) -> Result<Game, String> {
+        let levels = LevelSet::by_name(&self.level_set)
+            .ok_or_else(|| format!("unknown level set: {}", self.level_set))?;
+        let mut game = Game::with_levels(self.mode, self.field, 0, levels);
+        game.restore(&self.snapshot)?;
+        Ok(game)
+    }
+
+    pub fn load(storage: &Storage) -> Result<Option<SavedGame>, String> {
+        match storage.load(SAVE_KEY) {
+            Some(text) => SavedGame::decode(&text).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    pub fn save(&self, storage: &mut Storage) -> Result<(), String> {
+        storage.save(SAVE_KEY, &self.encode())
+    }
+
+    pub fn clear(storage: &mut Storage) {
+        storage.remove(SAVE_KEY);
+    }
+
+    pub fn encode(&self) -> String {
+        format!("SAVE {}\n{} {} {} {}\n{}\n", SAVE_VERSION,
+                self.mode.name(), self.level_set, self.field.0, self.field.1,
+                self.snapshot)
+    }
+
+    pub fn decode(text: &str) -> Result<SavedGame, String> {
+        let mut lines = text.lines();
+        let header: Vec<&str> = lines.next().unwrap_or("").split_whitespace().collect();
+        if header.len() != 2 || header[0] != "SAVE" {
+            return Err("missing header".to_string());
+        }
+        let version: u32 = header[1].parse()
+            .map_err(|_| format!("invalid version: {}", header[1]))?;
+
+        match version {
+            1 => SavedGame::decode_v1(lines),
+            _ => Err(format!("unknown version: {}", version)),
+        }
+    }
+
+    fn decode_v1<'a, I>(mut lines: I) -> Result<SavedGame, String>
+        where I: Iterator<Item = &'a str> {
+        let setup: Vec<&str> = lines.next().unwrap_or("").split_whitespace().collect();
+        if setup.len() != 4 {
+            return Err("invalid setup".to_string());
+        }
+        let mode = GameMode::from_name(setup[0])
+            .ok_or_else(|| format!("invalid mode: {}", setup[0]))?;
+        let width = setup[2].parse().map_err(|_| format!("invalid width: {}", setup[2]))?;
+        let height = setup[3].parse().map_err(|_| format!("invalid height: {}", setup[3]))?;
+
+        let mut fields = lines.next().unwrap_or("").split_whitespace();
+        let snapshot = Snapshot::parse(&mut fields)?;
+        if fields.next().is_some() {
+            return Err("trailing data on snapshot".to_string());
+        }
+
+        Ok(SavedGame {
+            mode:      mode,
+            level_set: setup[1].to_string(),
+            field:     (width, height),
+            snapshot:  snapshot,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sim::{PlayerInput, FIELD_SIZE};
+    use storage::MemoryStorage;
+
+    // Some way into a game, with blocks gone and the ball moving
+    fn game_in_progress() -> Game {
+        let mut game = Game::new(GameMode::Coop, FIELD_SIZE, 42);
+        let mut serve = PlayerInput::new();
+        serve.launch = true;
+        game.step(&[serve]);
+        for _ in 0..300 {
+            game.step(&[]);
+        }
+        game
+    }
+
+    #[test]
+    fn games_round_trip() {
+        let game = game_in_progress();
+        let saved = SavedGame::from_game(&game);
+
+        let mut storage = MemoryStorage::new();
+        saved.save(&mut storage).unwrap();
+        let loaded = SavedGame::load(&storage).unwrap().unwrap();
+        assert_eq!(loaded, saved);
+        assert_eq!(loaded.encode(), saved.encode());
+
+        // And plays on exactly the same
+        let mut restored = loaded.restore().unwrap();
+        let mut original = game;
+        for _ in 0..300 {
+            original.step(&[]);
+            restored.step(&[]);
+        }
+        assert_eq!(restored.checksum(), original.checksum());
+    }
+
+    #[test]
+    fn saves_can_be_cleared() {
+        let mut storage = MemoryStorage::new();
+        assert_eq!(SavedGame::load(&storage).unwrap(), None);
+
+        SavedGame::from_game(&game_in_progress()).save(&mut storage).unwrap();
+        SavedGame::clear(&mut storage);
+        assert_eq!(SavedGame::load(&storage).unwrap(), None);
+    }
+
+    #[test]
+    fn malformed_saves_are_rejected() {
+        let good = SavedGame::from_game(&game_in_progress()).encode();
+        let lines: Vec<&str> = good.lines().collect();
+        let with = |header: &str, setup: &str, snapshot: &str| format!("{}\n{}\n{}\n", header, setup, snapshot);
+
+        let bad = vec![
+            String::new(),
+            with("SAVE", lines[1], lines[2]),
+            with("GAME 1", lines[1], lines[2]),
+            with("SAVE one", lines[1], lines[2]),
+            with("SAVE 2", lines[1], lines[2]),
+            with(lines[0], "CO-OP CLASSIC 1280", lines[2]),
+            with(lines[0], "CO-OP CLASSIC 1280 720 0", lines[2]),
+            with(lines[0], "SOLO CLASSIC 1280 720", lines[2]),
+            with(lines[0], "CO-OP CLASSIC wide 720", lines[2]),
+            with(lines[0], "CO-OP CLASSIC 1280 tall", lines[2]),
+            with(lines[0], lines[1], ""),
+            with(lines[0], lines[1], &lines[2][..lines[2].len() / 2]),
+            with(lines[0], lines[1], &format!("{} more", lines[2])),
+        ];
+        for text in &bad {
+            assert!(SavedGame::decode(text).is_err(), "accepted {:?}", text);
+        }
+        assert!(SavedGame::decode(&good).is_ok());
+    }
+
+    #[test]
+    fn unknown_level_sets_are_not_restored() {
+        let mut saved = SavedGame::from_game(&game_in_progress());
+        saved.level_set = "NOWHERE".to_string();
+        let saved = SavedGame::decode(&saved.encode()).unwrap();
+        assert!(saved.restore().is_err());
+    }
+
+    #[test]
+    fn random_level_sets_round_trip() {
+        let game = Game::with_levels(GameMode::Single, FIELD_SIZE, 7, LevelSet::random("two words"));
+        let saved = SavedGame::from_game(&game);
+
+        let mut storage = MemoryStorage::new();
+        saved.save(&mut storage).unwrap();
+        let loaded = SavedGame::load(&storage).unwrap().unwrap();
+        assert_eq!(loaded, saved);
+        assert_eq!(loaded.restore().unwrap().checksum(), game.checksum());
+    }
+}
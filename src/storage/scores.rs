@@ -0,0 +1,254 @@
+use std::collections::BTreeMap;
+
+use sim::GameMode;
+use storage::Storage;
+
+pub const SCORES_KEY: &'static str = "brickbreak.scores";
+
+// Bump this whenever the format changes, and teach decode() how to
+// read the old one
+pub const SCORES_VERSION: u32 = 1;
+
+// Entries kept on each table
+pub const TABLE_SIZE: usize = 10;
+
+// Arcade style
+pub const NAME_LENGTH: usize = 3;
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct ScoreEntry {
+    pub name:  String,
+    pub score: u32,
+}
+
+// One table for each level set and game mode, best scores first.
+//
+// Stored as text, one entry per line, after a header with the format
+// version:
+//
+//     SCORES 1
+//     CLASSIC SINGLE ABC 1230
+//     CLASSIC CO-OP XYZ 880
+pub struct HighScores {
+    tables:    BTreeMap<(String, String), Vec<ScoreEntry>>,
+    // Set when the stored scores couldn't be read, so we don't wipe them
+    // out by saving on top. They might come from a newer version.
+    read_only: bool,
+}
+
+impl HighScores {
+    pub fn new() -> HighScores {
+        HighScores {
+            tables:    BTreeMap::new(),
+            read_only: false,
+        }
+    }
+
+    pub fn load(storage: &Storage) -> Result<HighScores, String> {
+        match storage.load(SCORES_KEY) {
+            Some(text) => HighScores::decode(&text).map_err(|reason| {
+                format!("could not read high scores: {}", reason)
+            }),
+            None => Ok(HighScores::new()),
+        }
+    }
+
+    // Empty, and never saved. For when the stored scores can't be read.
+    pub fn locked() -> HighScores {
+        let mut scores = HighScores::new();
+        scores.read_only = true;
+        scores
+    }
+
+    // Like load(), but falls back to an empty table which is never saved
+    pub fn load_or_lock(storage: &Storage) -> HighScores {
+        HighScores::load(storage).unwrap_or_else(|_| HighScores::locked())
+    }
+
+    pub fn save(&self, storage: &mut Storage) -> Result<(), String> {
+        if self.read_only {
+            return Err("high scores are read-only".to_string());
+        }
+        storage.save(SCORES_KEY, &self.encode())
+    }
+
+    fn key(level_set: &str, mode: GameMode) -> (String, String) {
+        (level_set.to_string(), mode.name().to_string())
+    }
+
+    pub fn table(&self, level_set: &str, mode: GameMode) -> &[ScoreEntry] {
+        match self.tables.get(&HighScores::key(level_set, mode)) {
+            Some(table) => table,
+            None => &[],
+        }
+    }
+
+    // Whether a score is good enough to make it into the table
+    pub fn qualifies(&self, level_set: &str, mode: GameMode, score: u32) -> bool {
+        let table = self.table(level_set, mode);
+        score > 0 && (table.len() < TABLE_SIZE
+                      || table.last().map_or(true, |last| score > last.score))
+    }
+
+    // Adds a score, returning its rank if it made it into the table.
+    // Ties go below older entries; they got there first.
+    pub fn insert(&mut self, level_set: &str, mode: GameMode, name: &str, score: u32) -> Option<usize> {
+        if !self.qualifies(level_set, mode, score) {
+            return None;
+        }
+
+        let table = self.tables.entry(HighScores::key(level_set, mode)).or_insert_with(Vec::new);
+        let rank = table.iter().position(|entry| score > entry.score).unwrap_or(table.len());
+        table.insert(rank, ScoreEntry {
+            name:  clean_name(name),
+            score: score,
+        });
+        table.truncate(TABLE_SIZE);
+        Some(rank)
+    }
+
+    pub fn encode(&self) -> String {
+        let mut text = format!("SCORES {}\n", SCORES_VERSION);
+        for (&(ref level_set, ref mode), table) in &self.tables {
+            for entry in table {
+                text.push_str(&format!("{} {} {} {}\n", level_set, mode, entry.name, entry.score));
+            }
+        }
+        text
+    }
+
+    pub fn decode(text: &str) -> Result<HighScores, String> {
+        let mut lines = text.lines();
+        let header: Vec<&str> = lines.next().unwrap_or("").split_whitespace().collect();
+        if header.len() != 2 || header[0] != "SCORES" {
+            return Err("missing header".to_string());
+        }
+        let version: u32 = header[1].parse()
+            .map_err(|_| format!("invalid version: {}", header[1]))?;
+
+        match version {
+            1 => HighScores::decode_v1(lines),
+            _ => Err(format!("unknown version: {}", version)),
+        }
+    }
+
+    fn decode_v1<'a, I>(lines: I) -> Result<HighScores, String>
+        where I: Iterator<Item = &'a str> {
+        let mut scores = HighScores::new();
+        for line in lines {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            if fields.is_empty() {
+                continue;
+            }
+            if fields.len() != 4 {
+                return Err(format!("invalid entry: {}", line));
+            }
+
+            let mode = GameMode::from_name(fields[1])
+                .ok_or_else(|| format!("invalid mode: {}", fields[1]))?;
+            let score = fields[3].parse()
+                .map_err(|_| format!("invalid score: {}", fields[3]))?;
+            scores.insert(fields[0], mode, fields[2], score);
+        }
+        Ok(scores)
+    }
+}
+
+// Names are a few capital letters, so they never break the format
+pub fn clean_name(name: &str) -> String {
+    let mut name: String = name.chars()
+        .filter(|c| c.is_ascii() && c.is_alphanumeric())
+        .map(|c| c.to_ascii_uppercase())
+        .take(NAME_LENGTH)
+        .collect();
+    while name.len() < NAME_LENGTH {
+        name.push('A');
+    }
+    name
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sim::LevelSet;
+    use storage::MemoryStorage;
+
+    fn sample() -> HighScores {
+        let mut scores = HighScores::new();
+        scores.insert("CLASSIC", GameMode::Single, "ABC", 1230);
+        scores.insert("CLASSIC", GameMode::Single, "DEF", 450);
+        scores.insert("CLASSIC", GameMode::Coop, "XYZ", 880);
+        scores.insert("OTHER", GameMode::Single, "GHI", 10);
+        scores
+    }
+
+    #[test]
+    fn scores_round_trip() {
+        let scores = sample();
+        let mut storage = MemoryStorage::new();
+        scores.save(&mut storage).unwrap();
+
+        let loaded = HighScores::load(&storage).unwrap();
+        assert!(loaded.tables == scores.tables);
+        assert_eq!(loaded.encode(), scores.encode());
+        assert_eq!(loaded.table("CLASSIC", GameMode::Single)[0],
+                   ScoreEntry { name: "ABC".to_string(), score: 1230 });
+    }
+
+    #[test]
+    fn nothing_stored_is_an_empty_table() {
+        let scores = HighScores::load(&MemoryStorage::new()).unwrap();
+        assert!(scores.table("CLASSIC", GameMode::Single).is_empty());
+    }
+
+    #[test]
+    fn malformed_scores_are_rejected() {
+        let bad = [
+            "",
+            "SCORES",
+            "HIGHSCORES 1",
+            "SCORES one",
+            "SCORES 2\nCLASSIC SINGLE ABC 10",
+            "SCORES 1\nCLASSIC SINGLE ABC",
+            "SCORES 1\nCLASSIC SINGLE ABC 10 20",
+            "SCORES 1\nCLASSIC SOLO ABC 10",
+            "SCORES 1\nCLASSIC SINGLE ABC -10",
+            "SCORES 1\nCLASSIC SINGLE ABC lots",
+        ];
+        for text in bad.iter() {
+            assert!(HighScores::decode(text).is_err(), "accepted {:?}", text);
+        }
+    }
+
+    #[test]
+    fn unreadable_scores_are_left_alone() {
+        let mut storage = MemoryStorage::new();
+        storage.save(SCORES_KEY, "SCORES 2\nfrom the future").unwrap();
+
+        let mut scores = HighScores::load_or_lock(&storage);
+        assert!(scores.table("CLASSIC", GameMode::Single).is_empty());
+        scores.insert("CLASSIC", GameMode::Single, "ABC", 100);
+        assert!(scores.save(&mut storage).is_err());
+        assert_eq!(storage.load(SCORES_KEY).unwrap(), "SCORES 2\nfrom the future");
+    }
+
+    #[test]
+    fn names_are_cleaned_up() {
+        assert_eq!(clean_name("ab"), "ABA");
+        assert_eq!(clean_name("x y z w"), "XYZ");
+        assert_eq!(clean_name(""), "AAA");
+    }
+
+    #[test]
+    fn random_level_sets_round_trip() {
+        let level_set = LevelSet::random("two words\tand 100%").name;
+        let mut scores = HighScores::new();
+        scores.insert(&level_set, GameMode::Single, "ABC", 1230);
+
+        let mut storage = MemoryStorage::new();
+        scores.save(&mut storage).unwrap();
+        let loaded = HighScores::load(&storage).unwrap();
+        assert_eq!(loaded.table(&level_set, GameMode::Single), scores.table(&level_set, GameMode::Single));
+        assert!(loaded.save(&mut storage).is_ok());
+    }
+}
@@ -0,0 +1,33 @@
+// Where each glyph sits on a font's atlas. Glyphs are laid out in rows
+// of ATLAS_COLUMNS cells, in the order of ATLAS_CHARS.
+
+use text::FontMetrics;
+
+// Printable ASCII, then the Latin-1 letters and signs (© among them)
+// which translations need
+pub const ATLAS_CHARS: &'static str = concat!(
+    " !\"#$%&'()*+,-./0123456789:;<=>?@ABCDEFGHIJKLMNOPQRSTUVWXYZ[\\]^_`abcdefghijklmnopqrstuvwxyz{|}~",
+    "¡¢£¤¥¦§¨©ª«¬®¯°±²³´µ¶·¸¹º»¼½¾¿ÀÁÂÃÄÅÆÇÈÉÊËÌÍÎÏÐÑÒÓÔÕÖ×ØÙÚÛÜÝÞßàáâãäåæçèéêëìíîïðñòóôõö÷øùúûüýþÿ");
+
+pub const ATLAS_COLUMNS: u32 = 16;
+
+// Characters missing from the atlas get drawn as this one
+const MISSING: char = '?';
+
+// In pixels
+pub fn atlas_size(metrics: &FontMetrics) -> (u32, u32) {
+    let count = ATLAS_CHARS.chars().count() as u32;
+    let rows = (count + ATLAS_COLUMNS - 1) / ATLAS_COLUMNS;
+    (ATLAS_COLUMNS * metrics.cell.0, rows * metrics.cell.1)
+}
+
+// (x, y, width, height) of a character's cell on the atlas
+pub fn glyph_region(metrics: &FontMetrics, ch: char) -> (u32, u32, u32, u32) {
+    let index = ATLAS_CHARS.chars().position(|c| c == ch)
+        .or_else(|| ATLAS_CHARS.chars().position(|c| c == MISSING))
+        .unwrap() as u32;
+    ((index % ATLAS_COLUMNS) * metrics.cell.0,
+     (index / ATLAS_COLUMNS) * metrics.cell.1,
+     metrics.cell.0,
+     metrics.cell.1)
+}
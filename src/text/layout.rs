@@ -0,0 +1,329 @@
+use text::{FontMetrics, glyph_region};
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum Align {
+    Left,
+    Center,
+    Right,
+}
+
+impl Align {
+    // As the canvas calls it
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Align::Left   => "left",
+            Align::Center => "center",
+            Align::Right  => "right",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Align> {
+        match name {
+            "left"   => Some(Align::Left),
+            "center" => Some(Align::Center),
+            "right"  => Some(Align::Right),
+            _ => None,
+        }
+    }
+}
+
+// A font at a given size
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub struct TextStyle {
+    pub metrics: FontMetrics,
+    // Height of a glyph, in pixels of the drawing area. Whole multiples
+    // of the font's own cell height keep a bitmap font crisp.
+    pub size:    f32,
+    // Distance between lines, as a multiple of the size
+    pub spacing: f32,
+}
+
+impl TextStyle {
+    pub fn new(metrics: FontMetrics, size: f32) -> TextStyle {
+        TextStyle {
+            metrics: metrics,
+            size:    size,
+            spacing: 1.5,
+        }
+    }
+
+    pub fn scale(&self) -> f32 {
+        self.size / self.metrics.cell.1 as f32
+    }
+
+    // Width of each glyph, the font being monospaced
+    pub fn advance(&self) -> f32 {
+        self.metrics.cell.0 as f32 * self.scale()
+    }
+
+    pub fn line_height(&self) -> f32 {
+        self.size * self.spacing
+    }
+
+    // Lines are a bit taller than glyphs, which sit in the middle
+    pub fn padding(&self) -> f32 {
+        (self.line_height() - self.size) / 2.0
+    }
+
+    // From the top of a line down to where the glyphs sit
+    pub fn baseline(&self) -> f32 {
+        self.padding() + (self.metrics.baseline as f32 * self.scale())
+    }
+
+    // Of a single line
+    pub fn width(&self, text: &str) -> f32 {
+        text.chars().count() as f32 * self.advance()
+    }
+
+    // Same font, small enough for a line of text to fit in max_width.
+    // Sizes go down through whole multiples of the cell height first,
+    // which keep the font crisp, and only go in between when even the
+    // smallest of those is too big.
+    pub fn fit(&self, text: &str, max_width: f32) -> TextStyle {
+        if self.width(text) <= max_width {
+            return *self;
+        }
+
+        let cell = self.metrics.cell.1 as f32;
+        let mut style = *self;
+        let mut multiple = (self.size / cell).ceil() - 1.0;
+        while multiple >= 1.0 {
+            style.size = multiple * cell;
+            if style.width(text) <= max_width {
+                return style;
+            }
+            multiple -= 1.0;
+        }
+
+        let chars = text.chars().count().max(1) as f32;
+        style.size = max_width / (chars * self.metrics.cell.0 as f32) * cell;
+        style
+    }
+
+    // Splits text into lines no wider than max_width. Lines break
+    // between words where possible, and wherever the text has a line
+    // break of its own.
+    pub fn wrap(&self, text: &str, max_width: f32) -> Vec<String> {
+        // At least one character goes on each line, however narrow
+        let max_chars = ((max_width / self.advance()).floor() as usize).max(1);
+        let mut lines = vec![];
+
+        for paragraph in text.split('\n') {
+            let mut line = String::new();
+            let mut line_chars = 0;
+
+            for word in paragraph.split_whitespace() {
+                let mut word: Vec<char> = word.chars().collect();
+
+                // Words too long for a line of their own get split
+                while word.len() > max_chars {
+                    if line_chars > 0 {
+                        lines.push(line);
+                        line = String::new();
+                        line_chars = 0;
+                    }
+                    let rest = word.split_off(max_chars);
+                    lines.push(word.into_iter().collect());
+                    word = rest;
+                }
+                if word.is_empty() {
+                    continue;
+                }
+
+                let needed = if line_chars > 0 { word.len() + 1 } else { word.len() };
+                if line_chars + needed > max_chars {
+                    lines.push(line);
+                    line = String::new();
+                    line_chars = 0;
+                }
+                if line_chars > 0 {
+                    line.push(' ');
+                    line_chars += 1;
+                }
+                line_chars += word.len();
+                line.extend(word);
+            }
+            lines.push(line);
+        }
+
+        lines
+    }
+}
+
+
+
+#[derive(PartialEq, Clone, Debug)]
+pub struct Line {
+    pub text:   String,
+    // From the left of the block
+    pub offset: f32,
+    pub width:  f32,
+}
+
+// Glyph to copy from the atlas onto the screen
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub struct Glyph {
+    pub ch:     char,
+    // Top left corner, on the screen
+    pub pos:    (f32, f32),
+    pub size:   (f32, f32),
+    // (x, y, width, height) on the atlas
+    pub region: (u32, u32, u32, u32),
+}
+
+// Some lines of text, laid out and aligned within a box
+#[derive(PartialEq, Clone, Debug)]
+pub struct TextBlock {
+    pub style: TextStyle,
+    pub align: Align,
+    pub lines: Vec<Line>,
+    pub size:  (f32, f32),
+}
+
+impl TextBlock {
+    // Without a width, lines only break where the text does, and the
+    // block is as wide as its longest line
+    pub fn new(style: TextStyle, align: Align, text: &str, max_width: Option<f32>) -> TextBlock {
+        let lines = match max_width {
+            Some(width) => style.wrap(text, width),
+            None => text.split('\n').map(|line| line.to_string()).collect(),
+        };
+        TextBlock::from_lines(style, align, lines, max_width)
+    }
+
+    fn from_lines(style: TextStyle, align: Align, lines: Vec<String>, max_width: Option<f32>) -> TextBlock {
+        let widest = lines.iter().map(|line| style.width(line)).fold(0.0, f32::max);
+        let width = max_width.unwrap_or(widest);
+
+        let lines: Vec<Line> = lines.into_iter().map(|text| {
+            let line_width = style.width(&text);
+            Line {
+                offset: match align {
+                    Align::Left   => 0.0,
+                    Align::Center => (width - line_width) / 2.0,
+                    Align::Right  => width - line_width,
+                },
+                width:  line_width,
+                text:   text,
+            }
+        }).collect();
+
+        TextBlock {
+            style: style,
+            align: align,
+            size:  (width, lines.len() as f32 * style.line_height()),
+            lines: lines,
+        }
+    }
+
+    // Splits the block into pages of at most max_lines lines each
+    pub fn paginate(&self, max_lines: usize) -> Vec<TextBlock> {
+        let max_lines = max_lines.max(1);
+        self.lines.chunks(max_lines).map(|lines| TextBlock {
+            style: self.style,
+            align: self.align,
+            lines: lines.to_vec(),
+            size:  (self.size.0, lines.len() as f32 * self.style.line_height()),
+        }).collect()
+    }
+
+    // Where every glyph goes, for a block whose top is at the given
+    // height. Horizontally, the position is wherever the block is
+    // aligned to: its left, center or right.
+    pub fn glyphs(&self, pos: (f32, f32)) -> Vec<Glyph> {
+        let left = match self.align {
+            Align::Left   => pos.0,
+            Align::Center => pos.0 - (self.size.0 / 2.0),
+            Align::Right  => pos.0 - self.size.0,
+        };
+        let style = &self.style;
+        let size = (style.advance(), style.size);
+
+        let mut glyphs = vec![];
+        for (row, line) in self.lines.iter().enumerate() {
+            let y = pos.1 + (row as f32 * style.line_height()) + style.padding();
+            for (column, ch) in line.text.chars().enumerate() {
+                if ch == ' ' {
+                    continue;
+                }
+                glyphs.push(Glyph {
+                    ch:     ch,
+                    pos:    (left + line.offset + (column as f32 * size.0), y),
+                    size:   size,
+                    region: glyph_region(&style.metrics, ch),
+                });
+            }
+        }
+        glyphs
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use text::GOHUFONT;
+
+    // Glyphs 6 pixels wide
+    fn style() -> TextStyle {
+        TextStyle::new(GOHUFONT, 11.0)
+    }
+
+    fn chars(count: usize) -> f32 {
+        count as f32 * 6.0
+    }
+
+    #[test]
+    fn lines_break_between_words() {
+        assert_eq!(style().wrap("one two three", chars(7)), vec!["one two", "three"]);
+        assert_eq!(style().wrap("  one   two  ", chars(7)), vec!["one two"]);
+    }
+
+    #[test]
+    fn long_words_get_split() {
+        assert_eq!(style().wrap("abcdefghijkl", chars(5)), vec!["abcde", "fghij", "kl"]);
+        assert_eq!(style().wrap("hi abcdefghij", chars(5)), vec!["hi", "abcde", "fghij"]);
+        // Never less than a character a line
+        assert_eq!(style().wrap("abc", 1.0), vec!["a", "b", "c"]);
+    }
+
+    #[test]
+    fn empty_text_is_one_empty_line() {
+        assert_eq!(style().wrap("", chars(10)), vec![""]);
+        assert_eq!(TextBlock::new(style(), Align::Left, "", None).lines.len(), 1);
+    }
+
+    #[test]
+    fn line_breaks_are_kept() {
+        assert_eq!(style().wrap("a\n\nb c", chars(10)), vec!["a", "", "b c"]);
+        let block = TextBlock::new(style(), Align::Left, "ab\nabcd", None);
+        assert_eq!(block.size, (chars(4), 2.0 * style().line_height()));
+    }
+
+    #[test]
+    fn fitting_shrinks_by_whole_multiples_first() {
+        let style = TextStyle::new(GOHUFONT, 33.0);
+        assert_eq!(style.fit("abcdef", 200.0), style);
+        assert_eq!(style.fit("abcdef", 80.0).size, 22.0);
+        assert_eq!(style.fit("abcdef", 36.0).size, 11.0);
+
+        // Smaller than the font was drawn for, but still fits
+        let tiny = style.fit("abcdef", 30.0);
+        assert!(tiny.size < 11.0);
+        assert!((tiny.width("abcdef") - 30.0).abs() < 0.001);
+    }
+
+    #[test]
+    fn pages_hold_at_most_so_many_lines() {
+        let block = TextBlock::new(style(), Align::Center, "a\nb\nc\nd\ne", Some(chars(10)));
+        let pages = block.paginate(2);
+        let texts: Vec<Vec<&str>> = pages.iter()
+            .map(|page| page.lines.iter().map(|line| line.text.as_str()).collect())
+            .collect();
+        assert_eq!(texts, vec![vec!["a", "b"], vec!["c", "d"], vec!["e"]]);
+        assert_eq!(pages[2].size, (chars(10), style().line_height()));
+        assert_eq!(pages[0].lines[0].offset, block.lines[0].offset);
+
+        assert_eq!(block.paginate(0).len(), 5);
+        assert_eq!(block.paginate(5).len(), 1);
+    }
+}
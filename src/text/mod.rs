@@ -0,0 +1,28 @@
+// Text.
+// Measures, wraps, aligns and paginates text, and works out where each
+// glyph goes on the screen and on the font's bitmap atlas. Drawing is
+// left to whoever has something to draw on: all a backend needs is a
+// way to copy rectangles out of the atlas.
+
+pub mod layout;
+pub mod atlas;
+
+pub use self::layout::{Align, TextStyle, TextBlock, Line, Glyph};
+pub use self::atlas::{ATLAS_CHARS, ATLAS_COLUMNS, atlas_size, glyph_region};
+
+// Glyphs of a monospaced bitmap font
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub struct FontMetrics {
+    pub family:   &'static str,
+    // Size of every glyph's cell, in pixels
+    pub cell:     (u32, u32),
+    // Distance from the top of a cell down to the baseline
+    pub baseline: u32,
+}
+
+// The bundled GohuFont, at the size its bitmaps were drawn for
+pub const GOHUFONT: FontMetrics = FontMetrics {
+    family:   "GohuFont",
+    cell:     (6, 11),
+    baseline: 9,
+};
@@ -0,0 +1,375 @@
+// Colors for everything on screen.
+// Themes are written on text files, one color (or list of colors) per
+// line, and the game ships a few of them:
+//
+//     # Comments start with a hash
+//     name       CLASSIC
+//     background #000
+//     text       #FFF
+//     blocks     level
+//     effects    #4F4 #4FF #F4F
+//     bevel      #666 #AAA
+//
+// Anything left out is taken from the classic theme. Blocks either keep
+// the colors their level gives them (`level`), or get one from the
+// theme's palette, always the same one for the same level color.
+
+pub mod vision;
+
+pub use self::vision::{ColorVision, VisionPalette, Pattern, ALL_COLOR_VISIONS};
+
+use sim::{BlockKind, Effect};
+
+pub const DEFAULT_THEME: &'static str = "CLASSIC";
+
+// Colors the built-in levels paint normal blocks with, each taking the
+// next entry on a theme's palette. They're ordered so that on palettes
+// of five, like every built-in one, no built-in level gets two of its
+// colors the same, and neither do neighboring rows of generated levels.
+// Any other color gets an entry by its hash.
+const LEVEL_COLORS: [&'static str; 8] = [
+    "#F44", "#FA4", "#4AF", "#FFF", "#FF4", "#4FF", "#F4F", "#4F4",
+];
+
+const FILES: [&'static str; 4] = [
+    include_str!("../../themes/classic.txt"),
+    include_str!("../../themes/processing.txt"),
+    include_str!("../../themes/neon.txt"),
+    include_str!("../../themes/paper.txt"),
+];
+
+#[derive(PartialEq, Clone, Debug)]
+pub struct Theme {
+    pub name:       String,
+    // Behind the field
+    pub background: String,
+    pub text:       String,
+    // Whatever is selected or needs to stand out
+    pub accent:     String,
+    pub warning:    String,
+    // Empty parts of bars and meters
+    pub dim:        String,
+    // Palette for normal blocks, or None to keep the level's colors
+    pub blocks:     Option<Vec<String>>,
+    pub solid:      Option<String>,
+    // Power-up blocks and capsules (wide, slow and extra life), or
+    // None to keep the game's own colors
+    pub effects:    Option<Vec<String>>,
+    // Numbers and letters drawn over blocks and capsules
+    pub block_text: String,
+    // Shading on the (lower, side) faces of every block
+    pub bevel:      (String, String),
+    pub afterimage: String,
+    // Tints for each player's paddle
+    pub paddles:    Vec<String>,
+    pub sparks:     String,
+}
+
+impl Theme {
+    // The one every other theme starts from
+    pub fn classic() -> Theme {
+        Theme {
+            name:       DEFAULT_THEME.to_string(),
+            background: "#000".to_string(),
+            text:       "#FFF".to_string(),
+            accent:     "#FF0".to_string(),
+            warning:    "#F44".to_string(),
+            dim:        "#444".to_string(),
+            blocks:     None,
+            solid:      None,
+            effects:    None,
+            block_text: "#000".to_string(),
+            bevel:      ("#666".to_string(), "#AAA".to_string()),
+            afterimage: "#FFF".to_string(),
+            paddles:    vec!["#3AF".to_string(), "#F83".to_string()],
+            sparks:     "#FF8".to_string(),
+        }
+    }
+
+    pub fn parse(text: &str) -> Result<Theme, String> {
+        let mut theme = Theme::classic();
+        let mut named = false;
+
+        for (number, line) in text.lines().enumerate() {
+            let number = number + 1;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let values = &fields[1..];
+            let colors = || -> Result<Vec<String>, String> {
+                if values.is_empty() {
+                    return Err(format!("line {}: {} needs a color", number, fields[0]));
+                }
+                values.iter().map(|value| {
+                    if is_color(value) {
+                        Ok(value.to_string())
+                    } else {
+                        Err(format!("line {}: invalid color {}", number, value))
+                    }
+                }).collect()
+            };
+            let color = || -> Result<String, String> {
+                let mut colors = colors()?;
+                if colors.len() != 1 {
+                    return Err(format!("line {}: {} takes a single color", number, fields[0]));
+                }
+                Ok(colors.remove(0))
+            };
+
+            match fields[0] {
+                "name" => {
+                    if values.is_empty() {
+                        return Err(format!("line {}: name is empty", number));
+                    }
+                    theme.name = values.join(" ");
+                    named = true;
+                },
+                "background" => theme.background = color()?,
+                "text"       => theme.text = color()?,
+                "accent"     => theme.accent = color()?,
+                "warning"    => theme.warning = color()?,
+                "dim"        => theme.dim = color()?,
+                "blocks" => {
+                    theme.blocks = if values == ["level"] { None } else { Some(colors()?) };
+                },
+                "solid" => {
+                    theme.solid = if values == ["level"] { None } else { Some(color()?) };
+                },
+                "effects" => {
+                    theme.effects = if values == ["level"] {
+                        None
+                    } else {
+                        let colors = colors()?;
+                        if colors.len() != 3 {
+                            return Err(format!("line {}: effects takes three colors", number));
+                        }
+                        Some(colors)
+                    };
+                },
+                "block_text" => theme.block_text = color()?,
+                "bevel" => {
+                    let mut shades = colors()?;
+                    if shades.len() != 2 {
+                        return Err(format!("line {}: bevel takes two colors", number));
+                    }
+                    let side = shades.remove(1);
+                    theme.bevel = (shades.remove(0), side);
+                },
+                "afterimage" => theme.afterimage = color()?,
+                "paddles"    => theme.paddles = colors()?,
+                "sparks"     => theme.sparks = color()?,
+                other => return Err(format!("line {}: unknown entry {}", number, other)),
+            }
+        }
+
+        if !named {
+            return Err("theme has no name".to_string());
+        }
+        Ok(theme)
+    }
+
+    // The themes shipped with the game, in the order they're picked from
+    pub fn builtin() -> Vec<Theme> {
+        FILES.iter()
+            .map(|text| Theme::parse(text).expect("built-in theme is broken"))
+            .collect()
+    }
+
+    pub fn by_name(name: &str) -> Option<Theme> {
+        Theme::builtin().into_iter().find(|theme| theme.name == name)
+    }
+
+    // Swaps the colors that tell things apart for ones the player can
+    // tell apart
+    pub fn with_vision(mut self, vision: ColorVision) -> Theme {
+        if let Some(palette) = vision.palette() {
+            self.blocks = Some(palette.blocks.iter().map(|color| color.to_string()).collect());
+            self.solid = Some(palette.solid.to_string());
+            self.effects = Some(palette.effects.iter().map(|color| color.to_string()).collect());
+        }
+        self
+    }
+
+    // Bright, saturated colors on black, for players who need more
+    // than the theme gives them. Any color vision palette goes on
+    // after this, and wins.
+    pub fn with_high_contrast(mut self) -> Theme {
+        let colors = |colors: &[&str]| colors.iter().map(|color| color.to_string()).collect();
+        self.background = "#000".to_string();
+        self.text       = "#FFF".to_string();
+        self.accent     = "#FF0".to_string();
+        self.warning    = "#F44".to_string();
+        self.dim        = "#888".to_string();
+        self.blocks     = Some(colors(&["#FFF", "#FF0", "#0FF", "#F0F", "#0F0"]));
+        self.solid      = Some("#888".to_string());
+        self.effects    = Some(colors(&["#0F0", "#0FF", "#F0F"]));
+        self.block_text = "#000".to_string();
+        // Blocks are drawn flat, outlined in the first shade
+        self.bevel      = ("#000".to_string(), "#000".to_string());
+        self.afterimage = "#FFF".to_string();
+        self.paddles    = colors(&["#0FF", "#F0F"]);
+        self.sparks     = "#FFF".to_string();
+        self
+    }
+
+    // What a block the level paints in level_color gets painted in.
+    // Power-up blocks keep their color, which tells what they drop,
+    // unless the theme has colors for effects.
+    pub fn block_color<'a>(&'a self, kind: BlockKind, level_color: &'a str) -> &'a str {
+        match kind {
+            BlockKind::Normal => match self.blocks {
+                Some(ref palette) => &palette[palette_index(level_color, palette.len())],
+                None => level_color,
+            },
+            BlockKind::Solid => match self.solid {
+                Some(ref color) => color,
+                None => level_color,
+            },
+            BlockKind::PowerUp(effect) => match self.effects {
+                Some(_) => self.effect_color(effect),
+                None => level_color,
+            },
+        }
+    }
+
+    pub fn effect_color(&self, effect: Effect) -> &str {
+        let index = match effect {
+            Effect::Wide      => 0,
+            Effect::Slow      => 1,
+            Effect::ExtraLife => 2,
+        };
+        match self.effects {
+            Some(ref colors) => &colors[index],
+            None => effect.color(),
+        }
+    }
+
+    pub fn paddle(&self, player: usize) -> &str {
+        &self.paddles[player % self.paddles.len()]
+    }
+}
+
+// Names of the built-in themes
+pub fn theme_names() -> Vec<String> {
+    Theme::builtin().into_iter().map(|theme| theme.name).collect()
+}
+
+// #RGB or #RRGGBB
+fn is_color(text: &str) -> bool {
+    (text.len() == 4 || text.len() == 7) && text.starts_with('#')
+        && text[1..].chars().all(|c| c.is_digit(16))
+}
+
+fn palette_index(level_color: &str, len: usize) -> usize {
+    let level_color = level_color.to_uppercase();
+    match LEVEL_COLORS.iter().position(|&color| color == level_color) {
+        Some(index) => index % len,
+        None => (hash(&level_color) % len as u64) as usize,
+    }
+}
+
+// FNV-1a, so colors map the same way everywhere
+fn hash(text: &str) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in text.to_uppercase().bytes() {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    hash
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sim::LevelSet;
+
+    // Every palette blocks may be painted from
+    fn palettes() -> Vec<Vec<String>> {
+        let mut themes = Theme::builtin();
+        themes.push(Theme::classic().with_high_contrast());
+        for &vision in ALL_COLOR_VISIONS.iter() {
+            themes.push(Theme::classic().with_vision(vision));
+        }
+        themes.into_iter().filter_map(|theme| theme.blocks).collect()
+    }
+
+    fn painted(palette: &[String], level_color: &str) -> String {
+        palette[palette_index(level_color, palette.len())].clone()
+    }
+
+    #[test]
+    fn builtin_themes_parse() {
+        let names = theme_names();
+        assert_eq!(names.len(), FILES.len());
+        assert_eq!(names[0], DEFAULT_THEME);
+        assert_eq!(Theme::by_name(DEFAULT_THEME), Some(Theme::classic()));
+    }
+
+    #[test]
+    fn left_out_colors_come_from_classic() {
+        let theme = Theme::parse("name DARK\nbackground #111").unwrap();
+        assert_eq!(theme.name, "DARK");
+        assert_eq!(theme.background, "#111");
+        assert_eq!(theme.text, Theme::classic().text);
+    }
+
+    #[test]
+    fn bad_themes_are_rejected() {
+        assert!(Theme::parse("background #111").is_err());
+        assert!(Theme::parse("name").is_err());
+        assert!(Theme::parse("name DARK\nshadow #111").is_err());
+        assert!(Theme::parse("name DARK\ntext white").is_err());
+        assert!(Theme::parse("name DARK\ntext #12345").is_err());
+        assert!(Theme::parse("name DARK\ntext #FFF #000").is_err());
+        assert!(Theme::parse("name DARK\nblocks").is_err());
+        assert!(Theme::parse("name DARK\neffects #FFF #000").is_err());
+        assert!(Theme::parse("name DARK\nbevel #FFF").is_err());
+    }
+
+    #[test]
+    fn builtin_level_colors_stay_apart() {
+        let levels = LevelSet::builtin();
+        for palette in palettes() {
+            for level in &levels.levels {
+                let mut colors: Vec<String> = level.blocks.iter()
+                    .filter(|block| block.kind == BlockKind::Normal)
+                    .map(|block| block.color.to_uppercase())
+                    .collect();
+                colors.sort();
+                colors.dedup();
+                if colors.len() > palette.len() {
+                    continue;
+                }
+
+                let mut painted: Vec<String> = colors.iter().map(|color| painted(&palette, color)).collect();
+                painted.sort();
+                painted.dedup();
+                assert_eq!(painted.len(), colors.len(), "{} on {:?}", level.name, palette);
+            }
+        }
+    }
+
+    #[test]
+    fn generated_bands_stay_apart() {
+        // The order rows of generated levels go in, over and over
+        let bands = ["#F44", "#FA4", "#FF4", "#4F4", "#4FF", "#4AF", "#F4F"];
+        for palette in palettes().into_iter().filter(|palette| palette.len() > 1) {
+            for (i, band) in bands.iter().enumerate() {
+                let next = bands[(i + 1) % bands.len()];
+                assert!(painted(&palette, band) != painted(&palette, next),
+                        "{} and {} on {:?}", band, next, palette);
+            }
+        }
+    }
+
+    #[test]
+    fn level_colors_map_the_same_in_any_case() {
+        for palette in palettes() {
+            assert_eq!(painted(&palette, "#f44"), painted(&palette, "#F44"));
+            assert_eq!(painted(&palette, "#abcdef"), painted(&palette, "#ABCDEF"));
+        }
+    }
+}
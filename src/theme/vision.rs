@@ -0,0 +1,99 @@
+// Help for players with color-vision deficiencies. Each kind of color
+// blindness gets a palette whose colors stay apart for it, and blocks
+// may also carry a pattern telling their kind, which needs no color
+// at all.
+
+use sim::{BlockKind, Effect};
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum ColorVision {
+    Normal,
+    // Red-blind
+    Protanopia,
+    // Green-blind
+    Deuteranopia,
+    // Blue-blind
+    Tritanopia,
+}
+
+pub const ALL_COLOR_VISIONS: [ColorVision; 4] = [
+    ColorVision::Normal,
+    ColorVision::Protanopia,
+    ColorVision::Deuteranopia,
+    ColorVision::Tritanopia,
+];
+
+// Colors a theme takes on for some color vision
+pub struct VisionPalette {
+    pub blocks:  &'static [&'static str],
+    pub solid:   &'static str,
+    // Wide, slow and extra life, in that order
+    pub effects: [&'static str; 3],
+}
+
+impl ColorVision {
+    // Also the name it goes by on the settings file
+    pub fn name(&self) -> &'static str {
+        match *self {
+            ColorVision::Normal       => "normal",
+            ColorVision::Protanopia   => "protanopia",
+            ColorVision::Deuteranopia => "deuteranopia",
+            ColorVision::Tritanopia   => "tritanopia",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<ColorVision> {
+        ALL_COLOR_VISIONS.iter().find(|vision| vision.name() == name).cloned()
+    }
+
+    // Normal vision keeps the theme's own colors
+    pub fn palette(&self) -> Option<VisionPalette> {
+        match *self {
+            ColorVision::Normal => None,
+            // Blues against oranges and yellows, with no reds or greens
+            // to mix up
+            ColorVision::Protanopia => Some(VisionPalette {
+                blocks:  &["#0072B2", "#E69F00", "#F0E442", "#56B4E9", "#FFFFFF"],
+                solid:   "#6E6E6E",
+                effects: ["#F0E442", "#56B4E9", "#CC79A7"],
+            }),
+            // Same idea, pushed further apart in brightness, since
+            // greens are the ones that wash out
+            ColorVision::Deuteranopia => Some(VisionPalette {
+                blocks:  &["#648FFF", "#FFB000", "#FE6100", "#DC267F", "#FFFFFF"],
+                solid:   "#6E6E6E",
+                effects: ["#FFB000", "#648FFF", "#DC267F"],
+            }),
+            // Reds against teals, keeping away from blue and yellow
+            ColorVision::Tritanopia => Some(VisionPalette {
+                blocks:  &["#E41A1C", "#00BFC4", "#F781BF", "#FFFFFF", "#A6761D"],
+                solid:   "#6E6E6E",
+                effects: ["#FF7F7F", "#00BFC4", "#F781BF"],
+            }),
+        }
+    }
+}
+
+
+
+// Drawn over a block to tell its kind without relying on color
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum Pattern {
+    Dot,
+    Cross,
+    Arrows,
+    Stripes,
+    Plus,
+}
+
+impl Pattern {
+    pub fn for_block(kind: BlockKind) -> Pattern {
+        match kind {
+            BlockKind::Normal                     => Pattern::Dot,
+            BlockKind::Solid                      => Pattern::Cross,
+            BlockKind::PowerUp(Effect::Wide)      => Pattern::Arrows,
+            BlockKind::PowerUp(Effect::Slow)      => Pattern::Stripes,
+            BlockKind::PowerUp(Effect::ExtraLife) => Pattern::Plus,
+        }
+    }
+}
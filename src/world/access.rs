@@ -0,0 +1,100 @@
+use stdweb::Value;
+
+use locale::Locale;
+
+// What a menu says, as a screen reader should hear it
+#[derive(PartialEq, Clone, Debug)]
+pub struct MenuView {
+    pub title:    String,
+    pub items:    Vec<String>,
+    pub selected: Option<usize>,
+}
+
+// Mirrors what's drawn on the canvas into elements assistive technology
+// can read: a hidden copy of the current menu, and a live region that
+// announcements get read out from. Pages without them get nothing.
+pub struct AccessBridge {
+    live: Value,
+    menu: Value,
+    // Menu on the page, so it only gets touched when it changes
+    shown: Option<MenuView>,
+}
+
+impl AccessBridge {
+    pub fn new() -> AccessBridge {
+        AccessBridge {
+            live:  js!( return document.getElementById("announcer"); ),
+            menu:  js!( return document.getElementById("menu"); ),
+            shown: None,
+        }
+    }
+
+    pub fn announce(&self, text: &str) {
+        js! {
+            var live = @{&self.live};
+            if (!live) {
+                return;
+            }
+            // Readers only notice new nodes, so repeating the same
+            // sentence still gets it read again
+            var line = document.createElement("p");
+            line.textContent = @{text};
+            live.appendChild(line);
+            while (live.childNodes.length > 5) {
+                live.removeChild(live.firstChild);
+            }
+        };
+    }
+
+    pub fn show_menu(&mut self, menu: Option<MenuView>, locale: &Locale) {
+        if self.shown == menu {
+            return;
+        }
+
+        // Moving along a menu reads out the new item
+        if let Some(ref menu) = menu {
+            let same_menu = self.shown.as_ref().map_or(false, |shown| shown.title == menu.title);
+            let selected = menu.selected.and_then(|selected| menu.items.get(selected));
+            if let Some(item) = selected {
+                let position = (menu.selected.unwrap() + 1).to_string();
+                let count = menu.items.len().to_string();
+                let text = if same_menu {
+                    locale.format("announce.menu_item",
+                                  &[("item", item), ("position", &position), ("count", &count)])
+                } else {
+                    locale.format("announce.menu", &[("title", &menu.title), ("item", item),
+                                                     ("position", &position), ("count", &count)])
+                };
+                self.announce(&text);
+            } else if !same_menu {
+                self.announce(&menu.title);
+            }
+        }
+
+        let (title, items, selected) = match menu {
+            Some(ref menu) => (menu.title.clone(), menu.items.clone(),
+                               menu.selected.map_or(-1, |selected| selected as i32)),
+            None => (String::new(), vec![], -1),
+        };
+        js! {
+            var menu = @{&self.menu};
+            if (!menu) {
+                return;
+            }
+            var items = @{items};
+            menu.setAttribute("aria-label", @{title});
+            while (menu.firstChild) {
+                menu.removeChild(menu.firstChild);
+            }
+            for (var i = 0; i < items.length; i++) {
+                var item = document.createElement("li");
+                item.textContent = items[i];
+                if (i === @{selected}) {
+                    item.setAttribute("aria-current", "true");
+                }
+                menu.appendChild(item);
+            }
+        };
+        self.shown = menu;
+    }
+}
@@ -0,0 +1,129 @@
+use std::collections::HashMap;
+use stdweb::Value;
+use stdweb::unstable::TryInto;
+
+use assets::{Manifest, Loading, LoadState, AssetKind};
+use world::state::Sprite;
+
+const MANIFEST: &'static str = include_str!("../../static/assets.txt");
+
+// Everything listed on the manifest, loaded or on its way.
+// Assets report back through Module.exports.assetCallback(index, error)
+// once they're done, with an empty error when all went well.
+pub struct Assets {
+    pub manifest: Manifest,
+    pub loading:  Loading,
+    images:       HashMap<String, Value>,
+    // Each one is an object whose `buffer` is filled in once fetched
+    sounds:       HashMap<String, Value>,
+}
+
+impl Assets {
+    // Starts loading everything at once
+    pub fn load() -> Assets {
+        let manifest = Manifest::parse(MANIFEST).expect("asset manifest is broken");
+        let mut assets = Assets {
+            loading:  Loading::new(&manifest),
+            manifest: manifest,
+            images:   HashMap::new(),
+            sounds:   HashMap::new(),
+        };
+
+        for (index, asset) in assets.manifest.assets.iter().enumerate() {
+            let index = index as u32;
+            let path = asset.path.as_str();
+            match asset.kind {
+                AssetKind::Image => {
+                    let image = js! {
+                        var img = new Image();
+                        img.onload = function () {
+                            Module.exports.assetCallback(@{index}, "");
+                        };
+                        img.onerror = function () {
+                            Module.exports.assetCallback(@{index}, "could not load " + @{path});
+                        };
+                        img.src = @{path};
+                        return img;
+                    };
+                    assets.images.insert(asset.name.clone(), image);
+                },
+                AssetKind::Font => {
+                    // Without the font loading API, the page's stylesheet
+                    // loads it, and we just don't get to know when
+                    let supported: bool = js! {
+                        if (typeof FontFace === "undefined" || !document.fonts) {
+                            return false;
+                        }
+                        var face = new FontFace(@{&asset.name}, "url(" + @{path} + ")");
+                        face.load().then(function (loaded) {
+                            document.fonts.add(loaded);
+                            Module.exports.assetCallback(@{index}, "");
+                        }, function () {
+                            Module.exports.assetCallback(@{index}, "could not load " + @{path});
+                        });
+                        return true;
+                    }.try_into().unwrap();
+                    if !supported {
+                        assets.loading.finish(index as usize, Ok(()));
+                    }
+                },
+                AssetKind::Sound => {
+                    let sound = js! {
+                        var sound = { buffer: null };
+                        fetch(@{path}).then(function (response) {
+                            if (!response.ok) {
+                                throw new Error(response.status);
+                            }
+                            return response.arrayBuffer();
+                        }).then(function (buffer) {
+                            sound.buffer = buffer;
+                            Module.exports.assetCallback(@{index}, "");
+                        }).catch(function () {
+                            Module.exports.assetCallback(@{index}, "could not load " + @{path});
+                        });
+                        return sound;
+                    };
+                    assets.sounds.insert(asset.name.clone(), sound);
+                },
+                // Already checked by Loading
+                AssetKind::Levels => {},
+            }
+        }
+
+        assets
+    }
+
+    pub fn finish(&mut self, index: usize, error: &str) {
+        let result = if error.is_empty() { Ok(()) } else { Err(error.to_string()) };
+        self.loading.finish(index, result);
+    }
+
+    // Sprites missing from the manifest come out blank, and get
+    // drawn as plain shapes
+    pub fn sprite(&self, name: &str) -> Sprite {
+        let def = self.manifest.sprite(name);
+        let image = def.and_then(|def| self.images.get(&def.image));
+        match (def, image) {
+            (Some(def), Some(image)) => Sprite {
+                image:  image.clone(),
+                region: def.region,
+            },
+            _ => Sprite {
+                image:  Value::Null,
+                region: None,
+            },
+        }
+    }
+
+    pub fn is_loaded(&self, kind: AssetKind, name: &str) -> bool {
+        let index = self.manifest.assets.iter().position(|asset| asset.kind == kind && asset.name == name);
+        match index {
+            Some(index) => self.loading.states[index] == LoadState::Loaded,
+            None => false,
+        }
+    }
+
+    pub fn sound(&self, name: &str) -> Option<&Value> {
+        self.sounds.get(name)
+    }
+}
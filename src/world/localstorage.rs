@@ -0,0 +1,49 @@
+use stdweb::Value;
+use stdweb::unstable::TryInto;
+
+use storage::Storage;
+
+// The browser's localStorage. Private browsing and full disks make it
+// throw, in which case we behave as if nothing was ever stored.
+pub struct LocalStorage;
+
+impl Storage for LocalStorage {
+    fn load(&self, key: &str) -> Option<String> {
+        let value = js! {
+            try {
+                return window.localStorage.getItem(@{key});
+            } catch (e) {
+                return null;
+            }
+        };
+        match value {
+            Value::String(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    fn save(&mut self, key: &str, value: &str) -> Result<(), String> {
+        let saved: bool = js! {
+            try {
+                window.localStorage.setItem(@{key}, @{value});
+                return true;
+            } catch (e) {
+                return false;
+            }
+        }.try_into().unwrap();
+
+        if saved {
+            Ok(())
+        } else {
+            Err(format!("could not save {}", key))
+        }
+    }
+
+    fn remove(&mut self, key: &str) {
+        js! {
+            try {
+                window.localStorage.removeItem(@{key});
+            } catch (e) {}
+        };
+    }
+}
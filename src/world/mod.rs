@@ -1,16 +1,45 @@
+use std::mem;
+
 use input;
 use stdweb::web;
 use stdweb::unstable::TryInto;
 
+use sim::{Game, GameMode, GameEvent, PlayerInput, Effect, BlockKind, LevelSet, FIELD_SIZE,
+          GRID_COLUMNS, GRID_ROWS};
+use net::Message;
+use storage::{HighScores, SavedGame};
+use settings::{Settings, Setting, ALL_SETTINGS};
+use theme::{Theme, Pattern};
+use locale::{Locale, AUTO_LANGUAGE};
+use fx::{Particles, Camera};
+use hud::{self, Hud, Anchor, Layout, MAX_LIFE_ICONS};
+use audio::{self, AudioQueue};
+use announce::Announcer;
+use editor::{Editor, BRUSH_COLORS};
+use assets::AssetKind;
+use text::{Align, TextBlock, GOHUFONT};
+
 mod state;
 mod render;
-mod physics;
+mod netplay;
+mod scene;
+mod localstorage;
+mod loader;
+mod sound;
+mod access;
 
 
-use self::state::{BallState, PaddleState, Block};
+use self::state::Sprites;
 use self::render::Renderer;
-use self::physics::Collision;
+use self::netplay::NetClient;
+use self::scene::{Scene, NameEntry, TitleItem};
+use self::localstorage::LocalStorage;
+use self::loader::Assets;
+use self::sound::AudioPlayer;
+use self::access::{AccessBridge, MenuView};
 
+// How long notices stay on screen (ms)
+const NOTICE_TIME: f64 = 3000.0;
 
 
 
@@ -20,477 +49,1536 @@ use self::physics::Collision;
 pub struct World {
     pub canvas:       web::Element,
     pub renderer:     Renderer,
+    pub assets:       Assets,
+    pub sprites:      Sprites,
 
     
     pub fps:          f64,
     pub pause:        bool,
     pub fullscreen:   bool,
+    // Message for the player, with how long it stays up (ms)
+    pub notice:       Option<(String, f64)>,
+    // Spectators may watch from the top player's side
+    pub view_flipped: bool,
 
     pub input:        input::KeyState,
+    pub pointer:      input::PointerState,
     pub tilt:         input::TiltState,
-    pub ball_state:   BallState,
-    pub paddle_state: PaddleState,
-
-    pub block_size:   (f32, f32),
-    pub level_blocks: Vec<Block>,
-    pub collided:     bool,
+    pub scene:        Scene,
+    pub game:         Game,
+    // What the game went through this frame. Online, catching up may
+    // take more than one tick.
+    pub events:       Vec<GameEvent>,
+    pub particles:    Particles,
+    pub camera:       Camera,
+    pub hud:          Hud,
+    pub audio:        AudioQueue,
+    pub player:       AudioPlayer,
+    pub announcer:    Announcer,
+    pub access:       AccessBridge,
+    pub netplay:      Option<NetClient>,
+
+    pub editor:       Editor,
+    // Game put away while the editor has the field, to get back to
+    // once it's closed
+    pub shelved:      Option<Game>,
+    // Playing the editor's level, which goes back to it when over
+    pub testing:      bool,
+
+    pub storage:      LocalStorage,
+    pub scores:       HighScores,
+    // Game the player left halfway, if any
+    pub saved:        Option<SavedGame>,
+    pub settings:     Settings,
+    pub theme:        Theme,
+    pub locale:       Locale,
+    // Boost state for each player, when boost is toggled instead of held
+    pub boost_locked: [bool; 2],
 }
 
 
 impl World {
     pub fn new() -> World {
         let canvas = web::document().get_element_by_id("viewport").unwrap();
+        let storage = LocalStorage;
+        let assets = Assets::load();
+        let sprites = Sprites::new(&assets);
+        let settings = Settings::load(&storage).unwrap_or_else(|reason| {
+            js! { console.log("Could not read settings: " + @{reason}); };
+            Settings::new()
+        });
+        let renderer = Renderer::new(&canvas, (FIELD_SIZE.0 as u32, FIELD_SIZE.1 as u32));
+        let seed: f64 = js!( return Date.now(); ).try_into().unwrap();
+        let scores = HighScores::load(&storage).unwrap_or_else(|reason| {
+            js! { console.log(@{reason}); };
+            HighScores::locked()
+        });
+        let saved = SavedGame::load(&storage).unwrap_or_else(|reason| {
+            js! { console.log("Could not read saved game: " + @{reason}); };
+            None
+        });
+        let editor = Editor::load(&storage).unwrap_or_else(|reason| {
+            js! { console.log("Could not read the editor's level: " + @{reason}); };
+            Editor::new()
+        });
+
         let mut world = World {
             canvas:     canvas.clone(),
-            renderer: Renderer::new(&canvas),
+            renderer: renderer,
+            assets:   assets,
+            sprites:  sprites,
             fps: 0.0,
             pause: false,
             
-            // Before you say "the document keeps track of fullscreen state":
-            // I already tried using that.
+            // Kept in sync by the document's fullscreen change events
             fullscreen: false,
+            notice:     None,
+            view_flipped: false,
             input:        input::KeyState::new(),
+            pointer:      input::PointerState::new(),
             tilt:         input::TiltState::new(),
-            ball_state:   BallState::new(),
-            paddle_state: PaddleState::new(),
-
-            block_size: (0.0, 0.0),
-            level_blocks: vec![],
-            collided: false,
+            scene:        Scene::Loading,
+            game:         Game::new(GameMode::Single, FIELD_SIZE, seed as u64),
+            events:       vec![],
+            particles:    Particles::new(seed as u64),
+            camera:       Camera::new(seed as u64),
+            hud:          Hud::new(),
+            audio:        AudioQueue::new(),
+            player:       AudioPlayer::new(),
+            announcer:    Announcer::new(),
+            access:       AccessBridge::new(),
+            netplay:      None,
+
+            editor:       editor,
+            shelved:      None,
+            testing:      false,
+
+            storage:      storage,
+            scores:       scores,
+            saved:        saved,
+            settings:     settings,
+            theme:        Theme::classic(),
+            locale:       Locale::english(),
+            boost_locked: [false, false],
         };
         
-        world.fit_viewport();
-        world.paddle_state.xpos = world.renderer.size.0 as f32 / 2.0;
-
-        // Load font
-        world.renderer.load_font("GohuFont", 14);
-
-        // == TEST: Create blocks
-        world.block_size = (world.renderer.size.0 as f32 * 0.06,
-                            world.renderer.size.1 as f32 * 0.0520845);
-        for i in 0..9 {
-            for j in 0..9 {
-                let pos = ((world.renderer.size.0 as f32 / 4.0) + (i as f32 * world.block_size.0) as f32,
-                           (world.renderer.size.1 as f32 / 4.0) + (j as f32 * world.block_size.1) as f32);
-                world.level_blocks.push(Block {
-                    pos: pos,
-                    color: "#fff".to_string(),
-                    active: true
-                });
-            }
-        }
-        // ==
+        world.apply_settings();
         
         world
     }
 
+    // Puts the current settings to use. Online games are left alone,
+    // since they have to stay the same for everyone.
+    fn apply_settings(&mut self) {
+        self.tilt.config.sensitivity = self.settings.tilt_sensitivity as f64;
+        self.camera.reduce_motion = self.settings.reduce_motion;
+        self.player.set_volumes(audio::gain(self.settings.music_volume),
+                                audio::gain(self.settings.effects_volume));
+        if self.netplay.is_none() {
+            self.game.boost_factor = self.settings.boost_multiplier;
+        }
+
+        let mut theme = Theme::by_name(&self.settings.theme).unwrap_or_else(Theme::classic);
+        if self.settings.high_contrast {
+            theme = theme.with_high_contrast();
+        }
+        self.theme = theme.with_vision(self.settings.color_vision);
+        self.locale = if self.settings.language == AUTO_LANGUAGE {
+            let tag: String = js!( return navigator.language || ""; ).try_into().unwrap();
+            Locale::negotiate(&tag)
+        } else {
+            Locale::by_code(&self.settings.language).unwrap_or_else(Locale::english)
+        };
+        js! { document.documentElement.lang = @{&self.locale.code}; };
+        self.renderer.flat = self.settings.high_contrast;
+        self.renderer.backing = if self.settings.high_contrast {
+            Some(self.theme.background.clone())
+        } else {
+            None
+        };
+        self.renderer.set_text_size(self.settings.font_size);
+        self.fit_viewport();
+    }
+
 
     
 
+    // Scales the field onto the canvas. The game itself never notices,
+    // so this is safe to do at any time.
     pub fn fit_viewport(&mut self) {
-        js!( @{&self.canvas}.width = @{&self.renderer.size.0};
-             @{&self.canvas}.height = @{&self.renderer.size.1}; );
+        let canvas_size = Renderer::window_size(self.settings.max_resolution);
+        let field = (self.game.field.0 as u32, self.game.field.1 as u32);
+        self.renderer.fit(&self.canvas, canvas_size, field);
+    }
+
+    // Fullscreen was entered or left, maybe from outside the game
+    // (e.g. pressing Esc)
+    pub fn fullscreen_changed(&mut self, fullscreen: bool) {
+        self.fullscreen = fullscreen;
+        self.fit_viewport();
+    }
+
+    pub fn orientation_changed(&mut self) {
+        // The neutral pose was taken holding the device some other way
+        self.tilt.neutral = None;
+        self.fit_viewport();
+    }
 
-        // Fix some values which are viewport-dependent
-        //self.paddle_state.xpos = self.renderer.size.0 as f32 / 2.0;
-        self.ball_state.diameter = self.renderer.size.1 as f32 * 0.034723;
-        self.ball_state.basespd = self.renderer.size.1 as f32 / 72.0;
+    // Nobody gets to keep playing on a window they can't see
+    pub fn focus_lost(&mut self) {
+        // Keys held while leaving never get released
+        self.input.async.clear();
 
-        self.paddle_state.ypos = 11.0 * self.renderer.size.1 as f32 / 12.0;
-        self.paddle_state.basespd = self.renderer.size.1 as f32 / 72.0 * 0.75;
-        self.paddle_state.spd = self.paddle_state.basespd;
-        self.paddle_state.sz = (self.renderer.size.0 as f32 * 0.12,
-                                self.renderer.size.1 as f32 * 0.034723);
+        let playing = match self.scene {
+            Scene::Playing => true,
+            _ => false,
+        };
+        if playing && self.netplay.is_none() {
+            self.pause = true;
+        }
+    }
+
+    // Plays on levels made up from a seed instead of the shipped ones
+    pub fn play_random(&mut self, seed: &str) {
+        self.game.levels = LevelSet::random(seed);
+        self.game.reset();
+    }
+
+    // Connects to a relay server and waits for the other player,
+    // or for the match to watch.
+    pub fn go_online(&mut self, url: &str, room: &str, mode: GameMode, watching: bool) {
+        let field = self.game.field;
+        self.netplay = Some(NetClient::connect(url, room, mode, field, watching));
+        if !self.is_loading() {
+            self.scene = Scene::Playing;
+        }
+        self.pause = false;
+    }
+
+    fn is_loading(&self) -> bool {
+        match self.scene {
+            Scene::Loading => true,
+            _ => false,
+        }
+    }
 
-        // TODO: Also reposition paddle and ball, if we get any problem
-        // coming back from fullscreen
+    pub fn asset_loaded(&mut self, index: usize, error: &str) {
+        if !error.is_empty() {
+            js! { console.log("Asset failed: " + @{error}); };
+        }
+        self.assets.finish(index, error);
+    }
+
+    fn is_watching(&self) -> bool {
+        match self.netplay {
+            Some(ref client) => client.watching,
+            None => false,
+        }
+    }
+
+    pub fn net_open(&mut self) {
+        if let Some(ref client) = self.netplay {
+            client.join();
+        }
+    }
+
+    pub fn net_message(&mut self, text: &str) {
+        let message = match Message::parse(text) {
+            Ok(message) => message,
+            Err(reason) => {
+                js! { console.log("Bad message from relay: " + @{reason}); };
+                return;
+            },
+        };
+
+        let started = match self.netplay {
+            Some(ref mut client) => client.receive(&mut self.game, &message),
+            None => None,
+        };
+
+        // Everyone plays on the host's field, or physics would differ
+        if let Some(config) = started {
+            self.game = config.create_game();
+            self.fit_viewport();
+        }
+    }
+
+    pub fn net_closed(&mut self) {
+        if let Some(ref mut client) = self.netplay {
+            client.closed();
+        }
     }
 
     // NOTE: This only works in event handlers.
+    // Whether it worked or not is only known later, through the
+    // fullscreen change and error events.
     pub fn toggle_fullscreen(&mut self) {
-        let fullscreen_press = {
-            let newstate = match self.input.async.get(&input::KeyType::FullScreen) {
-                Some(&state) => state,
-                None => false,
-            };
-            let oldstate = match self.input.new.get(&input::KeyType::FullScreen) {
-                Some(&state) => state,
-                None => false,
-            };
-            newstate && !oldstate
-        };
-        
-        if fullscreen_press {
-            js! {
-                if (typeof document.webkitCancelFullScreen !== "undefined") {
-                    if (@{&self.fullscreen}) {
-                        console.log("Exiting fullscreen mode");
-                        document.webkitCancelFullScreen();
-                    } else {
-                        console.log("Entering fullscreen mode");
-                        @{&self.canvas}.webkitRequestFullScreen(Element.ALLOW_KEYBOARD_INPUT);
-                    }
-                } else if (typeof document.mozCancelFullScreen !== "undefined") {
-                    if(@{&self.fullscreen}) {
-                        console.log("Exiting fullscreen mode");
-                        document.mozCancelFullScreen();
-                    } else {
-                        console.log("Entering fullscreen mode");
-                        @{&self.canvas}.mozRequestFullScreen();
-                    }
+        let supported: bool = js! {
+            var canvas = @{&self.canvas};
+            var current = document.fullscreenElement
+                || document.webkitFullscreenElement
+                || document.mozFullScreenElement;
+            var result;
+
+            if (current) {
+                var exit = document.exitFullscreen
+                    || document.webkitExitFullscreen
+                    || document.mozCancelFullScreen;
+                if (!exit) {
+                    return false;
                 }
-            };
-            self.fullscreen = !self.fullscreen;
+                console.log("Exiting fullscreen mode");
+                result = exit.call(document);
+            } else {
+                var request = canvas.requestFullscreen
+                    || canvas.webkitRequestFullscreen
+                    || canvas.mozRequestFullScreen;
+                if (!request) {
+                    return false;
+                }
+                console.log("Entering fullscreen mode");
+                result = request.call(canvas);
+            }
+
+            // Newer browsers also reject a promise, but the error
+            // event already takes care of that
+            if (result && typeof result.catch === "function") {
+                result.catch(function () {});
+            }
+            return true;
+        }.try_into().unwrap();
+
+        if !supported {
+            self.notify("notice.no_fullscreen");
         }
+    }
 
-        self.fit_viewport();
+    pub fn fullscreen_failed(&mut self) {
+        self.notify("notice.fullscreen_denied");
+    }
+
+    // Shows a message over whatever is on screen for a little while.
+    // Takes the key of its text on the string table.
+    fn notify(&mut self, key: &str) {
+        let text = self.locale.text(key).to_string();
+        self.show_notice(text);
+    }
+
+    fn show_notice(&mut self, text: String) {
+        js! { console.log(@{&text}); };
+        self.announcer.announce(&text);
+        self.notice = Some((text, NOTICE_TIME));
     }
 
     pub fn input_dispatch(&mut self, key: input::KeyType, pressed: bool) {
+        if pressed {
+            self.player.resume();
+        }
         self.input.async.insert(key, pressed);
     }
 
+    // Pointer positions come in canvas pixels
+    pub fn pointer_moved(&mut self, pos: (f32, f32)) {
+        let pos = self.renderer.to_field(pos);
+        let size = self.renderer.size;
+        let inside = pos.0 >= 0.0 && pos.1 >= 0.0
+            && pos.0 < size.0 as f32 && pos.1 < size.1 as f32;
+        self.pointer.pos = if inside { Some(pos) } else { None };
+        self.pointer.moved = true;
+    }
+
+    pub fn pointer_left(&mut self) {
+        self.pointer.pos = None;
+        self.pointer.async = [false; 2];
+    }
+
+    pub fn pointer_dispatch(&mut self, button: usize, pressed: bool) {
+        if pressed {
+            self.player.resume();
+        }
+        if button < self.pointer.async.len() {
+            self.pointer.async[button] = pressed;
+        }
+    }
+
+    pub fn gamepad_dispatch(&mut self, player: usize, mask: u32) {
+        let controls = input::PaddleControls::for_player(player);
+        self.input.pad_dispatch(&controls, mask);
+    }
+
+    // Reads a player's controls into this tick's input
+    fn player_input(&self, player: usize, steer: Option<f32>) -> PlayerInput {
+        let controls = input::PaddleControls::for_player(player);
+        let mut input = PlayerInput::new();
+        input.left   = self.input.held(&controls.left);
+        input.right  = self.input.held(&controls.right);
+        input.launch = self.input.held(&controls.launch);
+        input.boost  = if self.settings.toggle_boost {
+            self.boost_locked[player % self.boost_locked.len()]
+        } else {
+            self.input.held(&controls.boost)
+        };
+
+        // Tilt only ever drives the first player's paddle
+        if player == 0 {
+            if let Some(axis) = steer {
+                input.set_steer(axis);
+            }
+        }
+        input
+    }
+
 
 
 
 
 
     pub fn update(&mut self, dt: f64) {
+        let expired = match self.notice {
+            Some((_, ref mut time)) => {
+                *time -= dt;
+                *time <= 0.0
+            },
+            None => false,
+        };
+        if expired {
+            self.notice = None;
+        }
+
         // Collect input state
-        self.input.new = self.input.async.clone();
+        self.input.collect();
+        self.pointer.collect();
         if self.tilt.active {
             self.tilt.new = self.tilt.async.clone();
-            // Collect device orientation
-            let is_portrait: bool = js!( return (window.innerHeight > window.innerWidth); )
-                .try_into()
-                .unwrap();
-            if is_portrait {
-                self.tilt.orient = input::OrientationType::Portrait;
-            } else {
-                self.tilt.orient = input::OrientationType::Landscape;
+
+            // Capture neutral pose on demand, or on the very first sample
+            // so the paddle does not start out pinned to one side
+            if self.input.pressed(&input::KeyType::Calibrate) || !self.tilt.is_calibrated() {
+                self.tilt.calibrate();
+            }
+
+            // Serving on a tilting device also takes the
+            // current pose as neutral
+            let launch = input::PaddleControls::player_one().launch;
+            if self.game.ball_state.stopped && self.input.pressed(&launch) {
+                self.tilt.calibrate();
             }
         }
-        
-        // Process new input
-        for (key, state) in &self.input.new {
-            match (key, state) {
-                (&input::KeyType::Left,  &true) => {
-                    if !self.pause {
-                        self.paddle_state.xpos -= self.paddle_state.spd;
-                    }
-                },
-                (&input::KeyType::Right, &true) => {
-                    if !self.pause {
-                        self.paddle_state.xpos += self.paddle_state.spd;
-                    }
-                }
-                (&input::KeyType::S, &true)     => {
-                    if !self.pause && self.ball_state.stopped == true {
-                        // Eh well, something funny was going on with the rand crate, so
-                        // what the heck, might as well use js.
-                        let initial_angle: f64 = js!( return 67.5 + (Math.random() * 46); )
-                            .try_into()
-                            .unwrap();
-                        let initial_angle = initial_angle as f32; // We lose precision, but meh
-                        self.ball_state.spd =
-                            (self.ball_state.basespd * f32::cos(initial_angle.to_radians()),
-                             -self.ball_state.basespd * f32::sin(initial_angle.to_radians()) );
-
-                        self.ball_state.stopped = false;
-                    }
-                },
 
-                // Paddle move speed depends on whether you're holding A or not
-                (&input::KeyType::A, &true) => self.paddle_state.spd = self.paddle_state.basespd * 2.0,
-                (&input::KeyType::A, &false) => self.paddle_state.spd = self.paddle_state.basespd,
-                _ => {},
+        let playing = match self.scene {
+            Scene::Playing => true,
+            _ => false,
+        };
+        if playing {
+            let tick = self.game.tick;
+            self.events.clear();
+            self.update_game();
+
+            // Effects follow the game, so they freeze along with it
+            if self.game.tick != tick {
+                self.particles.feed(&self.game, &self.events, &self.theme);
+                self.particles.update();
+                self.camera.feed(&self.game, &self.events);
+                self.hud.feed(&self.game, &self.events);
+                self.hud.update();
+                self.audio.feed(&self.game, &self.events);
+                self.announcer.feed(&self.game, &self.events, &self.locale);
             }
+            if !self.pause {
+                self.camera.update();
+            } else {
+                self.audio.set_music(None);
+            }
+        } else {
+            self.audio.set_music(None);
+            self.update_menus();
+        }
+        self.player.play(self.audio.drain());
+
+        let menu = self.menu_view();
+        self.access.show_menu(menu, &self.locale);
+        for text in self.announcer.drain() {
+            self.access.announce(&text);
         }
 
+        // Give input to old
+        self.input.old = self.input.new.clone();
+        self.pointer.old = self.pointer.new;
+        self.pointer.moved = false;
+        if self.tilt.active {
+            self.tilt.old = self.tilt.new.clone();
+        }
+    }
+
+
+
+    
+
+
+    fn update_game(&mut self) {
+        // Nobody else can wait on us while playing offline, so these are
+        // the only times we may pause or mess with the game
+        let online = self.netplay.is_some();
+
+        // Switching modes is only allowed between serves
+        if !online && !self.testing && !self.pause && self.game.ball_state.stopped
+            && self.input.pressed(&input::KeyType::ModeSelect) {
+                self.game.mode = self.game.mode.next();
+                self.game.reset();
+            }
+
         // Check for single-press of pause key
-        {
-            let enter_press = {
-                let newstate = match self.input.new.get(&input::KeyType::Enter) {
-                    Some(&state) => state,
-                    None => false,
-                };
-                let oldstate = match self.input.old.get(&input::KeyType::Enter) {
-                    Some(&state) => state,
-                    None => false,
-                };
-                newstate && !oldstate
-            };
-            
-            if enter_press {
-                self.pause = !self.pause;
+        if !online && self.input.pressed(&input::KeyType::Enter) {
+            self.pause = !self.pause;
+        }
+
+        // Play-tests go back to the editor instead, whenever the
+        // designer wants
+        if self.testing && (self.input.pressed(&input::KeyType::Back)
+                            || (self.pause && self.input.pressed(&input::KeyType::Quit))) {
+            self.end_test(None);
+            return;
+        }
+
+        // Leaving from the pause screen keeps the game for later
+        if !online && self.pause && self.input.pressed(&input::KeyType::Quit) {
+            let saved = SavedGame::from_game(&self.game);
+            if let Err(reason) = saved.save(&mut self.storage) {
+                js! { console.log("Could not save game: " + @{reason}); };
+            }
+            self.saved = Some(saved);
+            self.pause = false;
+            self.scene = Scene::Title(0);
+            return;
+        }
+
+        // Spectators only get to pick where they watch from
+        if self.is_watching() {
+            if self.input.pressed(&input::KeyType::SwitchView) {
+                self.view_flipped = !self.view_flipped;
+            }
+            if let Some(ref mut client) = self.netplay {
+                client.watch(&mut self.game, &mut self.events);
             }
+
+            return;
         }
 
         // ======
         
         // The following events will only happen if the game is not paused.
         if !self.pause {
-
             // Process mobile input
-            {
-                if self.tilt.orient != input::OrientationType::Unknown {
-                    // Calculate beta and gamma rotations, respectively
-                    // Works well with landscape, and tilting up/down
-                    // instead of left/right
-                    // moving Y axis (pitch; gamma) around X axis (roll; beta)
-                    //let rotations = (self.tilt.new.beta, // main angle
-                    //                 self.tilt.new.gamma);
-                    //let spin = (rotations.0.cos() * rotations.1.sin())
-                    //    .atan2(rotations.0.sin());
-
-                    // moving X axis (roll; beta) around Y axis (pitch; gamma)
-                    //let rotations = (self.tilt.new.gamma,
-                    //                 self.tilt.new.beta);
-                    //let spin = (rotations.0.cos() * rotations.1.sin())
-                    //    .atan2(rotations.0.sin());
-
-                    // moving Z axis (yaw; alpha) around Y axis (pitch; gamma)
-                    //let rotations = (self.tilt.new.gamma,
-                    //                 self.tilt.new.alpha);
-                    //let spin = (rotations.0.cos() * rotations.1.sin())
-                    //    .atan2(rotations.0.sin());
-
-                    let spin = self.tilt.new.beta;
-
-                    
-                    
-                    let ratio = spin as f32 *
-                    // Make the tilt a little more violent by narrowing
-                    // the distance down, depending on orientation
-                        if self.tilt.orient == input::OrientationType::Portrait {
-                            3.0
-                        } else { 3.5 };
-
-                    let halfwidth = self.renderer.size.0 as f32 / 2.0;
-                    self.paddle_state.xpos =  0.0 +  (halfwidth * ratio);
+            let steer = if self.tilt.active {
+                let new_tilt = self.tilt.new.clone();
+                Some(self.tilt.feed(&new_tilt) as f32)
+            } else {
+                None
+            };
+
+            // Players who can't hold a key down tap it instead
+            if self.settings.toggle_boost {
+                for player in 0..self.boost_locked.len() {
+                    let boost = input::PaddleControls::for_player(player).boost;
+                    if self.input.pressed(&boost) {
+                        self.boost_locked[player] = !self.boost_locked[player];
+                    }
                 }
             }
 
-            // Clamp paddle position
-            let paddle_halfwidth = self.paddle_state.sz.0 / 2.0;
-            let paddle_bounds = (self.paddle_state.xpos - paddle_halfwidth,         // left
-                                 self.paddle_state.xpos + paddle_halfwidth,         // right
-                                 self.paddle_state.ypos,                            // top
-                                 self.paddle_state.ypos + self.paddle_state.sz.1 ); // bottom
-
-            if paddle_bounds.0 < 0.0 {
-                self.paddle_state.xpos = paddle_halfwidth;
-            } else if paddle_bounds.1 > self.renderer.size.0 as f32 {
-                self.paddle_state.xpos = self.renderer.size.0 as f32 - paddle_halfwidth;
+            let inputs: Vec<PlayerInput> = (0..self.game.paddles.len())
+                .map(|player| self.player_input(player, steer))
+                .collect();
+
+            match self.netplay {
+                // Online, we always play with player one's controls,
+                // whichever seat we got
+                Some(ref mut client) => client.update(&mut self.game, inputs[0], &mut self.events),
+                // Offline, big hits hold the game still for a moment
+                None => if !self.camera.is_frozen() {
+                    self.game.step(&inputs);
+                    self.events.extend(self.game.events.iter().cloned());
+                },
             }
+        } // End of pausable events
 
-            // Handle ball state
-            if self.ball_state.stopped {
-                self.ball_state.pos.0 = self.paddle_state.xpos;
-                self.ball_state.pos.1 = 21.0 * self.renderer.size.1 as f32 / 24.0;
-                if self.ball_state.afterimages.len() > 0 {
-                    self.ball_state.afterimages.clear();
-                }
+        // One go at the level is enough to tell how it plays
+        if self.testing {
+            let cleared = self.events.iter().any(|event| match *event {
+                GameEvent::LevelCleared { .. } => true,
+                _ => false,
+            });
+            if cleared {
+                self.end_test(Some("editor.cleared"));
+            } else if self.game.is_over() {
+                self.end_test(Some("editor.lost"));
+            }
+            return;
+        }
+
+        // Offline, see if that was good enough for the table
+        if !online && self.game.is_over() && self.game.mode.keeps_high_scores() {
+            self.forget_saved_game();
+            let (mode, score) = (self.game.mode, self.game.score);
+            self.scene = if self.scores.qualifies(&self.game.levels.name, mode, score) {
+                Scene::NameEntry(NameEntry::new(mode, score))
             } else {
-                // Transform position
-                self.ball_state.pos.0 += self.ball_state.spd.0;
-                self.ball_state.pos.1 += self.ball_state.spd.1;
-
-                // Handle basic boundary collision
-                let ball_radius = self.ball_state.diameter / 2.0;
-                let ball_boundary = (self.ball_state.pos.0 - ball_radius,   // left
-                                     self.ball_state.pos.0 + ball_radius,   // right
-                                     self.ball_state.pos.1 - ball_radius,   // top
-                                     self.ball_state.pos.1 + ball_radius ); // bottom
-
-                // Handle X axis
-                if ball_boundary.0 < 0.0 && self.ball_state.spd.0 < 0.0 {
-                    self.ball_state.pos.0 = ball_radius;
-                    self.ball_state.spd.0 *= -1.0;
-                } else if ball_boundary.1 > self.renderer.size.0 as f32 && self.ball_state.spd.0 > 0.0 {
-                    self.ball_state.pos.0 = self.renderer.size.0 as f32 - ball_radius;
-                    self.ball_state.spd.0 *= -1.0;
-                }
+                Scene::HighScores(mode, None)
+            };
+        }
+    }
 
-                // Handle Y axis
-                if ball_boundary.2 < 0.0 && self.ball_state.spd.1 < 0.0 {
-                    self.ball_state.pos.1 = ball_radius;
-                    self.ball_state.spd.1 *= -1.0;
-                } else if ball_boundary.2 > self.renderer.size.1 as f32 && self.ball_state.spd.1 > 0.0 {
-                    // Respawn ball
-                    self.ball_state.stopped = true;
+    fn forget_saved_game(&mut self) {
+        if self.saved.take().is_some() {
+            SavedGame::clear(&mut self.storage);
+        }
+    }
+
+    // Title, name entry and high scores
+    fn update_menus(&mut self) {
+        // The editor has keys of its own
+        let editing = match self.scene {
+            Scene::Editor => true,
+            _ => false,
+        };
+        if editing {
+            self.update_editor();
+            return;
+        }
+
+        let confirm = self.input.pressed(&input::KeyType::S)
+            || self.input.pressed(&input::KeyType::Enter);
+        let (left, right) = (self.input.pressed(&input::KeyType::Left),
+                             self.input.pressed(&input::KeyType::Right));
+        let back = self.input.pressed(&input::KeyType::Back);
+        // Also set for resumed games, which need them applied again
+        let mut settings_changed = false;
+
+        let next = match self.scene {
+            // Moves on by itself once everything is in, but waits for
+            // the player to acknowledge anything that went wrong
+            Scene::Loading => {
+                let loading = &self.assets.loading;
+                if loading.is_done() && (loading.errors().is_empty() || confirm) {
+                    self.player.decode(&self.assets);
+                    if self.assets.is_loaded(AssetKind::Font, GOHUFONT.family) {
+                        self.renderer.build_atlas();
+                    }
+                    if self.netplay.is_some() {
+                        Some(Scene::Playing)
+                    } else {
+                        Some(Scene::Title(0))
+                    }
+                } else {
+                    None
                 }
+            },
+            Scene::Title(selected) => {
+                let items = TitleItem::all(self.saved.is_some());
+                let count = items.len();
+                // The menu gets shorter once the saved game is gone
+                let selected = selected.min(count - 1);
+                let item = items[selected];
+
+                // Shortcuts work from anywhere on the menu
+                let chosen = if self.input.pressed(&input::KeyType::Resume) && self.saved.is_some() {
+                    Some(TitleItem::Resume)
+                } else if self.input.pressed(&input::KeyType::HighScores) {
+                    Some(TitleItem::HighScores)
+                } else if self.input.pressed(&input::KeyType::Options) {
+                    Some(TitleItem::Options)
+                } else if confirm {
+                    Some(item)
+                } else {
+                    None
+                };
 
-                // Handle paddle collision
-                // Check if we're within Y and X range, respectively.
-                if self.ball_state.spd.1 > 0.0 // If we're descending, and...
-                    && ((ball_boundary.3 >= paddle_bounds.2) // We're at least intersecting...
-                        && (ball_boundary.3 <= paddle_bounds.3)) // the paddle in any way...
-                    // Then we verify if we're within X range...
-                    && (ball_boundary.1 >= paddle_bounds.0 && ball_boundary.0 <= paddle_bounds.1) {
-                        // We kind of bounce proportionally to the relative paddle position.
-                        // The further away from the center of the paddle, the more open the
-                        // bouncing angle is, scaling to 0.0 to 45.0 towards the edge.
-                        // We first calculate a ratio [-1.0, 1.0], 0.0 being the paddle center.
-                        let ratio = (-2.0 * ((self.ball_state.pos.0 - paddle_bounds.0)
-                                             / (paddle_bounds.1 - paddle_bounds.0)))
-                            + 1.0;
-                        
-                        // We compute the angle by assuming 90 degrees and then adding an angle
-                        // in range [-45, 45]
-                        let theta: f32 = ((90.0 + (ratio * 45.0)) as f32).to_radians();
-
-                        // And now we apply theta to our ball's base speed, distributing it to
-                        // the axis
-                        self.ball_state.spd = ( self.ball_state.basespd * f32::cos(theta),
-                                                -self.ball_state.basespd * f32::sin(theta) );
+                if self.input.pressed(&input::KeyType::ModeSelect)
+                    || chosen == Some(TitleItem::Mode)
+                    || (item == TitleItem::Mode && (left || right)) {
+                        self.game.mode = if left { self.game.mode.previous() } else { self.game.mode.next() };
+                        self.game.reset();
                     }
 
-                // Afterimages
-                if self.ball_state.afterimages.len() >= 7 {
-                    self.ball_state.afterimages.drain(0..1);
+                match chosen {
+                    Some(TitleItem::Play) => {
+                        // There's only room for one game in progress
+                        if self.saved.take().is_some() {
+                            SavedGame::clear(&mut self.storage);
+                        }
+                        self.game.reset();
+                        Some(Scene::Playing)
+                    },
+                    Some(TitleItem::Resume) => match self.saved.as_ref().map(|saved| saved.restore()) {
+                        Some(Ok(game)) => {
+                            self.game = game;
+                            settings_changed = true;
+                            Some(Scene::Playing)
+                        },
+                        Some(Err(reason)) => {
+                            js! { console.log("Could not resume game: " + @{reason}); };
+                            None
+                        },
+                        None => None,
+                    },
+                    Some(TitleItem::HighScores) => {
+                        let mut mode = self.game.mode;
+                        while !mode.keeps_high_scores() {
+                            mode = mode.next();
+                        }
+                        Some(Scene::HighScores(mode, None))
+                    },
+                    Some(TitleItem::Options) => Some(Scene::Options(0)),
+                    Some(TitleItem::Editor) => Some(Scene::Editor),
+                    Some(TitleItem::Mode) => None,
+                    None => {
+                        if self.input.pressed(&input::KeyType::Up) {
+                            Some(Scene::Title((selected + count - 1) % count))
+                        } else if self.input.pressed(&input::KeyType::Down) {
+                            Some(Scene::Title((selected + 1) % count))
+                        } else {
+                            None
+                        }
+                    },
                 }
-                self.ball_state.afterimages.push(self.ball_state.pos);
-            } // End of moving ball events
-
-
-
-            // Basic collision
-            let tilesz = self.block_size;
-
-            self.collided = false;
-
-            // We iterate over all blocks and only
-            // keep those who have not been collided.
-            // This is not the best way to handle collision,
-            // but it's enough for the amount of onscreen objs
-            let mut retrieved_collisions = vec![];
-            {
-                let ballstate = &self.ball_state;
-                self.level_blocks.retain(|ref block| {
-                    let tile_bounds = (block.pos.0 - tilesz.0,
-                                       block.pos.0 + tilesz.0,
-                                       block.pos.1 - tilesz.1,
-                                       block.pos.1 + tilesz.1);
-                    if let Some(collision) = Collision::collides(ballstate, block.pos, tile_bounds) {
-                        retrieved_collisions.push(collision);
-                        return false;
+            },
+            Scene::NameEntry(ref mut entry) => {
+                let (up, down) = (self.input.pressed(&input::KeyType::Up),
+                                  self.input.pressed(&input::KeyType::Down));
+                if left || right || up || down {
+                    entry.roll(right || up);
+                }
+
+                if confirm && entry.confirm() {
+                    let rank = self.scores.insert(&self.game.levels.name, entry.mode,
+                                                  &entry.name(), entry.score);
+                    if let Err(reason) = self.scores.save(&mut self.storage) {
+                        js! { console.log(@{reason}); };
                     }
-                    
-                    true
-                });
+                    Some(Scene::HighScores(entry.mode, rank))
+                } else {
+                    None
+                }
+            },
+            Scene::HighScores(mode, _) => {
+                if left || right {
+                    let mut mode = mode;
+                    loop {
+                        mode = if right { mode.next() } else { mode.previous() };
+                        if mode.keeps_high_scores() {
+                            break;
+                        }
+                    }
+                    Some(Scene::HighScores(mode, None))
+                } else if confirm || self.input.pressed(&input::KeyType::HighScores) || back {
+                    Some(Scene::Title(0))
+                } else {
+                    None
+                }
+            },
+            Scene::Options(selected) => {
+                let count = ALL_SETTINGS.len();
+                if self.input.pressed(&input::KeyType::Up) {
+                    Some(Scene::Options((selected + count - 1) % count))
+                } else if self.input.pressed(&input::KeyType::Down) {
+                    Some(Scene::Options((selected + 1) % count))
+                } else if left || right {
+                    self.settings.adjust(ALL_SETTINGS[selected], right);
+                    settings_changed = true;
+                    None
+                } else if confirm || self.input.pressed(&input::KeyType::Options) || back {
+                    if let Err(reason) = self.settings.save(&mut self.storage) {
+                        js! { console.log("Could not save settings: " + @{reason}); };
+                    }
+                    Some(Scene::Title(0))
+                } else {
+                    None
+                }
+            },
+            Scene::Playing | Scene::Editor => None,
+        };
+
+        if settings_changed {
+            self.apply_settings();
+        }
+        if let Some(scene) = next {
+            let editing = match scene {
+                Scene::Editor => true,
+                _ => false,
+            };
+            self.scene = scene;
+            if editing {
+                self.open_editor();
             }
+        }
+    }
 
-            // Calculate resulting vector
-            // Multiblock consensus
-            let final_collision = retrieved_collisions.iter()
-                .fold(Collision::new(),
-                      |acc, ref val| {
-                          let mut acc = acc;
-                          acc.valid = true;
-                          acc.vector.0 += val.vector.0;
-                          acc.vector.1 += val.vector.1;
-                          acc.vector.0 = acc.vector.0.signum();
-                          acc.vector.1 = acc.vector.1.signum();
-                          acc
-                      });
-
-            // Single-block "consensus"
-            //let final_collision = match retrieved_collisions.first() {
-            //    Some(collision) => collision.clone(),
-            //    None => Collision::new(),
-            //};
-            
-            // Apply result to ball.
-            // Notice that the resulting vector only ensures that
-            // the ball's speeds have the same signal as the result
-            // vector.
-            if final_collision.valid {
-                let mut ball_spd = self.ball_state.spd;
-                //js! { console.log("Final: " + @{format!("{:?}", final_collision.vector)}); }
-                if final_collision.vector.0 != 0.0
-                    && ball_spd.0.signum() != final_collision.vector.0.signum() {
-                    ball_spd.0 *= -1.0;
+    // Lays the editor's level out on the field, keeping whatever game
+    // was there for later
+    fn open_editor(&mut self) {
+        let seed: f64 = js!( return Date.now(); ).try_into().unwrap();
+        let game = Game::with_levels(GameMode::Single, FIELD_SIZE, seed as u64, self.editor.level_set());
+        let game = mem::replace(&mut self.game, game);
+        if self.shelved.is_none() {
+            self.shelved = Some(game);
+        }
+        self.fit_viewport();
+    }
+
+    fn close_editor(&mut self) {
+        if let Some(game) = self.shelved.take() {
+            self.game = game;
+        }
+        self.fit_viewport();
+        self.scene = Scene::Title(0);
+    }
+
+    fn start_test(&mut self) {
+        if !self.editor.can_play() {
+            self.notify("editor.nothing_to_break");
+            return;
+        }
+        self.game.mode = GameMode::Single;
+        self.game.levels = self.editor.level_set();
+        self.game.reset();
+        self.pause = false;
+        self.testing = true;
+        self.scene = Scene::Playing;
+    }
+
+    // Back to the editor, saying why if it wasn't the designer's call
+    fn end_test(&mut self, reason: Option<&str>) {
+        self.testing = false;
+        self.pause = false;
+        self.game.reset();
+        self.scene = Scene::Editor;
+        if let Some(reason) = reason {
+            self.notify(reason);
+        }
+    }
+
+    // Keeps the level around for next time, after every change
+    fn save_editor(&mut self) {
+        if let Err(reason) = self.editor.save(&mut self.storage) {
+            js! { console.log("Could not save the editor's level: " + @{reason}); };
+        }
+    }
+
+    fn update_editor(&mut self) {
+        let pressed = |input: &input::KeyState, key: input::KeyType| input.pressed(&key);
+        let cursor = self.editor.cursor;
+        let mut changed = false;
+
+        if pressed(&self.input, input::KeyType::Back) {
+            self.close_editor();
+            return;
+        }
+        if pressed(&self.input, input::KeyType::PlayTest) {
+            self.start_test();
+            return;
+        }
+
+        // Keyboard
+        let moves = [(input::KeyType::Left, (-1, 0)), (input::KeyType::Right, (1, 0)),
+                     (input::KeyType::Up, (0, -1)), (input::KeyType::Down, (0, 1))];
+        for &(ref key, delta) in moves.iter() {
+            if self.input.pressed(key) {
+                self.editor.move_cursor(delta);
+            }
+        }
+        if pressed(&self.input, input::KeyType::S) || pressed(&self.input, input::KeyType::Enter) {
+            if self.editor.paint(cursor) {
+                changed = true;
+            } else if self.editor.level.block_at(cursor) != Some(&self.editor.brush.block(cursor)) {
+                self.notify("editor.too_many_kinds");
+            }
+        }
+        if pressed(&self.input, input::KeyType::Erase) {
+            changed |= self.editor.erase(cursor);
+        }
+        if pressed(&self.input, input::KeyType::Recolor) {
+            if self.editor.recolor(cursor) {
+                changed = true;
+            } else if self.editor.level.block_at(cursor)
+                .map_or(false, |block| block.color != self.editor.brush.color) {
+                self.notify("editor.too_many_kinds");
+            }
+        }
+        if pressed(&self.input, input::KeyType::Pick) {
+            self.editor.pick(cursor);
+        }
+        if pressed(&self.input, input::KeyType::NextKind) {
+            self.editor.next_kind(true);
+        }
+        if pressed(&self.input, input::KeyType::PrevColor) {
+            self.editor.next_color(false);
+        }
+        if pressed(&self.input, input::KeyType::NextColor) {
+            self.editor.next_color(true);
+        }
+        if pressed(&self.input, input::KeyType::MoreHp) {
+            self.editor.change_hp(true);
+        }
+        if pressed(&self.input, input::KeyType::LessHp) {
+            self.editor.change_hp(false);
+        }
+        if pressed(&self.input, input::KeyType::Undo) {
+            changed |= self.editor.undo();
+        }
+        if pressed(&self.input, input::KeyType::Redo) {
+            changed |= self.editor.redo();
+        }
+        if pressed(&self.input, input::KeyType::Rename) {
+            changed |= self.rename_level();
+        }
+        if pressed(&self.input, input::KeyType::Export) {
+            self.export_level();
+        }
+
+        // Mouse: the main button paints, the other one erases, and both
+        // keep going while dragged along
+        if let Some(pos) = self.pointer.pos {
+            let cell = self.game.cell_at(pos);
+            if let Some(cell) = cell {
+                if self.pointer.moved {
+                    self.editor.cursor = cell;
+                }
+            }
+
+            let (paint, erase) = (input::POINTER_PRIMARY, input::POINTER_SECONDARY);
+            if self.pointer.pressed(paint) || self.pointer.pressed(erase) {
+                self.editor.begin_stroke();
+                let swatch = self.editor_swatches().iter()
+                    .position(|&(corner, size)| pos.0 >= corner.0 && pos.1 >= corner.1
+                              && pos.0 < corner.0 + size.0 && pos.1 < corner.1 + size.1);
+                if let Some(swatch) = swatch {
+                    self.editor.set_color(BRUSH_COLORS[swatch]);
                 }
-                if final_collision.vector.1 != 0.0
-                    && ball_spd.1.signum() != final_collision.vector.1.signum() {
-                    ball_spd.1 *= -1.0;
+            }
+            if let Some(cell) = cell {
+                if self.pointer.held(paint) {
+                    changed |= self.editor.paint(cell);
+                } else if self.pointer.held(erase) {
+                    changed |= self.editor.erase(cell);
                 }
-                self.ball_state.spd = ball_spd;
             }
-            
-            
-        } // End of pausable events
+        }
+        if self.pointer.released(input::POINTER_PRIMARY) || self.pointer.released(input::POINTER_SECONDARY) {
+            self.editor.end_stroke();
+        }
 
-        // Give input to old
-        self.input.old = self.input.new.clone();
-        if self.tilt.active {
-            self.tilt.old = self.tilt.new.clone();
+        if changed {
+            self.game.levels = self.editor.level_set();
+            self.game.setup_blocks();
+            self.save_editor();
         }
     }
 
+    // Asks for a new name, the browser's own way
+    fn rename_level(&mut self) -> bool {
+        let name: String = js! {
+            var name = window.prompt(@{self.locale.text("editor.rename")}, @{&self.editor.level.name});
+            return name || "";
+        }.try_into().unwrap();
+        // Keys released while the prompt was up never made it here
+        self.input.async.clear();
+        self.editor.rename(&name)
+    }
+
+    // Downloads the level as a file
+    fn export_level(&mut self) {
+        let text = match self.editor.export() {
+            Ok(text) => text,
+            Err(_) => {
+                self.notify("editor.too_many_kinds");
+                return;
+            },
+        };
+        let file = format!("{}.txt", self.editor.level.name.to_lowercase().replace(' ', "-"));
+        js! {
+            var blob = new Blob([@{text}], { type: "text/plain" });
+            var link = document.createElement("a");
+            link.href = URL.createObjectURL(blob);
+            link.download = @{file};
+            document.body.appendChild(link);
+            link.click();
+            document.body.removeChild(link);
+            setTimeout(function () { URL.revokeObjectURL(link.href); }, 0);
+        };
+    }
+
+    // NOTE: Browsers only open file pickers from event handlers.
+    // The file comes back through level_file_loaded().
+    pub fn open_level_file(&mut self) {
+        let editing = match self.scene {
+            Scene::Editor => true,
+            _ => false,
+        };
+        if !editing {
+            return;
+        }
+        js! {
+            var picker = document.createElement("input");
+            picker.type = "file";
+            picker.accept = ".txt,text/plain";
+            picker.addEventListener("change", function () {
+                if (!picker.files.length) {
+                    return;
+                }
+                var reader = new FileReader();
+                reader.onload = function () {
+                    Module.exports.levelFileCallback(reader.result);
+                };
+                reader.readAsText(picker.files[0]);
+            }, false);
+            picker.click();
+        };
+    }
+
+    pub fn level_file_loaded(&mut self, text: &str) {
+        match self.editor.import(text) {
+            Ok(()) => {
+                self.game.levels = self.editor.level_set();
+                self.game.setup_blocks();
+                self.save_editor();
+                self.notify("editor.imported");
+            },
+            Err(reason) => {
+                let text = self.locale.format("editor.import_failed", &[("reason", &reason.to_uppercase())]);
+                self.show_notice(text);
+            },
+        }
+    }
+
+    // Colors to pick with the mouse, below the grid: (corner, size)
+    fn editor_swatches(&self) -> Vec<((f32, f32), (f32, f32))> {
+        let size = (self.game.block_size.0 * 0.75, self.game.block_size.1 * 0.75);
+        let gap = size.0 * 0.25;
+        let width = (BRUSH_COLORS.len() as f32 * (size.0 + gap)) - gap;
+        let left = (self.renderer.size.0 as f32 - width) / 2.0;
+        let top = self.renderer.size.1 as f32 * 19.0 / 24.0 - size.1;
+        (0..BRUSH_COLORS.len())
+            .map(|i| ((left + (i as f32 * (size.0 + gap)), top), size))
+            .collect()
+    }
+
 
 
     
 
+    // Whatever menu is on screen, for screen readers
+    fn menu_view(&self) -> Option<MenuView> {
+        let menu = |title: &str, items: Vec<String>, selected: Option<usize>| Some(MenuView {
+            title:    title.to_string(),
+            items:    items,
+            selected: selected,
+        });
+
+        let locale = &self.locale;
+        match self.scene {
+            Scene::Loading => menu(locale.text("loading"), vec![], None),
+            Scene::Title(selected) => {
+                let items = TitleItem::all(self.saved.is_some());
+                let selected = selected.min(items.len() - 1);
+                menu("SUPER BRICKBREAK", items.iter().map(|item| self.title_label(*item)).collect(),
+                     Some(selected))
+            },
+            Scene::HighScores(mode, highlight) => {
+                let table = self.scores.table(&self.game.levels.name, mode);
+                let items = if table.is_empty() {
+                    vec![locale.text("scores.empty").to_string()]
+                } else {
+                    table.iter().enumerate()
+                        .map(|(i, entry)| format!("{}. {} {}", i + 1, entry.name, entry.score))
+                        .collect()
+                };
+                menu(&locale.format("scores.menu", &[("mode", locale.mode(mode))]), items, highlight)
+            },
+            Scene::Options(selected) => {
+                let items = ALL_SETTINGS.iter()
+                    .map(|setting| locale.format("options.item", &[
+                        ("setting", &self.setting_label(*setting)),
+                        ("value", &self.setting_value(*setting)),
+                    ]))
+                    .collect();
+                menu(locale.text("options.title"), items, Some(selected))
+            },
+            Scene::NameEntry(ref entry) => {
+                let items = entry.name().chars().enumerate()
+                    .map(|(i, letter)| locale.format("entry.letter", &[
+                        ("number", &(i + 1).to_string()),
+                        ("letter", &letter.to_string()),
+                    ]))
+                    .collect();
+                menu(&locale.format("entry.new_high_score", &[("score", &entry.score.to_string())]),
+                     items, Some(entry.cursor))
+            },
+            Scene::Playing if self.pause => {
+                let mut items = vec![locale.text("pause.resume").to_string()];
+                if self.netplay.is_none() {
+                    let quit = if self.testing { "editor.quit" } else { "pause.quit" };
+                    items.push(locale.text(quit).to_string());
+                }
+                menu(locale.text("pause"), items, None)
+            },
+            Scene::Editor => {
+                let title = locale.format("editor.title", &[("name", &self.editor.level.name)]);
+                menu(&title, vec![self.editor_cell_line(), self.editor_brush_line()], None)
+            },
+            Scene::Playing => None,
+        }
+    }
 
     pub fn render(&self) {
-        self.renderer.clear();
+        self.renderer.clear(&self.theme.background);
+
+        match self.scene {
+            Scene::Loading => self.render_loading(),
+            Scene::Title(selected) => self.render_title(selected),
+            Scene::HighScores(mode, highlight) => self.render_scores(mode, highlight),
+            Scene::Options(selected) => self.render_options(selected),
+            Scene::Playing => self.render_game(),
+            Scene::Editor => self.render_editor(),
+            Scene::NameEntry(ref entry) => {
+                self.render_game();
+                self.render_name_entry(entry);
+            },
+        }
+
+        // Wrapped, and kept off the bottom edge however many lines it takes
+        if let Some((ref text, _)) = self.notice {
+            let block = TextBlock::new(self.renderer.text, Align::Center, text,
+                                       Some(self.menu_width()));
+            let bottom = self.renderer.size.1 as f32 * 23.0 / 24.0;
+            self.renderer.draw_text_block(&self.theme.accent,
+                                          (self.renderer.size.0 as f32 / 2.0, bottom - block.size.1),
+                                          &block);
+        }
+    }
+
+    fn render_game(&self) {
+        let ball_radius = self.game.ball_state.diameter / 2.0;
+        let flipped = self.view_flipped && self.is_watching();
 
-        let ball_radius = self.ball_state.diameter / 2.0;
+        self.renderer.begin_view(flipped, &self.camera.view());
 
         // Afterimages
-        let mut i: u8 = 0;
-        for &afterimage in &self.ball_state.afterimages {
-            let color = format!("#{:02X}{:02X}{:02X}", i, i, i);
-            let color = color.as_ref();
-            self.renderer.draw_circle(color, afterimage, ball_radius);
-            i += 13;
+        if !self.settings.reduce_motion {
+            // Older ones fade into the background
+            for (i, &afterimage) in self.game.ball_state.afterimages.iter().enumerate() {
+                self.renderer.set_alpha(i as f32 * 13.0 / 255.0);
+                self.renderer.draw_circle(&self.theme.afterimage, afterimage, ball_radius);
+            }
+            self.renderer.set_alpha(1.0);
         }
         
+        // Debris and sparks
+        for particle in self.particles.iter() {
+            let half = particle.size / 2.0;
+            self.renderer.set_alpha(particle.alpha());
+            self.renderer.draw_box(particle.color.as_ref(),
+                                   (particle.pos.0 - half, particle.pos.1 - half),
+                                   (particle.size, particle.size));
+        }
+        self.renderer.set_alpha(1.0);
+
         // Actual ball
         {
-            let sprite = &self.ball_state.sprite;
-            let pos = self.ball_state.pos;
-            let diameter = self.ball_state.diameter;
+            let sprite = &self.sprites.ball;
+            let pos = self.game.ball_state.pos;
+            let diameter = self.game.ball_state.diameter;
             self.renderer.draw_sphere(sprite, pos, diameter);
         }
         
-        // Paddle
-        {
-            let sprite = &self.paddle_state.sprite;
-            let pos = ( self.paddle_state.xpos - (self.paddle_state.sz.0 / 2.0),
-                        self.paddle_state.ypos );
-            let size = self.paddle_state.sz;
-            self.renderer.draw_paddle(sprite, pos, size);
+        // Paddles
+        for (i, paddle) in self.game.paddles.iter().enumerate() {
+            let sprite = &self.sprites.paddle;
+            let pos = ( paddle.xpos - (paddle.sz.0 / 2.0),
+                        paddle.ypos );
+            let size = paddle.sz;
+
+            // Paddles only get tinted when there's more than one of them,
+            // so players can tell theirs apart
+            let tint = if self.game.paddles.len() > 1 {
+                Some(self.theme.paddle(i))
+            } else {
+                None
+            };
+            self.renderer.draw_paddle(sprite, pos, size, tint);
+        }
+
+        // Blocks
+        for block in &self.game.level_blocks {
+            self.draw_block(block.kind, &block.color, block.hp, block.pos, self.game.block_size);
         }
 
-        // Testing tiles
-        for block in &self.level_blocks {
-            self.renderer.draw_tile(block.color.as_ref(),
-                                    block.pos,
-                                    self.block_size);
+        // Falling capsules
+        for powerup in &self.game.powerups {
+            let size = (self.game.block_size.0 / 2.0, self.game.block_size.1 / 2.0);
+            let letter = match powerup.effect {
+                Effect::Wide      => "W",
+                Effect::Slow      => "S",
+                Effect::ExtraLife => "1UP",
+            };
+            self.renderer.draw_box(self.theme.effect_color(powerup.effect),
+                                   (powerup.pos.0 - (size.0 / 2.0), powerup.pos.1 - (size.1 / 2.0)),
+                                   size);
+            self.renderer.draw_text(&self.theme.block_text, "center",
+                                    (powerup.pos.0, powerup.pos.1 + (size.1 / 4.0)),
+                                    letter);
         }
 
-        // FPS
-        self.renderer.draw_text("white", "left",
-                                (ball_radius, ball_radius + 4.0),
-                                format!("FPS: {}", f64::floor(self.fps)).as_ref());
+        self.renderer.end_view();
 
-        // Copyright
-        self.renderer.draw_text("white", "right",
-                                ((self.renderer.size.0 as f32) - ball_radius, ball_radius + 8.0),
-                                "©2018 Lucas Vieira");
-        self.renderer.draw_text("white", "right",
-                                ((self.renderer.size.0 as f32) - ball_radius, (ball_radius * 3.0) + 8.0),
-                                "Prototype Version");
+        self.render_hud(flipped);
+
+        // Online status
+        if let Some(ref client) = self.netplay {
+            if let Some(status) = client.status(&self.locale) {
+                self.renderer.draw_text_fit(&self.theme.text, "center",
+                                            (self.renderer.size.0 as f32 / 2.0,
+                                             self.renderer.size.1 as f32 / 2.0 - (ball_radius * 3.0)),
+                                            status.as_ref(), self.menu_width());
+            }
+        }
 
         // Pause text
         if self.pause {
-            self.renderer.draw_text("white", "center",
-                                    (self.renderer.size.0 as f32 / 2.0, self.renderer.size.1 as f32 / 2.0),
-                                    "PAUSE");
+            self.renderer.draw_text_fit(&self.theme.text, "center",
+                                        (self.renderer.size.0 as f32 / 2.0, self.renderer.size.1 as f32 / 2.0),
+                                        self.locale.text("pause"), self.menu_width());
+            if self.netplay.is_none() {
+                let quit = if self.testing { "editor.quit" } else { "pause.quit" };
+                self.renderer.draw_text_fit(&self.theme.text, "center",
+                                            (self.renderer.size.0 as f32 / 2.0,
+                                             self.renderer.size.1 as f32 / 2.0 + (ball_radius * 3.0)),
+                                            self.locale.text(quit), self.menu_width());
+            }
         }
         
     }
+
+    // Tough blocks show how many hits they have left
+    fn draw_block(&self, kind: BlockKind, color: &str, hp: u32, pos: (f32, f32), size: (f32, f32)) {
+        self.renderer.draw_tile(self.theme.block_color(kind, color), pos, size, &self.theme.bevel);
+        if self.settings.block_patterns {
+            self.renderer.draw_pattern(Pattern::for_block(kind), &self.theme.block_text, pos, size);
+        }
+        if kind.is_breakable() && hp > 1 {
+            self.renderer.draw_text(&self.theme.block_text, "center",
+                                    (pos.0, pos.1 + (size.1 / 4.0)),
+                                    format!("{}", hp).as_ref());
+        }
+    }
+
+    fn block_label(&self, kind: BlockKind, hp: u32, color: &str) -> String {
+        let kind = match kind {
+            BlockKind::Normal => self.locale.text("kind.normal"),
+            BlockKind::Solid => self.locale.text("kind.solid"),
+            BlockKind::PowerUp(effect) => self.locale.effect(effect),
+        };
+        self.locale.format("editor.block", &[("kind", kind), ("hp", &hp.to_string()), ("color", color)])
+    }
+
+    fn editor_brush_line(&self) -> String {
+        let brush = &self.editor.brush;
+        let block = self.block_label(brush.kind, brush.hp, &brush.color);
+        self.locale.format("editor.brush", &[("block", &block)])
+    }
+
+    fn editor_cell_line(&self) -> String {
+        let cursor = self.editor.cursor;
+        let block = match self.editor.level.block_at(cursor) {
+            Some(block) => self.block_label(block.kind, block.hp, &block.color),
+            None => self.locale.text("editor.empty").to_string(),
+        };
+        self.locale.format("editor.cell", &[
+            ("column", &(cursor.0 + 1).to_string()),
+            ("row", &(cursor.1 + 1).to_string()),
+            ("block", &block),
+        ])
+    }
+
+    fn render_editor(&self) {
+        let size = self.game.block_size;
+        let title = self.locale.format("editor.title", &[("name", &self.editor.level.name)]);
+        self.draw_menu_line(&self.theme.text, 1.5, title.as_ref());
+        self.draw_menu_line(&self.theme.text, 2.5, self.editor_brush_line().as_ref());
+        self.draw_menu_line(&self.theme.text, 3.5, self.editor_cell_line().as_ref());
+
+        // Empty cells still show where blocks can go
+        for row in 0..GRID_ROWS {
+            for column in 0..GRID_COLUMNS {
+                let center = self.game.cell_position((column, row));
+                self.renderer.draw_outline(&self.theme.dim,
+                                           (center.0 - (size.0 / 2.0), center.1 - (size.1 / 2.0)),
+                                           size, 1.0);
+            }
+        }
+        for block in &self.editor.level.blocks {
+            let pos = self.game.cell_position(block.cell);
+            self.draw_block(block.kind, &block.color, block.hp, pos, size);
+        }
+        let cursor = self.game.cell_position(self.editor.cursor);
+        self.renderer.draw_outline(&self.theme.accent,
+                                   (cursor.0 - (size.0 / 2.0), cursor.1 - (size.1 / 2.0)),
+                                   size, 3.0);
+
+        // Palette, the brush's color outlined
+        for (i, &(pos, swatch)) in self.editor_swatches().iter().enumerate() {
+            self.renderer.draw_box(BRUSH_COLORS[i], pos, swatch);
+            if BRUSH_COLORS[i] == self.editor.brush.color {
+                self.renderer.draw_outline(&self.theme.accent, pos, swatch, 3.0);
+            }
+        }
+
+        self.draw_menu_line(&self.theme.text, 21.5, self.locale.text("editor.hint_edit"));
+        self.draw_menu_line(&self.theme.text, 22.5, self.locale.text("editor.hint_brush"));
+        self.draw_menu_line(&self.theme.text, 23.5, self.locale.text("editor.hint_file"));
+    }
+
+    // Everything over the field, anchored to the edges of the screen.
+    // Each anchor along an edge gets a third of it, and text shrinks
+    // to stay within that.
+    fn render_hud(&self, flipped: bool) {
+        let layout = Layout::new((self.renderer.size.0 as f32, self.renderer.size.1 as f32),
+                                 &self.renderer.text);
+        let width = layout.column_width();
+        let text = |color: &str, anchor: Anchor, line: u32, text: &str| {
+            self.renderer.draw_text_fit(color, anchor.align().name(), layout.text(anchor, line), text, width);
+        };
+        let locale = &self.locale;
+
+        if self.game.mode == GameMode::Versus {
+            // Each player's points sit by the edge they defend
+            let format = &self.game.versus.format;
+            let (top, bottom) = if flipped { (0, 1) } else { (1, 0) };
+            let points = |player: usize| locale.format("hud.points", &[
+                ("player", &(player + 1).to_string()),
+                ("points", &self.game.versus.points[player].to_string()),
+                ("target", &format.target.to_string()),
+            ]);
+            text(&self.theme.text, Anchor::Top, 0, points(top).as_ref());
+            text(&self.theme.text, Anchor::Bottom, 0, points(bottom).as_ref());
+
+            if let Some(winner) = self.game.versus.winner {
+                text(&self.theme.text, Anchor::Center, 2,
+                     locale.format("hud.winner", &[("player", &(winner + 1).to_string())]).as_ref());
+            }
+        } else {
+            text(&self.theme.text, Anchor::TopLeft, 0,
+                 locale.format("hud.score", &[("score", &format!("{:06}", self.game.score))]).as_ref());
+
+            // Lives left, as balls
+            let diameter = layout.line * 0.6;
+            let pos = layout.rect(Anchor::TopLeft, 1, (diameter, diameter));
+            let y = pos.1 + (diameter / 2.0);
+            let icons = self.game.lives.min(MAX_LIFE_ICONS);
+            for i in 0..icons {
+                let x = pos.0 + (i as f32 * diameter * 1.5) + (diameter / 2.0);
+                self.renderer.draw_sphere(&self.sprites.ball, (x, y), diameter);
+            }
+            if self.game.lives > MAX_LIFE_ICONS {
+                self.renderer.draw_text(&self.theme.text, "left",
+                                        (pos.0 + (icons as f32 * diameter * 1.5),
+                                         layout.text(Anchor::TopLeft, 1).1),
+                                        format!("+{}", self.game.lives - icons).as_ref());
+            }
+
+            text(&self.theme.text, Anchor::Top, 0, locale.format("hud.level", &[
+                ("mode", locale.mode(self.game.mode)),
+                ("level", &(self.game.level + 1).to_string()),
+            ]).as_ref());
+            text(&self.theme.text, Anchor::Top, 1, self.game.levels.get(self.game.level).name.as_ref());
+        }
+
+        // Power-up timers, each with a bar running out under its name
+        let bar_width = layout.line * 4.0;
+        for (i, timer) in hud::timers(&self.game).iter().enumerate() {
+            let line = 3 + (i as u32 * 2);
+            let seconds = format!("{:.0}", timer.seconds.ceil());
+            let label = if self.game.paddles.len() > 1 {
+                locale.format("hud.timer_player", &[
+                    ("player", &(timer.player + 1).to_string()),
+                    ("effect", locale.effect(timer.effect)),
+                    ("seconds", &seconds),
+                ])
+            } else {
+                locale.format("hud.timer", &[("effect", locale.effect(timer.effect)), ("seconds", &seconds)])
+            };
+            text(self.theme.effect_color(timer.effect), Anchor::TopLeft, line, label.as_ref());
+            let pos = layout.rect(Anchor::TopLeft, line + 1, (bar_width, layout.line / 4.0));
+            self.renderer.draw_box(&self.theme.dim, pos, (bar_width, layout.line / 4.0));
+            self.renderer.draw_box(self.theme.effect_color(timer.effect), pos, (bar_width * timer.left, layout.line / 4.0));
+        }
+
+        if let Some(combo) = self.hud.shown_combo() {
+            text(&self.theme.accent, Anchor::Right, 0,
+                 locale.format("hud.combo", &[("combo", &combo.to_string())]).as_ref());
+        }
+
+        if self.settings.show_fps {
+            text(&self.theme.text, Anchor::BottomRight, 0,
+                 locale.format("hud.fps", &[("fps", &f64::floor(self.fps).to_string())]).as_ref());
+        }
+
+        // Copyright
+        text(&self.theme.text, Anchor::TopRight, 0, "©2018 Lucas Vieira");
+        text(&self.theme.text, Anchor::TopRight, 1, locale.text("hud.prototype"));
+
+        if self.testing {
+            text(&self.theme.accent, Anchor::BottomLeft, 0, locale.text("editor.testing"));
+        }
+
+        // Spectator HUD
+        if let Some(ref client) = self.netplay {
+            if let Some(ref spectator) = client.spectator {
+                let lag = spectator.latest.saturating_sub(spectator.tick);
+                let state = locale.text(if lag > 30 { "hud.catching_up" } else { "hud.live" });
+                text(&self.theme.text, Anchor::BottomLeft, 1,
+                     locale.format("hud.watching", &[("room", &client.room), ("state", state)]).as_ref());
+                text(&self.theme.text, Anchor::BottomLeft, 0, locale.text("hud.switch_sides"));
+            }
+        }
+    }
+
+    // Widest a line of a menu may get
+    fn menu_width(&self) -> f32 {
+        self.renderer.size.0 as f32 * 0.9
+    }
+
+    // A line of a menu, centered on the screen. Lines are counted in
+    // 24ths of its height.
+    fn draw_menu_line(&self, color: &str, line: f32, text: &str) {
+        let pos = (self.renderer.size.0 as f32 / 2.0, self.renderer.size.1 as f32 * line / 24.0);
+        self.renderer.draw_text_fit(color, "center", pos, text, self.menu_width());
+    }
+
+    fn setting_label(&self, setting: Setting) -> String {
+        self.locale.text(&format!("setting.{}", setting.key())).to_string()
+    }
+
+    // As the options menu shows it
+    fn setting_value(&self, setting: Setting) -> String {
+        let value = self.settings.value(setting);
+        match setting {
+            Setting::Language => if value == AUTO_LANGUAGE {
+                self.locale.text("value.auto").to_string()
+            } else {
+                Locale::by_code(&value).map(|locale| locale.name).unwrap_or(value)
+            },
+            Setting::ColorVision => self.locale.text(&format!("vision.{}", value)).to_string(),
+            _ if value == "on" || value == "off" =>
+                self.locale.text(&format!("value.{}", value)).to_string(),
+            _ => value.to_uppercase(),
+        }
+    }
+
+    fn render_loading(&self) {
+        let center = self.renderer.size.0 as f32 / 2.0;
+        let line = self.renderer.size.1 as f32 / 24.0;
+        let loading = &self.assets.loading;
+
+        // Progress bar
+        let width = self.renderer.size.0 as f32 / 2.0;
+        self.draw_menu_line(&self.theme.text, 10.0, self.locale.text("loading"));
+        self.renderer.draw_box(&self.theme.dim, (center - (width / 2.0), line * 11.0), (width, line / 2.0));
+        self.renderer.draw_box(&self.theme.text, (center - (width / 2.0), line * 11.0),
+                               (width * loading.progress(), line / 2.0));
+
+        // Reasons can get long, so they're wrapped
+        let errors = loading.errors();
+        let messages: Vec<String> = errors.iter().map(|&(index, reason)| {
+            let asset = &self.assets.manifest.assets[index];
+            format!("{} {}: {}", asset.kind.name().to_uppercase(), asset.name, reason.to_uppercase())
+        }).collect();
+        let block = TextBlock::new(self.renderer.text, Align::Center, &messages.join("\n"),
+                                   Some(self.renderer.size.0 as f32 * 0.75));
+        self.renderer.draw_text_block(&self.theme.warning, (center, line * 13.0), &block);
+        if loading.is_done() && !errors.is_empty() {
+            self.renderer.draw_text_fit(&self.theme.text, "center", (center, (line * 14.0) + block.size.1),
+                                        self.locale.text("loading.continue"), self.menu_width());
+        }
+    }
+
+    fn title_label(&self, item: TitleItem) -> String {
+        let locale = &self.locale;
+        match item {
+            TitleItem::Play => locale.text("title.play").to_string(),
+            TitleItem::Resume => match self.saved {
+                Some(ref saved) => locale.format("title.resume_saved", &[("mode", locale.mode(saved.mode))]),
+                None => locale.text("title.resume").to_string(),
+            },
+            TitleItem::Mode => locale.format("title.mode", &[("mode", locale.mode(self.game.mode))]),
+            TitleItem::HighScores => locale.text("title.high_scores").to_string(),
+            TitleItem::Editor => locale.text("title.editor").to_string(),
+            TitleItem::Options => locale.text("title.options").to_string(),
+        }
+    }
+
+    fn render_title(&self, selected: usize) {
+        self.draw_menu_line(&self.theme.text, 8.0, "SUPER BRICKBREAK");
+        let items = TitleItem::all(self.saved.is_some());
+        let selected = selected.min(items.len() - 1);
+        for (i, item) in items.iter().enumerate() {
+            let (color, label) = if i == selected {
+                (&self.theme.accent, format!("> {} <", self.title_label(*item)))
+            } else {
+                (&self.theme.text, self.title_label(*item))
+            };
+            self.draw_menu_line(color, 12.0 + i as f32, label.as_ref());
+        }
+        self.draw_menu_line(&self.theme.text, 19.0, self.locale.text("title.hint"));
+        self.draw_menu_line(&self.theme.text, 23.0, "©2018 Lucas Vieira");
+    }
+
+    fn render_name_entry(&self, entry: &NameEntry) {
+        // Brackets mark the letter being picked
+        let name: String = entry.name().chars().enumerate()
+            .map(|(i, letter)| if i == entry.cursor { format!("[{}]", letter) } else { format!(" {} ", letter) })
+            .collect();
+
+        self.draw_menu_line(&self.theme.text, 10.0, self.locale.text("entry.game_over"));
+        self.draw_menu_line(&self.theme.text, 11.0,
+                            self.locale.format("entry.new_high_score",
+                                               &[("score", &entry.score.to_string())]).as_ref());
+        self.draw_menu_line(&self.theme.text, 13.0, name.as_ref());
+        self.draw_menu_line(&self.theme.text, 15.0, self.locale.text("entry.hint"));
+    }
+
+    fn render_scores(&self, mode: GameMode, highlight: Option<usize>) {
+        let table = self.scores.table(&self.game.levels.name, mode);
+
+        self.draw_menu_line(&self.theme.text, 4.0, self.locale.format("scores.title", &[
+            ("levels", &self.game.levels.name),
+            ("mode", self.locale.mode(mode)),
+        ]).as_ref());
+
+        if table.is_empty() {
+            self.draw_menu_line(&self.theme.text, 8.0, self.locale.text("scores.empty"));
+        }
+        for (i, entry) in table.iter().enumerate() {
+            let color = if Some(i) == highlight { &self.theme.accent } else { &self.theme.text };
+            self.draw_menu_line(color, 7.0 + i as f32,
+                                format!("{:2}. {} {:8}", i + 1, entry.name, entry.score).as_ref());
+        }
+
+        self.draw_menu_line(&self.theme.text, 20.0, self.locale.text("scores.hint"));
+    }
+
+    fn render_options(&self, selected: usize) {
+        self.draw_menu_line(&self.theme.text, 4.0, self.locale.text("options.title"));
+
+        for (i, setting) in ALL_SETTINGS.iter().enumerate() {
+            let color = if i == selected { &self.theme.accent } else { &self.theme.text };
+            let row = self.locale.format("options.row", &[
+                ("category", self.locale.text(&format!("category.{}", setting.category()))),
+                ("setting", &self.setting_label(*setting)),
+                ("value", &self.setting_value(*setting)),
+            ]);
+            self.draw_menu_line(color, 6.0 + i as f32, row.as_ref());
+        }
+
+        self.draw_menu_line(&self.theme.text, 22.0, self.locale.text("options.hint"));
+    }
 }
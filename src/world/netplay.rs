@@ -0,0 +1,193 @@
+use stdweb::Value;
+use stdweb::unstable::TryInto;
+
+use sim::{Game, GameEvent, GameMode, PlayerInput};
+use net::{Lockstep, Message, SessionConfig, Spectator};
+use locale::Locale;
+
+// How many ticks we may simulate on a single frame, when catching up
+// after remote inputs arrived late
+const MAX_CATCHUP: u32 = 2;
+
+// Spectators replay a tick per frame, like the players do. Once this many
+// ticks pile up (say, right after joining) they speed up to catch up.
+const SPECTATOR_SLACK: u64 = 8;
+const SPECTATOR_CATCHUP: u32 = 4;
+
+// Browser side of an online session.
+// Owns the WebSocket to the relay, and drives the lockstep session
+// once the relay says everyone is in. Spectators drive a read-only
+// session instead.
+pub struct NetClient {
+    socket:        Value,
+    pub room:      String,
+    mode:          GameMode,
+    field:         (f32, f32),
+    pub watching:  bool,
+    pub player:    Option<usize>,
+    pub session:   Option<Lockstep>,
+    pub spectator: Option<Spectator>,
+    pub closed:    bool,
+}
+
+impl NetClient {
+    // Socket events are forwarded to the callbacks exported in main.rs
+    pub fn connect(url: &str, room: &str, mode: GameMode, field: (f32, f32),
+                   watching: bool) -> NetClient {
+        let socket = js! {
+            var socket = new WebSocket(@{url});
+            socket.onopen = function () {
+                Module.exports.netOpenCallback();
+            };
+            socket.onmessage = function (e) {
+                Module.exports.netMessageCallback("" + e.data);
+            };
+            socket.onclose = function () {
+                Module.exports.netClosedCallback();
+            };
+            return socket;
+        };
+
+        NetClient {
+            socket:    socket,
+            room:      room.to_string(),
+            mode:      mode,
+            field:     field,
+            watching:  watching,
+            player:    None,
+            session:   None,
+            spectator: None,
+            closed:    false,
+        }
+    }
+
+    pub fn send(&self, message: &Message) {
+        let text = message.to_string();
+        js! {
+            var socket = @{&self.socket};
+            if (socket.readyState === WebSocket.OPEN) {
+                socket.send(@{text});
+            }
+        };
+    }
+
+    pub fn join(&self) {
+        if self.watching {
+            self.send(&Message::Watch(self.room.clone()));
+        } else {
+            self.send(&Message::Join(self.room.clone()));
+        }
+    }
+
+    pub fn closed(&mut self) {
+        self.closed = true;
+    }
+
+    // Handles a message from the relay. Returns the session config
+    // when a match is about to start.
+    pub fn receive(&mut self, game: &mut Game, message: &Message) -> Option<SessionConfig> {
+        match *message {
+            Message::Welcome(player) => {
+                self.player = Some(player);
+
+                // Whoever gets the first seat decides how the match is played
+                if player == 0 {
+                    let seed: f64 = js!( return Date.now(); ).try_into().unwrap();
+                    self.send(&Message::Config(SessionConfig {
+                        seed:  seed as u64,
+                        mode:  self.mode,
+                        field: self.field,
+                    }));
+                }
+            },
+            Message::Start(ref config) => {
+                if self.watching {
+                    self.spectator = Some(Spectator::new(config));
+                    return Some(config.clone());
+                }
+                if let Some(player) = self.player {
+                    self.session = Some(Lockstep::new(player, config));
+                    return Some(config.clone());
+                }
+            },
+            Message::Error(ref reason) => {
+                js! { console.log("Relay error: " + @{reason}); };
+            },
+            _ => {
+                if let Some(ref mut session) = self.session {
+                    session.receive(message);
+                }
+                if let Some(ref mut spectator) = self.spectator {
+                    spectator.receive(game, message);
+                }
+            },
+        }
+        None
+    }
+
+    // Events of every tick stepped go on the end of `events`
+    pub fn update(&mut self, game: &mut Game, input: PlayerInput, events: &mut Vec<GameEvent>) {
+        let outbox = match self.session {
+            Some(ref mut session) => {
+                session.submit(input);
+                let mut steps = 0;
+                while steps < MAX_CATCHUP && session.advance(game) {
+                    events.extend(game.events.iter().cloned());
+                    steps += 1;
+                }
+                session.drain_outbox()
+            },
+            None => return,
+        };
+
+        for message in &outbox {
+            self.send(message);
+        }
+    }
+
+    // Replays whatever the players have sent so far. Events go on the
+    // end of `events`, as with update().
+    pub fn watch(&mut self, game: &mut Game, events: &mut Vec<GameEvent>) {
+        if let Some(ref mut spectator) = self.spectator {
+            let budget = if spectator.behind() > SPECTATOR_SLACK {
+                SPECTATOR_CATCHUP
+            } else {
+                1
+            };
+
+            let mut steps = 0;
+            while steps < budget && spectator.advance(game) {
+                events.extend(game.events.iter().cloned());
+                steps += 1;
+            }
+        }
+    }
+
+    // What to tell the player, if anything
+    pub fn status(&self, locale: &Locale) -> Option<String> {
+        let left = |player: usize| locale.format("net.player_left", &[("player", &(player + 1).to_string())]);
+        if self.closed {
+            return Some(locale.text("net.connection_lost").to_string());
+        }
+
+        if self.watching {
+            return match self.spectator {
+                None => Some(locale.text("net.waiting_match").to_string()),
+                Some(ref spectator) => spectator.left.map(left),
+            };
+        }
+
+        match self.session {
+            None => Some(locale.text("net.waiting_opponent").to_string()),
+            Some(ref session) => {
+                if let Some(player) = session.left {
+                    Some(left(player))
+                } else if session.desync.is_some() {
+                    Some(locale.text("net.out_of_sync").to_string())
+                } else {
+                    None
+                }
+            },
+        }
+    }
+}
@@ -1,35 +1,184 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
 use stdweb::web::Element;
 use stdweb::Value;
 use stdweb::unstable::TryInto;
 
+use fx::CameraView;
+use text::{self, Align, TextStyle, TextBlock, GOHUFONT, ATLAS_CHARS};
+use theme::Pattern;
+use world::state::Sprite;
+
 pub struct Renderer {
     context: Value,
+    // Offscreen canvas used for tinting sprites
+    tint_buffer: Value,
+    // Size everything is drawn at, which is the size of the field.
+    // It gets scaled to fit the canvas, keeping its aspect ratio, and
+    // whatever is left over on the canvas is left black.
     pub size:    (u32, u32),
+    // Actual size of the canvas, in pixels
+    pub canvas_size: (u32, u32),
+    scale:       f32,
+    offset:      (f32, f32),
+    pub text:    TextStyle,
+    // Bitmap of every glyph in white, once the font is in. Until then,
+    // text goes through the canvas' own text drawing.
+    atlas:       Value,
+    // Copies of the atlas in each color asked for so far
+    tinted:      RefCell<HashMap<String, Value>>,
+    // High contrast drawing: tiles are flat and outlined instead of
+    // shaded, and text gets a backing of this color behind it
+    pub flat:    bool,
+    pub backing: Option<String>,
 }
 
 impl Renderer {
-    pub fn new(canvas: &Element) -> Renderer {
+    pub fn new(canvas: &Element, size: (u32, u32)) -> Renderer {
         Renderer {
             context: js!( return @{&canvas}.getContext("2d"); ),
-            size: {
-                let sz: (u32, u32) = (js!( return window.innerWidth ).try_into().unwrap(),
-                                      js!( return window.innerHeight ).try_into().unwrap());
-                ( if sz.0 > 1280 { 1280 } else { sz.0 },
-                  if sz.1 > 720  { 720  } else { sz.1 }  )
-            },
+            tint_buffer: js!( return document.createElement("canvas"); ),
+            size:        size,
+            canvas_size: size,
+            scale:       1.0,
+            offset:      (0.0, 0.0),
+            text:        TextStyle::new(GOHUFONT, GOHUFONT.cell.1 as f32),
+            atlas:       Value::Null,
+            tinted:      RefCell::new(HashMap::new()),
+            flat:        false,
+            backing:     None,
         }
     }
 
-    pub fn load_font(&self, font: &'static str, size: u32) {
-        let real_size: u32 = (size as f32 * self.size.0 as f32 / 720.0) as u32;
+    // Size of the browser window, up to the given maximum
+    pub fn window_size(max_size: (u32, u32)) -> (u32, u32) {
+        let sz: (u32, u32) = (js!( return window.innerWidth ).try_into().unwrap(),
+                              js!( return window.innerHeight ).try_into().unwrap());
+        ( if sz.0 > max_size.0 { max_size.0 } else { sz.0 },
+          if sz.1 > max_size.1 { max_size.1 } else { sz.1 } )
+    }
+
+    // Where a point on the canvas (in pixels) is on the drawing area
+    pub fn to_field(&self, pos: (f32, f32)) -> (f32, f32) {
+        ((pos.0 - self.offset.0) / self.scale,
+         (pos.1 - self.offset.1) / self.scale)
+    }
+
+    // Letterboxes (or pillarboxes) a drawing area of the given size
+    // onto a canvas of canvas_size pixels
+    pub fn fit(&mut self, canvas: &Element, canvas_size: (u32, u32), size: (u32, u32)) {
+        self.size = size;
+        self.canvas_size = canvas_size;
+
+        let scale = (canvas_size.0 as f32 / size.0 as f32)
+            .min(canvas_size.1 as f32 / size.1 as f32);
+        self.scale = scale;
+        self.offset = ((canvas_size.0 as f32 - (size.0 as f32 * scale)) / 2.0,
+                       (canvas_size.1 as f32 - (size.1 as f32 * scale)) / 2.0);
+
+        // Resizing the canvas resets everything on its context
+        js!( @{canvas}.width = @{canvas_size.0};
+             @{canvas}.height = @{canvas_size.1}; );
+        let size = self.text.size as u32;
+        self.set_text_size(size);
+    }
+
+    // In pixels of the drawing area
+    pub fn set_text_size(&mut self, size: u32) {
+        self.text.size = size as f32;
         js! {
-            @{&self.context}.font = @{real_size} + "px " + @{font};
+            @{&self.context}.font = @{size} + "px " + @{self.text.metrics.family} + ", monospace";
+        };
+    }
+
+    // Draws every glyph onto the atlas at the font's own size, where it
+    // lines up with the pixel grid. Only worth calling once the font
+    // has loaded, since whatever font stands in for it won't fit the
+    // cells.
+    pub fn build_atlas(&mut self) {
+        let metrics = self.text.metrics;
+        let size = text::atlas_size(&metrics);
+        self.atlas = js! {
+            var atlas = document.createElement("canvas");
+            atlas.width = @{size.0};
+            atlas.height = @{size.1};
+            var ctx = atlas.getContext("2d");
+            ctx.font = @{metrics.cell.1} + "px " + @{metrics.family};
+            ctx.fillStyle = "white";
+            var chars = Array.from(@{ATLAS_CHARS});
+            for (var i = 0; i < chars.length; i++) {
+                var column = i % @{text::ATLAS_COLUMNS};
+                var row = Math.floor(i / @{text::ATLAS_COLUMNS});
+                ctx.fillText(chars[i], column * @{metrics.cell.0},
+                             (row * @{metrics.cell.1}) + @{metrics.baseline});
+            }
+            return atlas;
         };
+        self.tinted.borrow_mut().clear();
     }
 
-    pub fn clear(&self) {
+    // The atlas in some color, which gets made the first time around
+    fn atlas_in(&self, color: &str) -> Value {
+        if let Some(atlas) = self.tinted.borrow().get(color) {
+            return atlas.clone();
+        }
+        let atlas = js! {
+            var source = @{&self.atlas};
+            var atlas = document.createElement("canvas");
+            atlas.width = source.width;
+            atlas.height = source.height;
+            var ctx = atlas.getContext("2d");
+            ctx.drawImage(source, 0, 0);
+            ctx.globalCompositeOperation = "source-in";
+            ctx.fillStyle = @{color};
+            ctx.fillRect(0, 0, atlas.width, atlas.height);
+            return atlas;
+        };
+        self.tinted.borrow_mut().insert(color.to_string(), atlas.clone());
+        atlas
+    }
+
+    // Also sets up scaling for the frame. Nothing gets drawn out of
+    // the drawing area, so the bars around it stay black.
+    pub fn clear(&self, background: &str) {
         js! {
-            @{&self.context}.clearRect(0, 0, @{&self.size.0}, @{&self.size.1});
+            var ctx = @{&self.context};
+            ctx.setTransform(1, 0, 0, 1, 0, 0);
+            ctx.clearRect(0, 0, @{&self.canvas_size.0}, @{&self.canvas_size.1});
+            ctx.setTransform(@{self.scale}, 0, 0, @{self.scale},
+                             @{self.offset.0}, @{self.offset.1});
+            ctx.beginPath();
+            ctx.rect(0, 0, @{&self.size.0}, @{&self.size.1});
+            ctx.clip();
+            ctx.fillStyle = @{background};
+            ctx.fillRect(0, 0, @{&self.size.0}, @{&self.size.1});
+        };
+    }
+
+    // Everything drawn until end_view() is seen from the given side,
+    // through the camera. Flipped, the top of the field ends up at the
+    // bottom of the screen.
+    pub fn begin_view(&self, flipped: bool, camera: &CameraView) {
+        let back = (-camera.center.0, -camera.center.1);
+        js! {
+            var ctx = @{&self.context};
+            ctx.save();
+            if (@{flipped}) {
+                ctx.translate(@{&self.size.0}, @{&self.size.1});
+                ctx.rotate(Math.PI);
+            }
+
+            ctx.translate(@{camera.offset.0}, @{camera.offset.1});
+            ctx.translate(@{camera.center.0}, @{camera.center.1});
+            ctx.rotate(@{camera.angle});
+            ctx.scale(@{camera.zoom}, @{camera.zoom});
+            ctx.translate(@{back.0}, @{back.1});
+        };
+    }
+
+    pub fn end_view(&self) {
+        js! {
+            @{&self.context}.restore();
         };
     }
 
@@ -40,6 +189,13 @@ impl Renderer {
 
     // Primitives
 
+    // Opacity for whatever is drawn next
+    pub fn set_alpha(&self, alpha: f32) {
+        js! {
+            @{&self.context}.globalAlpha = @{alpha};
+        };
+    }
+
     pub fn draw_box(&self, color: &str, pos: (f32, f32), sz: (f32, f32)) {
         js!{
             @{&self.context}.beginPath();
@@ -49,6 +205,18 @@ impl Renderer {
         };
     }
 
+    // Just the edges, drawn inside the box
+    pub fn draw_outline(&self, color: &str, pos: (f32, f32), sz: (f32, f32), width: f32) {
+        js! {
+            var ctx = @{&self.context};
+            var width = @{width};
+            ctx.strokeStyle = @{color};
+            ctx.lineWidth = width;
+            ctx.strokeRect(@{pos.0} + (width / 2), @{pos.1} + (width / 2),
+                           @{sz.0} - width, @{sz.1} - width);
+        };
+    }
+
     pub fn draw_circle(&self, color: &str, pos: (f32, f32), radius: f32) {
         js! {
             @{&self.context}.beginPath();
@@ -59,7 +227,24 @@ impl Renderer {
         };
     }
 
-    pub fn draw_tile(&self, color: &str, pos: (f32, f32), size: (f32, f32)) {
+    // Bevel shades go on the (lower, side) faces. Flat tiles get
+    // outlined in the first shade instead.
+    pub fn draw_tile(&self, color: &str, pos: (f32, f32), size: (f32, f32),
+                     bevel: &(String, String)) {
+        if self.flat {
+            js! {
+                var ctx = @{&self.context};
+                var left = @{pos.0 - (size.0 / 2.0)};
+                var top = @{pos.1 - (size.1 / 2.0)};
+                ctx.fillStyle = @{color};
+                ctx.fillRect(left, top, @{size.0}, @{size.1});
+                ctx.strokeStyle = @{&bevel.0};
+                ctx.lineWidth = 2;
+                ctx.strokeRect(left + 1, top + 1, @{size.0 - 2.0}, @{size.1 - 2.0});
+            };
+            return;
+        }
+
         js! (
             var ctx = @{&self.context};
             var pos_x = @{pos.0};
@@ -86,7 +271,7 @@ impl Renderer {
             ctx.lineTo(pos_x, pos_y);
             ctx.lineTo(pos_x + size_x,
                        pos_y + size_y);
-            ctx.fillStyle = "#666";
+            ctx.fillStyle = @{&bevel.0};
             ctx.fill();
             ctx.closePath();
 
@@ -97,7 +282,7 @@ impl Renderer {
             ctx.lineTo(pos_x, pos_y);
             ctx.lineTo(pos_x - size_x,
                        pos_y + size_y);
-            ctx.fillStyle = "#AAA";
+            ctx.fillStyle = @{&bevel.1};
             ctx.fill();
             ctx.closePath();
 
@@ -108,7 +293,7 @@ impl Renderer {
             ctx.lineTo(pos_x, pos_y);
             ctx.lineTo(pos_x + size_x,
                        pos_y + size_y);
-            ctx.fillStyle = "#AAA";
+            ctx.fillStyle = @{&bevel.1};
             ctx.fill();
             ctx.closePath();
         );
@@ -117,37 +302,251 @@ impl Renderer {
     
 
 
-    // Game objects
-    
-    pub fn draw_paddle(&self, sprite: &Value, pos: (f32, f32), size: (f32, f32)) {
+    // Marks a tile centered on pos with a pattern, in lines a tenth
+    // of its height thick
+    pub fn draw_pattern(&self, pattern: Pattern, color: &str, pos: (f32, f32), size: (f32, f32)) {
+        let (half_w, half_h) = (size.0 / 2.0, size.1 / 2.0);
+        // Kept clear of the edges, where the bevel is
+        let (inset_w, inset_h) = (half_w * 0.6, half_h * 0.6);
+        let (left, right) = (pos.0 - inset_w, pos.0 + inset_w);
+        let (top, bottom) = (pos.1 - inset_h, pos.1 + inset_h);
+
         js! {
-            @{&self.context}.drawImage(@{sprite},
-                                       @{pos.0}, @{pos.1},
-                                       @{size.0},
-                                       @{size.1});
+            var ctx = @{&self.context};
+            ctx.strokeStyle = @{color};
+            ctx.fillStyle = @{color};
+            ctx.lineWidth = @{size.1 / 10.0};
+            ctx.beginPath();
+        };
+        match pattern {
+            Pattern::Dot => js! {
+                // One in each corner, leaving the middle for hit points
+                var ctx = @{&self.context};
+                var radius = @{size.1 / 10.0};
+                [[@{left}, @{top}], [@{right}, @{top}], [@{left}, @{bottom}], [@{right}, @{bottom}]]
+                    .forEach(function (dot) {
+                        ctx.moveTo(dot[0] + radius, dot[1]);
+                        ctx.arc(dot[0], dot[1], radius, 0, Math.PI * 2.0);
+                    });
+                ctx.fill();
+            },
+            Pattern::Cross => js! {
+                var ctx = @{&self.context};
+                ctx.moveTo(@{left}, @{top});
+                ctx.lineTo(@{right}, @{bottom});
+                ctx.moveTo(@{right}, @{top});
+                ctx.lineTo(@{left}, @{bottom});
+                ctx.stroke();
+            },
+            Pattern::Arrows => js! {
+                // Pointing outwards, like the paddle is about to stretch
+                var ctx = @{&self.context};
+                var tip = @{inset_h};
+                ctx.moveTo(@{left} + tip, @{top});
+                ctx.lineTo(@{left}, @{pos.1});
+                ctx.lineTo(@{left} + tip, @{bottom});
+                ctx.moveTo(@{right} - tip, @{top});
+                ctx.lineTo(@{right}, @{pos.1});
+                ctx.lineTo(@{right} - tip, @{bottom});
+                ctx.stroke();
+            },
+            Pattern::Stripes => js! {
+                var ctx = @{&self.context};
+                [@{top}, @{pos.1}, @{bottom}].forEach(function (y) {
+                    ctx.moveTo(@{left}, y);
+                    ctx.lineTo(@{right}, y);
+                });
+                ctx.stroke();
+            },
+            Pattern::Plus => js! {
+                var ctx = @{&self.context};
+                ctx.moveTo(@{pos.0}, @{top});
+                ctx.lineTo(@{pos.0}, @{bottom});
+                ctx.moveTo(@{pos.0} - @{inset_h}, @{pos.1});
+                ctx.lineTo(@{pos.0} + @{inset_h}, @{pos.1});
+                ctx.stroke();
+            },
         };
     }
 
-    pub fn draw_sphere(&self, sprite: &Value, pos: (f32, f32), diameter: f32) {
+
+
+    // Game objects
+
+    // Whether a sprite's image is there to be drawn. Images that are
+    // still loading, or failed to, would draw nothing or even throw.
+    fn is_ready(&self, sprite: &Sprite) -> bool {
+        let ready: bool = js! {
+            var img = @{&sprite.image};
+            return !!img && img.complete && img.naturalWidth > 0;
+        }.try_into().unwrap();
+        ready
+    }
+
+    // Stretches a sprite over the given area of a context
+    fn draw_sprite_on(&self, context: &Value, sprite: &Sprite, pos: (f32, f32), size: (f32, f32)) {
+        match sprite.region {
+            Some((x, y, width, height)) => js! {
+                @{context}.drawImage(@{&sprite.image},
+                                     @{x}, @{y}, @{width}, @{height},
+                                     @{pos.0}, @{pos.1}, @{size.0}, @{size.1});
+            },
+            None => js! {
+                @{context}.drawImage(@{&sprite.image},
+                                     @{pos.0}, @{pos.1}, @{size.0}, @{size.1});
+            },
+        };
+    }
+    
+    pub fn draw_paddle(&self, sprite: &Sprite, pos: (f32, f32), size: (f32, f32),
+                       tint: Option<&str>) {
+        if !self.is_ready(sprite) {
+            self.draw_box(tint.unwrap_or("#CCC"), pos, size);
+            return;
+        }
+
+        match tint {
+            None => self.draw_sprite_on(&self.context, sprite, pos, size),
+            Some(color) => {
+                // Color only the sprite's opaque pixels, on a separate
+                // canvas, so whatever is behind it stays untouched
+                let buffer_size = (size.0.ceil(), size.1.ceil());
+                let buffer_context = js! {
+                    var buffer = @{&self.tint_buffer};
+                    buffer.width = @{buffer_size.0};
+                    buffer.height = @{buffer_size.1};
+                    return buffer.getContext("2d");
+                };
+                self.draw_sprite_on(&buffer_context, sprite, (0.0, 0.0), buffer_size);
+
+                js! {
+                    var buffer = @{&self.tint_buffer};
+                    var ctx = @{&buffer_context};
+                    ctx.globalCompositeOperation = "source-atop";
+                    ctx.globalAlpha = 0.5;
+                    ctx.fillStyle = @{color};
+                    ctx.fillRect(0, 0, buffer.width, buffer.height);
+                    ctx.globalAlpha = 1.0;
+                    ctx.globalCompositeOperation = "source-over";
+
+                    @{&self.context}.drawImage(buffer,
+                                               @{pos.0}, @{pos.1},
+                                               @{size.0},
+                                               @{size.1});
+                };
+            },
+        }
+    }
+
+    pub fn draw_sphere(&self, sprite: &Sprite, pos: (f32, f32), diameter: f32) {
+        if !self.is_ready(sprite) {
+            self.draw_circle("white", pos, diameter / 2.0);
+            return;
+        }
+
         let pos = (pos.0 - (diameter / 2.0),
                    pos.1 - (diameter / 2.0));
-        js! {
-            @{&self.context}.drawImage(@{sprite},
-                                       @{pos.0}, @{pos.1},
-                                       @{diameter},
-                                       @{diameter});
-        };
+        self.draw_sprite_on(&self.context, sprite, pos, (diameter, diameter));
     }
 
 
     
     // Miscellaneous
 
+    // Single line, sitting on a baseline at pos
     pub fn draw_text(&self, color: &str, align: &str, pos: (f32, f32), text: &str) {
+        if self.atlas == Value::Null && self.backing.is_none() {
+            self.fill_text(color, align, pos, text, None);
+            return;
+        }
+
+        let align = Align::from_name(align).unwrap_or(Align::Left);
+        let block = TextBlock::new(self.text, align, text, None);
+        self.draw_text_block(color, (pos.0, pos.1 - self.text.baseline()), &block);
+    }
+
+    // Same, but shrunk if it has to, so it's never wider than max_width.
+    // Translations can run much longer than the English they replace.
+    pub fn draw_text_fit(&self, color: &str, align: &str, pos: (f32, f32), text: &str,
+                         max_width: f32) {
+        let style = self.text.fit(text, max_width);
+        if style == self.text {
+            self.draw_text(color, align, pos, text);
+            return;
+        }
+        if self.atlas == Value::Null && self.backing.is_none() {
+            self.fill_text(color, align, pos, text, Some(max_width));
+            return;
+        }
+
+        let align = Align::from_name(align).unwrap_or(Align::Left);
+        let block = TextBlock::new(style, align, text, None);
+        self.draw_text_block(color, (pos.0, pos.1 - style.baseline()), &block);
+    }
+
+    // Through the canvas' own text drawing, for when there's no atlas.
+    // The canvas squeezes the text itself to fit max_width.
+    fn fill_text(&self, color: &str, align: &str, pos: (f32, f32), text: &str,
+                 max_width: Option<f32>) {
+        let max_width = max_width.unwrap_or(-1.0);
         js! {
-            @{&self.context}.fillStyle = @{color};
-            @{&self.context}.textAlign = @{align};
-            @{&self.context}.fillText(@{text}, @{pos.0}, @{pos.1});
+            var ctx = @{&self.context};
+            ctx.fillStyle = @{color};
+            ctx.textAlign = @{align};
+            if (@{max_width} > 0) {
+                ctx.fillText(@{text}, @{pos.0}, @{pos.1}, @{max_width});
+            } else {
+                ctx.fillText(@{text}, @{pos.0}, @{pos.1});
+            }
+        };
+    }
+
+    // Lines of text, whose top is at pos. Horizontally, pos is wherever
+    // the block is aligned to.
+    pub fn draw_text_block(&self, color: &str, pos: (f32, f32), block: &TextBlock) {
+        // Only behind the lines themselves, so the field still shows
+        // between them
+        if let Some(ref backing) = self.backing {
+            let left = match block.align {
+                Align::Left   => pos.0,
+                Align::Center => pos.0 - (block.size.0 / 2.0),
+                Align::Right  => pos.0 - block.size.0,
+            };
+            let height = block.style.line_height();
+            for (i, line) in block.lines.iter().enumerate() {
+                if line.width > 0.0 {
+                    self.draw_box(backing, (left + line.offset - 2.0, pos.1 + (i as f32 * height)),
+                                  (line.width + 4.0, height));
+                }
+            }
+        }
+
+        if self.atlas == Value::Null {
+            let baseline = block.style.baseline();
+            for (i, line) in block.lines.iter().enumerate() {
+                let y = pos.1 + (i as f32 * block.style.line_height()) + baseline;
+                self.fill_text(color, block.align.name(), (pos.0, y), &line.text, Some(block.size.0));
+            }
+            return;
+        }
+
+        // All at once, as (source x, y, width, height, then where to)
+        let mut quads: Vec<f32> = vec![];
+        for glyph in block.glyphs(pos) {
+            let (x, y, width, height) = glyph.region;
+            quads.extend_from_slice(&[x as f32, y as f32, width as f32, height as f32,
+                                      glyph.pos.0, glyph.pos.1, glyph.size.0, glyph.size.1]);
+        }
+        js! {
+            var ctx = @{&self.context};
+            var atlas = @{self.atlas_in(color)};
+            var quads = @{quads};
+            ctx.imageSmoothingEnabled = false;
+            for (var i = 0; i < quads.length; i += 8) {
+                ctx.drawImage(atlas, quads[i], quads[i + 1], quads[i + 2], quads[i + 3],
+                              quads[i + 4], quads[i + 5], quads[i + 6], quads[i + 7]);
+            }
+            ctx.imageSmoothingEnabled = true;
         };
     }
 }
@@ -0,0 +1,84 @@
+use sim::GameMode;
+use storage::scores::NAME_LENGTH;
+
+// What the player is looking at
+pub enum Scene {
+    // Waiting on the asset manifest
+    Loading,
+    // Title menu, with the selected item
+    Title(usize),
+    Playing,
+    // Game over with a score good enough for the table
+    NameEntry(NameEntry),
+    // Table for the given mode, with the rank just achieved highlighted
+    HighScores(GameMode, Option<usize>),
+    // Options menu, with the selected setting
+    Options(usize),
+    // Level editor, which keeps its state on the world
+    Editor,
+}
+
+// Entries on the title menu
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum TitleItem {
+    Play,
+    Resume,
+    Mode,
+    HighScores,
+    Editor,
+    Options,
+}
+
+impl TitleItem {
+    // Resuming only shows up with a game to resume
+    pub fn all(can_resume: bool) -> Vec<TitleItem> {
+        let mut items = vec![TitleItem::Play];
+        if can_resume {
+            items.push(TitleItem::Resume);
+        }
+        items.extend_from_slice(&[TitleItem::Mode, TitleItem::HighScores, TitleItem::Editor,
+                                  TitleItem::Options]);
+        items
+    }
+}
+
+const ALPHABET: &'static [u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789";
+
+// Arcade style name entry: left and right roll the current letter,
+// launch moves on to the next one
+pub struct NameEntry {
+    pub letters: [usize; NAME_LENGTH],
+    pub cursor:  usize,
+    pub mode:    GameMode,
+    pub score:   u32,
+}
+
+impl NameEntry {
+    pub fn new(mode: GameMode, score: u32) -> NameEntry {
+        NameEntry {
+            letters: [0; NAME_LENGTH],
+            cursor:  0,
+            mode:    mode,
+            score:   score,
+        }
+    }
+
+    pub fn roll(&mut self, forward: bool) {
+        let letter = &mut self.letters[self.cursor];
+        *letter = if forward {
+            (*letter + 1) % ALPHABET.len()
+        } else {
+            (*letter + ALPHABET.len() - 1) % ALPHABET.len()
+        };
+    }
+
+    // Returns true once every letter was picked
+    pub fn confirm(&mut self) -> bool {
+        self.cursor += 1;
+        self.cursor >= NAME_LENGTH
+    }
+
+    pub fn name(&self) -> String {
+        self.letters.iter().map(|&letter| ALPHABET[letter] as char).collect()
+    }
+}
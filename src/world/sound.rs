@@ -0,0 +1,197 @@
+use std::collections::HashMap;
+use stdweb::Value;
+
+use assets::{AssetKind, LoadState};
+use audio::{bank, AudioEvent, Sound, ALL_SOUNDS};
+use audio::synth::SAMPLE_RATE;
+use world::loader::Assets;
+
+// Plays whatever the audio queue asks for, through WebAudio.
+// Where WebAudio is missing, the context is null and nothing happens.
+pub struct AudioPlayer {
+    context:      Value,
+    music_gain:   Value,
+    effects_gain: Value,
+    // Decoded sounds, by name. Each one is an object whose `buffer`
+    // is filled in once decoding is done.
+    buffers:      HashMap<String, Value>,
+    // Track that should be playing, and the source playing it
+    track:        Option<String>,
+    music:        Option<Value>,
+}
+
+impl AudioPlayer {
+    pub fn new() -> AudioPlayer {
+        let context = js! {
+            var Context = window.AudioContext || window.webkitAudioContext;
+            return Context ? new Context() : null;
+        };
+        let (music_gain, effects_gain) = {
+            let gain = || js! {
+                var ctx = @{&context};
+                if (!ctx) {
+                    return null;
+                }
+                var gain = ctx.createGain();
+                gain.connect(ctx.destination);
+                return gain;
+            };
+            (gain(), gain())
+        };
+
+        AudioPlayer {
+            context:      context,
+            music_gain:   music_gain,
+            effects_gain: effects_gain,
+            buffers:      HashMap::new(),
+            track:        None,
+            music:        None,
+        }
+    }
+
+    // Browsers keep audio suspended until the player does something,
+    // so this gets called on every key press
+    pub fn resume(&self) {
+        js! {
+            var ctx = @{&self.context};
+            if (ctx && ctx.state === "suspended") {
+                ctx.resume();
+            }
+        };
+    }
+
+    pub fn set_volumes(&self, music: f32, effects: f32) {
+        js! {
+            if (@{&self.context}) {
+                @{&self.music_gain}.gain.value = @{music};
+                @{&self.effects_gain}.gain.value = @{effects};
+            }
+        };
+    }
+
+    // Decodes every sound that finished loading, and synthesizes the
+    // ones which have no file
+    pub fn decode(&mut self, assets: &Assets) {
+        let loaded = assets.manifest.assets.iter().zip(assets.loading.states.iter())
+            .filter(|&(asset, state)| asset.kind == AssetKind::Sound && *state == LoadState::Loaded);
+        for (asset, _) in loaded {
+            let file = match assets.sound(&asset.name) {
+                Some(file) => file,
+                None => continue,
+            };
+            let decoded = js! {
+                var decoded = { buffer: null };
+                var ctx = @{&self.context};
+                if (ctx) {
+                    // Decoding takes the data away, so it gets a copy
+                    ctx.decodeAudioData(@{file}.buffer.slice(0), function (buffer) {
+                        decoded.buffer = buffer;
+                    }, function () {
+                        console.log("Could not decode sound " + @{&asset.name});
+                    });
+                }
+                return decoded;
+            };
+            self.buffers.insert(asset.name.clone(), decoded);
+        }
+
+        for sound in ALL_SOUNDS.iter() {
+            if !self.buffers.contains_key(sound.name()) {
+                let buffer = self.buffer(bank::render_sound(*sound));
+                self.buffers.insert(sound.name().to_string(), buffer);
+            }
+        }
+        for name in bank::music_names() {
+            if !self.buffers.contains_key(&name) {
+                if let Some(samples) = bank::render_music(&name) {
+                    let buffer = self.buffer(samples);
+                    self.buffers.insert(name, buffer);
+                }
+            }
+        }
+    }
+
+    // Same shape as a decoded sound, already filled in
+    fn buffer(&self, samples: Vec<f32>) -> Value {
+        let length = samples.len() as u32;
+        js! {
+            var ctx = @{&self.context};
+            if (!ctx || @{length} == 0) {
+                return { buffer: null };
+            }
+            var buffer = ctx.createBuffer(1, @{length}, @{SAMPLE_RATE});
+            buffer.getChannelData(0).set(@{samples});
+            return { buffer: buffer };
+        }
+    }
+
+    pub fn play(&mut self, events: Vec<AudioEvent>) {
+        for event in events {
+            match event {
+                AudioEvent::Play(sound) => self.play_sound(sound),
+                AudioEvent::Music(track) => {
+                    self.stop_music();
+                    self.track = track;
+                },
+            }
+        }
+        self.start_music();
+    }
+
+    fn play_sound(&self, sound: Sound) {
+        let decoded = match self.buffers.get(sound.name()) {
+            Some(decoded) => decoded,
+            None => return,
+        };
+        js! {
+            var ctx = @{&self.context};
+            var buffer = @{decoded}.buffer;
+            if (!ctx || !buffer) {
+                return;
+            }
+            var source = ctx.createBufferSource();
+            source.buffer = buffer;
+            source.connect(@{&self.effects_gain});
+            source.start();
+        };
+    }
+
+    // Music may still be decoding when asked for, so this keeps
+    // trying on every frame until it gets going
+    fn start_music(&mut self) {
+        if self.music.is_some() {
+            return;
+        }
+        let decoded = match self.track {
+            Some(ref track) => match self.buffers.get(track) {
+                Some(decoded) => decoded,
+                None => return,
+            },
+            None => return,
+        };
+        let source = js! {
+            var ctx = @{&self.context};
+            var buffer = @{decoded}.buffer;
+            if (!ctx || !buffer) {
+                return null;
+            }
+            var source = ctx.createBufferSource();
+            source.buffer = buffer;
+            source.loop = true;
+            source.connect(@{&self.music_gain});
+            source.start();
+            return source;
+        };
+        if source != Value::Null {
+            self.music = Some(source);
+        }
+    }
+
+    fn stop_music(&mut self) {
+        if let Some(source) = self.music.take() {
+            js! {
+                @{source}.stop();
+            };
+        }
+    }
+}
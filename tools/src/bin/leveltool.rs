@@ -0,0 +1,178 @@
+// Works on level files without a browser, for checking levels before
+// they ship.
+//
+// Files may be on the text format or the packed one, whichever; they
+// are told apart by how they start.
+//
+// Usage: leveltool check <level>...
+//        leveltool render [--mode MODE] [--theme THEME] [--scale SCALE] <level> <png>
+//        leveltool convert <level> <output>
+//        leveltool generate [--difficulty DIFFICULTY] <seed> <output>
+//
+// Check prints every problem found and fails if there were any.
+// Convert and generate write the packed format to files ending in
+// .bbl, and the text format to anything else.
+
+extern crate brickbreak;
+
+use std::env;
+use std::fmt::Write;
+use std::fs;
+use std::process;
+
+use brickbreak::sim::{check_level, generate, GameMode, Level, PACKED_MAGIC, MIN_DIFFICULTY, MAX_DIFFICULTY};
+use brickbreak::editor::{png, preview};
+use brickbreak::theme::{Theme, DEFAULT_THEME};
+
+const USAGE: &'static str = "usage: leveltool check <level>...
+       leveltool render [--mode MODE] [--theme THEME] [--scale SCALE] <level> <png>
+       leveltool convert <level> <output>
+       leveltool generate [--difficulty DIFFICULTY] <seed> <output>";
+
+// Extension of packed level files
+const PACKED_EXTENSION: &'static str = ".bbl";
+
+fn read_level(path: &str) -> Result<Level, String> {
+    let data = fs::read(path).map_err(|e| format!("could not read {}: {}", path, e))?;
+    let level = if data.starts_with(PACKED_MAGIC) {
+        Level::unpack(&data)
+    } else {
+        String::from_utf8(data)
+            .map_err(|_| "not a level file".to_string())
+            .and_then(|text| Level::parse(&text))
+    };
+    level.map_err(|reason| format!("{}: {}", path, reason))
+}
+
+fn write(path: &str, data: &[u8]) -> Result<(), String> {
+    fs::write(path, data).map_err(|e| format!("could not write {}: {}", path, e))
+}
+
+// Returns whether every level passed
+fn check(paths: &[String]) -> Result<bool, String> {
+    if paths.is_empty() {
+        return Err(USAGE.to_string());
+    }
+
+    let mut passed = true;
+    for path in paths {
+        match read_level(path) {
+            Ok(level) => {
+                let problems = check_level(&level);
+                for problem in &problems {
+                    println!("{}: {}", path, problem);
+                }
+                if problems.is_empty() {
+                    println!("{}: ok", path);
+                }
+                passed &= problems.is_empty();
+            },
+            Err(reason) => {
+                println!("{}", reason);
+                passed = false;
+            },
+        }
+    }
+    Ok(passed)
+}
+
+fn render(args: &[String]) -> Result<(), String> {
+    let mut mode = GameMode::Single;
+    let mut theme = DEFAULT_THEME.to_string();
+    let mut scale = 0.5;
+    let mut paths = vec![];
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| args.next().cloned().ok_or_else(|| format!("{} needs a value", name));
+        match arg.as_ref() {
+            "--mode" => {
+                let name = value("--mode")?;
+                mode = GameMode::from_name(&name.to_uppercase())
+                    .ok_or_else(|| format!("unknown mode {}", name))?;
+            },
+            "--theme" => theme = value("--theme")?,
+            "--scale" => {
+                let text = value("--scale")?;
+                scale = match text.parse::<f32>() {
+                    Ok(scale) if scale > 0.0 => scale,
+                    _ => return Err(format!("invalid scale {}", text)),
+                };
+            },
+            _ => paths.push(arg.clone()),
+        }
+    }
+    if paths.len() != 2 {
+        return Err(USAGE.to_string());
+    }
+
+    let theme = Theme::by_name(&theme.to_uppercase()).ok_or_else(|| format!("unknown theme {}", theme))?;
+    let level = read_level(&paths[0])?;
+    let image = preview::render(&level, mode, &theme, scale);
+    write(&paths[1], &png::encode(image.size, &image.pixels))
+}
+
+// On whichever format the file's name asks for
+fn write_level(path: &str, level: &Level) -> Result<(), String> {
+    let data = if path.ends_with(PACKED_EXTENSION) {
+        level.pack()?
+    } else {
+        let mut text = String::new();
+        write!(text, "{}", level).map_err(|_| "too many kinds of block".to_string())?;
+        text.into_bytes()
+    };
+    write(path, &data)
+}
+
+fn convert(args: &[String]) -> Result<(), String> {
+    if args.len() != 2 {
+        return Err(USAGE.to_string());
+    }
+    let level = read_level(&args[0])?;
+    write_level(&args[1], &level).map_err(|reason| format!("{}: {}", args[0], reason))
+}
+
+fn generate_level(args: &[String]) -> Result<(), String> {
+    let mut difficulty = MIN_DIFFICULTY;
+    let mut rest = vec![];
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if arg == "--difficulty" {
+            let text = args.next().ok_or("--difficulty needs a value".to_string())?;
+            difficulty = match text.parse::<u32>() {
+                Ok(value) if value >= MIN_DIFFICULTY && value <= MAX_DIFFICULTY => value,
+                _ => return Err(format!("difficulty goes from {} to {}", MIN_DIFFICULTY, MAX_DIFFICULTY)),
+            };
+        } else {
+            rest.push(arg.clone());
+        }
+    }
+    if rest.len() != 2 {
+        return Err(USAGE.to_string());
+    }
+
+    write_level(&rest[1], &generate(&rest[0], difficulty))
+}
+
+fn run(args: &[String]) -> Result<bool, String> {
+    match args.first().map(|command| command.as_ref()) {
+        Some("check") => check(&args[1..]),
+        Some("render") => render(&args[1..]).map(|_| true),
+        Some("convert") => convert(&args[1..]).map(|_| true),
+        Some("generate") => generate_level(&args[1..]).map(|_| true),
+        _ => Err(USAGE.to_string()),
+    }
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    match run(&args) {
+        Ok(true) => {},
+        Ok(false) => process::exit(1),
+        Err(error) => {
+            eprintln!("leveltool: {}", error);
+            process::exit(1);
+        },
+    }
+}
@@ -0,0 +1,59 @@
+// Renders every sound effect and music track to WAV files, to listen
+// to them without running the game.
+//
+// Also prints a checksum of each one, so a change to the synthesizer
+// that shouldn't change how things sound can be checked against the
+// last run.
+//
+// Usage: soundgen [folder]
+
+extern crate brickbreak;
+
+use std::env;
+use std::fs;
+use std::process;
+
+use brickbreak::audio::{bank, wav, ALL_SOUNDS};
+use brickbreak::audio::synth::SAMPLE_RATE;
+
+// FNV-1a over the 16-bit samples, which is what ends up in the file
+fn checksum(data: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for &byte in data {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    hash
+}
+
+fn write(folder: &str, name: &str, samples: &[f32]) -> Result<(), String> {
+    let data = wav::encode(samples, SAMPLE_RATE);
+    let path = format!("{}/{}.wav", folder, name);
+    fs::write(&path, &data).map_err(|e| format!("could not write {}: {}", path, e))?;
+
+    let peak = samples.iter().fold(0.0f32, |peak, sample| peak.max(sample.abs()));
+    println!("{:<16} {:>6.2}s  peak {:.2}  {:016x}",
+             name, samples.len() as f32 / SAMPLE_RATE as f32, peak, checksum(&data[44..]));
+    Ok(())
+}
+
+fn run(folder: &str) -> Result<(), String> {
+    fs::create_dir_all(folder).map_err(|e| format!("could not create {}: {}", folder, e))?;
+
+    for sound in ALL_SOUNDS.iter() {
+        write(folder, sound.name(), &bank::render_sound(*sound))?;
+    }
+    for name in bank::music_names() {
+        let samples = bank::render_music(&name).expect("music track without notes");
+        write(folder, &name, &samples)?;
+    }
+    Ok(())
+}
+
+fn main() {
+    let folder = env::args().nth(1).unwrap_or("sounds".to_string());
+    if let Err(error) = run(&folder) {
+        eprintln!("soundgen: {}", error);
+        process::exit(1);
+    }
+}